@@ -0,0 +1,193 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use wayland_client::protocol::{wl_data_device_manager::DndAction, wl_pointer::ButtonState};
+
+use wayland_book_rust::data_device::{DataOffer, DropEvent};
+use wayland_book_rust::event_loop::LoopHandle;
+use wayland_book_rust::keybindings::{KeyMatch, Keybindings};
+use wayland_book_rust::output::{OutputEvent, OutputInfo};
+use wayland_book_rust::primary_selection::PrimarySelection;
+use wayland_book_rust::seat::{KeyEvent, PointerEvent, ShortcutsInhibitEvent, TextInputEvent, TouchEvent};
+use wayland_book_rust::Window;
+
+const TEXT: &str = "text/plain;charset=utf-8";
+const URI_LIST: &str = "text/uri-list";
+const BTN_MIDDLE: u32 = 0x112;
+
+pub fn print_selection(handle: LoopHandle) -> impl FnMut(Option<&DataOffer>) {
+    move |offer| match offer {
+        Some(offer) if offer.has_mime_type(TEXT) => {
+            let result = offer.receive(TEXT, &handle, |data| match data {
+                Ok(data) => eprintln!("selection: {}", String::from_utf8_lossy(&data)),
+                Err(error) => eprintln!("Failed to receive selection: {}", error),
+            });
+            if let Err(error) = result {
+                eprintln!("Failed to receive selection: {}", error);
+            }
+        }
+        Some(offer) => eprintln!("selection offers: {:?}", offer.mime_types()),
+        None => eprintln!("selection cleared"),
+    }
+}
+
+/// Accepts files dragged onto the surface and prints their URIs.
+pub fn print_drops(handle: LoopHandle) -> impl FnMut(DropEvent) {
+    move |event| match event {
+        DropEvent::Enter {
+            offer: Some(offer),
+            x,
+            y,
+        } => {
+            eprintln!("drag entered at {}, {} offering {:?}", x, y, offer.mime_types());
+            if offer.has_mime_type(URI_LIST) {
+                offer.accept(Some(URI_LIST));
+                let actions = offer.source_actions() & (DndAction::Copy | DndAction::Move);
+                offer.set_actions(actions, DndAction::Copy);
+            } else {
+                offer.accept(None);
+                offer.set_actions(DndAction::None, DndAction::None);
+            }
+        }
+        DropEvent::Drop { offer: Some(offer) } => {
+            if !offer.has_mime_type(URI_LIST) {
+                return;
+            }
+            eprintln!("dropped with action {:?}", offer.action());
+            let finished = offer.clone();
+            let result = offer.receive(URI_LIST, &handle, move |data| {
+                match data {
+                    Ok(data) => {
+                        for uri in String::from_utf8_lossy(&data).lines() {
+                            eprintln!("dropped: {}", uri);
+                        }
+                    }
+                    Err(error) => eprintln!("Failed to receive drop: {}", error),
+                }
+                finished.finish();
+            });
+            if let Err(error) = result {
+                eprintln!("Failed to receive drop: {}", error);
+            }
+        }
+        DropEvent::Motion {
+            offer: Some(_),
+            time,
+            x,
+            y,
+        } => eprintln!("drag motion @ {}: {}, {}", time, x, y),
+        DropEvent::Leave => eprintln!("drag left"),
+        _ => (),
+    }
+}
+
+pub fn paste_primary_on_middle_click(primary: PrimarySelection) -> impl FnMut(&PointerEvent) {
+    move |event| {
+        if let Some((BTN_MIDDLE, ButtonState::Pressed)) = event.button() {
+            let result = primary.receive_selection(TEXT, |data| match data {
+                Ok(data) => eprintln!("primary selection: {}", String::from_utf8_lossy(&data)),
+                Err(error) => eprintln!("Failed to receive primary selection: {}", error),
+            });
+            if let Err(error) = result {
+                eprintln!("Failed to receive primary selection: {}", error);
+            }
+        }
+    }
+}
+
+/// Prints the text typed through the input method so far, as it changes.
+pub fn print_text_input() -> impl FnMut(&TextInputEvent) {
+    let mut text = String::new();
+    let mut cursor = 0;
+    move |event| match event {
+        TextInputEvent::Enter(surface) => {
+            eprintln!("text input enter surface {}", surface.as_ref().id());
+        }
+        TextInputEvent::Leave(surface) => {
+            eprintln!("text input leave surface {}", surface.as_ref().id());
+        }
+        TextInputEvent::Change(change) => {
+            change.apply(&mut text, &mut cursor);
+            match &change.preedit {
+                Some(preedit) => eprintln!("text input: {} [{}]", text, preedit.text),
+                None => eprintln!("text input: {}", text),
+            }
+        }
+    }
+}
+
+pub fn print_pointer_frame(event: &PointerEvent) {
+    eprintln!("{}", event);
+}
+
+pub fn print_touch_frame(event: &TouchEvent) {
+    eprint!("{}", event);
+}
+
+pub fn print_key(event: &KeyEvent) {
+    let state = if event.pressed { "pressed" } else { "released" };
+    match event.keysym {
+        Some(sym) => eprint!("key {}: sym {} ({})", state, sym, sym.0),
+        None => eprint!("key {}: keycode {}", state, event.keycode),
+    }
+    match &event.utf8 {
+        Some(text) => eprintln!(", utf8 {:?}", text),
+        None => eprintln!(),
+    }
+}
+
+/// Prints the actions of the key bindings pressed.
+pub fn print_keybindings<F>(mut bindings: Keybindings<String>, mut run: F) -> impl FnMut(&KeyEvent)
+where
+    F: FnMut(&str),
+{
+    move |event| match bindings.handle(event) {
+        KeyMatch::Action(action) => {
+            eprintln!("key binding: {}", action);
+            run(&action);
+        }
+        KeyMatch::Pending => eprintln!("key binding: waiting for the rest of the chord"),
+        KeyMatch::Unbound => (),
+    }
+}
+
+/// Saves what `window` shows as `screenshot-<seconds since 1970>.png`.
+pub fn save_screenshot(window: &Window) {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = format!("screenshot-{}.png", seconds);
+    match window.screenshot(&path) {
+        Ok(true) => eprintln!("saved {}", path),
+        Ok(false) => eprintln!("nothing drawn yet to take a screenshot of"),
+        Err(error) => eprintln!("screenshot failed: {}", error),
+    }
+}
+
+pub fn print_shortcuts_inhibit(event: &ShortcutsInhibitEvent) {
+    match event {
+        ShortcutsInhibitEvent::Active(surface) => {
+            eprintln!("shortcuts inhibited on surface {}", surface.as_ref().id());
+        }
+        ShortcutsInhibitEvent::Inactive(surface) => {
+            eprintln!("shortcuts restored on surface {}", surface.as_ref().id());
+        }
+    }
+}
+
+pub fn print_outputs(event: &OutputEvent) {
+    fn print(change: &str, info: &OutputInfo) {
+        let mode = match info.current_mode() {
+            Some(mode) => format!("{}x{}@{}mHz", mode.width, mode.height, mode.refresh),
+            None => "no mode".to_owned(),
+        };
+        eprintln!(
+            "output {} {}: {} {} at {}, {}, {}, scale {}",
+            info.name, change, info.make, info.model, info.x, info.y, mode, info.scale
+        );
+    }
+    match event {
+        OutputEvent::Added(info) => print("added", info),
+        OutputEvent::Changed(info) => print("changed", info),
+        OutputEvent::Removed(info) => print("removed", info),
+    }
+}
@@ -0,0 +1,144 @@
+#![allow(clippy::single_match)]
+
+use std::cell::RefCell;
+use std::env;
+use std::rc::Rc;
+
+use wayland_client::{
+    protocol::{wl_data_device_manager::WlDataDeviceManager, wl_seat::WlSeat},
+    Interface,
+};
+
+use wayland_protocols::unstable::keyboard_shortcuts_inhibit::v1::client::zwp_keyboard_shortcuts_inhibit_manager_v1::ZwpKeyboardShortcutsInhibitManagerV1;
+use wayland_protocols::unstable::primary_selection::v1::client::zwp_primary_selection_device_manager_v1::ZwpPrimarySelectionDeviceManagerV1;
+use wayland_protocols::unstable::text_input::v3::client::{
+    zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+    zwp_text_input_v3::{ContentHint, ContentPurpose},
+};
+
+use wayland_book_rust::capture::Recorder;
+use wayland_book_rust::seat::{self, SeatChange};
+use wayland_book_rust::{
+    data_device, keybindings, output, primary_selection, trace, Checkerboard, Connection, Error,
+    Window,
+};
+
+mod debug_callbacks;
+
+fn main() -> Result<(), Error> {
+    trace::init_from_env()?;
+    let mut connection = Connection::connect()?;
+    let globals = connection.globals();
+
+    let data_device_manager = globals.bind_optional::<WlDataDeviceManager>(3..=3);
+    for global in globals.bound() {
+        eprintln!("Bound {} v{}", global.interface, global.version);
+    }
+    output::watch(globals, debug_callbacks::print_outputs);
+
+    let handle = connection.loop_handle();
+    let window = Rc::new(Window::new(&connection, "Example client", Checkerboard::new()));
+    // It scrolls on its own, so it wants every frame.
+    window.set_continuous(true);
+    // RECORD=video.y4m records a video, any other path a PNG per frame.
+    if let Some(path) = env::var_os("RECORD") {
+        let recorder = if path.to_string_lossy().ends_with(".y4m") {
+            Recorder::y4m(path, 60)
+        } else {
+            Recorder::png_sequence(path)
+        };
+        window.record(recorder);
+    }
+    let surface = window.surface();
+
+    // Seats may come and go. The first one gets the clipboard, key bindings
+    // and input methods, the others only have their input printed.
+    let first_seat = Rc::new(RefCell::new(None));
+    seat::watch(globals, {
+        let first_seat = first_seat.clone();
+        move |change| match change {
+            SeatChange::Added(name, seat) => {
+                eprintln!("seat {} added", name);
+                seat.on_pointer_frame(debug_callbacks::print_pointer_frame);
+                seat.on_key(debug_callbacks::print_key);
+                seat.on_touch_frame(debug_callbacks::print_touch_frame);
+                first_seat.borrow_mut().get_or_insert_with(|| seat.clone());
+            }
+            SeatChange::Removed(name, seat) => {
+                eprintln!("seat {} removed", name);
+                let first_seat = first_seat.borrow();
+                let first = first_seat.as_ref().map(|first| first.wl_seat().as_ref());
+                if first.is_some_and(|first| first.equals(seat.wl_seat().as_ref())) {
+                    eprintln!("warning: the clipboard and key bindings went with it");
+                }
+            }
+        }
+    });
+    let seat_handle = first_seat.borrow().clone().ok_or(Error::MissingGlobal {
+        interface: WlSeat::NAME,
+    })?;
+    let seat = seat_handle.wl_seat().clone();
+    let serials = seat_handle.serials().clone();
+
+    // Without a data device manager, there is no clipboard nor drag and drop.
+    if let Some(manager) = &data_device_manager {
+        let data_device = data_device::setup(manager, &seat, &serials, &handle);
+        data_device.on_selection(debug_callbacks::print_selection(handle.clone()));
+        data_device.set_drop_handler(
+            &surface,
+            debug_callbacks::print_drops(handle.clone()),
+        );
+    }
+
+    // Key bindings from the file in $KEYBINDINGS, or a few examples. The
+    // screenshot action saves what the window shows.
+    let mut bindings = keybindings::Keybindings::<String>::new();
+    match env::var_os("KEYBINDINGS") {
+        Some(path) => bindings.load_file(path)?,
+        None => {
+            bindings.load_str("Ctrl+Shift+T = new-tab\nCtrl+K Ctrl+S = save-all\nPrint = screenshot")?;
+        }
+    }
+    seat_handle.on_key(debug_callbacks::print_keybindings(bindings, {
+        let window = window.clone();
+        move |action| {
+            if action == "screenshot" {
+                debug_callbacks::save_screenshot(&window);
+            }
+        }
+    }));
+
+    // The primary selection is optional, middle-click paste just won't work without it.
+    if let Some(manager) = globals.bind_optional::<ZwpPrimarySelectionDeviceManagerV1>(1..=1) {
+        let primary = primary_selection::setup(&manager, &seat, &serials, &handle);
+        seat_handle.on_pointer_frame(debug_callbacks::paste_primary_on_middle_click(primary));
+    }
+
+    // Without a text input manager, only what the keyboard layout produces can be typed.
+    if let Some(manager) = globals.bind_optional::<ZwpTextInputManagerV3>(1..=1) {
+        let text_input = seat_handle.text_input(&manager);
+        text_input.enable_for(
+            &surface,
+            ContentHint::None,
+            ContentPurpose::Normal,
+        );
+        seat_handle.on_text_input(debug_callbacks::print_text_input());
+    }
+
+    // Ask for every key while focused, as a remote desktop client would.
+    // The inhibitor must be kept alive for as long as it should apply.
+    let _shortcuts_inhibitor = globals
+        .bind_optional::<ZwpKeyboardShortcutsInhibitManagerV1>(1..=1)
+        .map(|manager| {
+            seat_handle.on_shortcuts_inhibit(debug_callbacks::print_shortcuts_inhibit);
+            seat_handle.inhibit_shortcuts(&manager, &surface)
+        });
+
+    while !window.is_closed() {
+        connection.dispatch()?;
+        if let Some(error) = window.take_error() {
+            return Err(error);
+        }
+    }
+    window.stop_recording()
+}
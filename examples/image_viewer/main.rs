@@ -0,0 +1,57 @@
+//! Shows a PNG, PPM or QOI image, fitted to the window and centered.
+//!
+//! ```text
+//! cargo run --example image_viewer -- picture.png
+//! ```
+
+use std::env;
+use std::path::Path;
+use std::process;
+
+use wayland_book_rust::{trace, Canvas, Connection, Error, Filter, Frame, Image, Window};
+
+const BACKGROUND: u32 = 0xFF20_2020;
+
+/// Where an image of `image_width` by `image_height` pixels is drawn to
+/// fill as much of a canvas as it can without being cut or distorted.
+fn fit(image_width: usize, image_height: usize, width: usize, height: usize) -> (f32, f32, f32, f32) {
+    let scale = (width as f32 / image_width as f32).min(height as f32 / image_height as f32);
+    let (fitted_width, fitted_height) = (image_width as f32 * scale, image_height as f32 * scale);
+    // Whole pixels keep the edges sharp.
+    let x = ((width as f32 - fitted_width) / 2.0).round();
+    let y = ((height as f32 - fitted_height) / 2.0).round();
+    (x, y, fitted_width, fitted_height)
+}
+
+fn main() -> Result<(), Error> {
+    trace::init_from_env()?;
+    let path = match env::args_os().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: image_viewer <png, ppm or qoi file>");
+            process::exit(2);
+        }
+    };
+    let image = Image::open(&path)?;
+    let title = Path::new(&path)
+        .file_name()
+        .map_or_else(|| "Image".into(), |name| name.to_string_lossy());
+
+    let mut connection = Connection::connect()?;
+    // The canvas is sized by the compositor's configure events, and the
+    // window only redraws when they change it.
+    let renderer = move |canvas: &mut Canvas, _: &Frame| {
+        canvas.clear(BACKGROUND);
+        let (x, y, width, height) = fit(image.width(), image.height(), canvas.width(), canvas.height());
+        canvas.draw_image(&image, x, y, width, height, Filter::Bilinear);
+    };
+    let window = Window::new(&connection, &title, renderer);
+
+    while !window.is_closed() {
+        connection.dispatch()?;
+        if let Some(error) = window.take_error() {
+            return Err(error);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,349 @@
+//! Lists the globals of the compositor, with details about outputs, seats
+//! and shm formats. Pass `--json` for machine readable output.
+//!
+//! Needs the `wayland-info` feature: `cargo run --features wayland-info`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::os::unix::io::FromRawFd;
+use std::process;
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+use wayland_client::{
+    protocol::{
+        wl_keyboard::{self, KeymapFormat},
+        wl_output::WlOutput,
+        wl_seat::{self, Capability, WlSeat},
+        wl_shm::{self, WlShm},
+    },
+    Display, EventQueue, Interface, Main,
+};
+
+use wayland_book_rust::output::{self, OutputEvent, OutputInfo};
+use wayland_book_rust::seat;
+use wayland_book_rust::{GlobalChange, Globals};
+
+#[derive(Default)]
+struct Keymap {
+    format: String,
+    layouts: Vec<String>,
+    /// Why the keymap could not be read, if it couldn't.
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct SeatDetails {
+    name: Option<String>,
+    capabilities: Vec<&'static str>,
+    keymap: Option<Keymap>,
+    /// Keeps the keyboard alive until its keymap arrives.
+    keyboard: Option<Main<wl_keyboard::WlKeyboard>>,
+}
+
+enum Details {
+    None,
+    Output(OutputInfo),
+    Seat(Rc<RefCell<SeatDetails>>),
+    Shm(Vec<String>),
+}
+
+struct Global {
+    name: u32,
+    interface: String,
+    version: u32,
+    details: Details,
+}
+
+/// The details of the globals, by name, as they arrive.
+#[derive(Default)]
+struct Collected {
+    outputs: HashMap<u32, OutputInfo>,
+    seats: HashMap<u32, Rc<RefCell<SeatDetails>>>,
+    shm_formats: HashMap<u32, Vec<String>>,
+}
+
+fn lowercase<T: std::fmt::Debug>(value: T) -> String {
+    format!("{:?}", value).to_lowercase()
+}
+
+fn follow_seat(seat: &Main<WlSeat>) -> Rc<RefCell<SeatDetails>> {
+    let details = Rc::new(RefCell::new(SeatDetails::default()));
+    seat.quick_assign({
+        let details = details.clone();
+        move |seat, event, _| match event {
+            wl_seat::Event::Capabilities { capabilities } => {
+                // The keyboard is kept in the details, so must not keep them alive.
+                let weak = Rc::downgrade(&details);
+                let mut details = details.borrow_mut();
+                let names = [
+                    (Capability::Pointer, "pointer"),
+                    (Capability::Keyboard, "keyboard"),
+                    (Capability::Touch, "touch"),
+                ];
+                details.capabilities = names
+                    .iter()
+                    .filter(|(capability, _)| capabilities.contains(*capability))
+                    .map(|(_, name)| *name)
+                    .collect();
+                if capabilities.contains(Capability::Keyboard) && details.keyboard.is_none() {
+                    let keyboard = seat.get_keyboard();
+                    keyboard.quick_assign({
+                        let details = weak;
+                        move |_, event, _| {
+                            if let wl_keyboard::Event::Keymap { format, fd, size } = event {
+                                let file = unsafe { File::from_raw_fd(fd) };
+                                if let Some(details) = details.upgrade() {
+                                    details.borrow_mut().keymap =
+                                        Some(read_keymap(format, &file, size as usize));
+                                }
+                            }
+                        }
+                    });
+                    details.keyboard = Some(keyboard);
+                }
+            }
+            wl_seat::Event::Name { name } => details.borrow_mut().name = Some(name),
+            _ => (),
+        }
+    });
+    details
+}
+
+fn read_keymap(format: KeymapFormat, file: &File, size: usize) -> Keymap {
+    let mut keymap = Keymap {
+        format: lowercase(format),
+        ..Keymap::default()
+    };
+    match seat::read_keymap(&xkb::Context::default(), format, file, size) {
+        Ok(Some(compiled)) => {
+            keymap.layouts = compiled.layouts().iter().map(|(_, name)| name.to_owned()).collect();
+        }
+        Ok(None) => (),
+        Err(error) => keymap.error = Some(error.to_string()),
+    }
+    keymap
+}
+
+fn roundtrip(event_queue: &mut EventQueue) -> Result<(), String> {
+    event_queue
+        .sync_roundtrip(&mut (), |_, _, _| ())
+        .map(|_| ())
+        .map_err(|error| format!("Connection to the compositor failed: {}", error))
+}
+
+fn collect() -> Result<Vec<Global>, String> {
+    let display = Display::connect_to_env()
+        .map_err(|error| format!("Failed to connect to the compositor: {}", error))?;
+    let mut event_queue = display.create_event_queue();
+    let attached = display.attach(event_queue.token());
+    let globals = Globals::new(&attached);
+    let collected = Rc::new(RefCell::new(Collected::default()));
+    output::watch(&globals, {
+        let collected = collected.clone();
+        move |event| {
+            if let OutputEvent::Added(info) | OutputEvent::Changed(info) = event {
+                collected.borrow_mut().outputs.insert(info.name, info.clone());
+            }
+        }
+    });
+    globals.on_global::<WlSeat, _>(1..=WlSeat::VERSION, {
+        let collected = collected.clone();
+        move |change| {
+            if let GlobalChange::Added { name, global } = change {
+                collected.borrow_mut().seats.insert(name, follow_seat(&global));
+            }
+        }
+    });
+    globals.on_global::<WlShm, _>(1..=WlShm::VERSION, {
+        let collected = collected.clone();
+        move |change| {
+            if let GlobalChange::Added { name, global } = change {
+                let collected = collected.clone();
+                global.quick_assign(move |_, event, _| {
+                    if let wl_shm::Event::Format { format } = event {
+                        let mut collected = collected.borrow_mut();
+                        collected.shm_formats.entry(name).or_default().push(lowercase(format));
+                    }
+                });
+            }
+        }
+    });
+    // One roundtrip for the globals, one for their details, and one more
+    // for the keymaps of keyboards created on the way.
+    for _ in 0..3 {
+        roundtrip(&mut event_queue)?;
+    }
+
+    let mut collected = collected.borrow_mut();
+    let globals = globals
+        .live()
+        .into_iter()
+        .map(|info| {
+            let details = match info.interface.as_str() {
+                WlSeat::NAME => collected.seats.remove(&info.name).map(Details::Seat),
+                WlShm::NAME => Some(Details::Shm(collected.shm_formats.remove(&info.name).unwrap_or_default())),
+                WlOutput::NAME => collected.outputs.remove(&info.name).map(Details::Output),
+                _ => None,
+            };
+            Global {
+                name: info.name,
+                interface: info.interface,
+                version: info.version,
+                details: details.unwrap_or(Details::None),
+            }
+        })
+        .collect();
+    Ok(globals)
+}
+
+fn mode_flags(mode: &output::OutputMode) -> Vec<&'static str> {
+    let flags = [(mode.current, "current"), (mode.preferred, "preferred")];
+    flags.iter().filter(|(set, _)| *set).map(|(_, name)| *name).collect()
+}
+
+fn print_text(globals: &[Global]) {
+    for global in globals {
+        println!(
+            "interface: '{}', version: {}, name: {}",
+            global.interface, global.version, global.name
+        );
+        match &global.details {
+            Details::Output(output) => {
+                println!("\tx: {}, y: {}, scale: {},", output.x, output.y, output.scale);
+                println!(
+                    "\tphysical_width: {} mm, physical_height: {} mm,",
+                    output.physical_width, output.physical_height
+                );
+                println!("\tmake: '{}', model: '{}',", output.make, output.model);
+                println!(
+                    "\tsubpixel_orientation: {}, output_transform: {},",
+                    lowercase(output.subpixel),
+                    lowercase(output.transform)
+                );
+                for mode in &output.modes {
+                    println!("\tmode:");
+                    println!(
+                        "\t\twidth: {} px, height: {} px, refresh: {:.3} Hz,",
+                        mode.width,
+                        mode.height,
+                        f64::from(mode.refresh) / 1000.0
+                    );
+                    println!("\t\tflags: {}", mode_flags(mode).join(" "));
+                }
+            }
+            Details::Seat(seat) => {
+                let seat = seat.borrow();
+                if let Some(name) = &seat.name {
+                    println!("\tname: {}", name);
+                }
+                println!("\tcapabilities: {}", seat.capabilities.join(" "));
+                if let Some(keymap) = &seat.keymap {
+                    println!("\tkeymap format: {}", keymap.format);
+                    match &keymap.error {
+                        Some(error) => println!("\tkeymap: {}", error),
+                        None => println!("\tkeyboard layouts: {}", keymap.layouts.join(", ")),
+                    }
+                }
+            }
+            Details::Shm(formats) => {
+                println!("\tformats: {}", formats.join(" "));
+            }
+            Details::None => (),
+        }
+    }
+}
+
+fn to_json(globals: &[Global]) -> Value {
+    let globals: Vec<Value> = globals
+        .iter()
+        .map(|global| {
+            let mut value = json!({
+                "name": global.name,
+                "interface": global.interface,
+                "version": global.version,
+            });
+            let details = match &global.details {
+                Details::Output(output) => {
+                    let modes: Vec<Value> = output
+                        .modes
+                        .iter()
+                        .map(|mode| {
+                            json!({
+                                "width": mode.width,
+                                "height": mode.height,
+                                "refresh": mode.refresh,
+                                "flags": mode_flags(mode),
+                            })
+                        })
+                        .collect();
+                    json!({
+                        "x": output.x,
+                        "y": output.y,
+                        "physical_width": output.physical_width,
+                        "physical_height": output.physical_height,
+                        "make": output.make,
+                        "model": output.model,
+                        "subpixel": lowercase(output.subpixel),
+                        "transform": lowercase(output.transform),
+                        "scale": output.scale,
+                        "modes": modes,
+                    })
+                }
+                Details::Seat(seat) => {
+                    let seat = seat.borrow();
+                    let keymap = seat.keymap.as_ref().map(|keymap| {
+                        json!({
+                            "format": keymap.format,
+                            "layouts": keymap.layouts,
+                            "error": keymap.error,
+                        })
+                    });
+                    json!({
+                        "name": seat.name,
+                        "capabilities": seat.capabilities,
+                        "keymap": keymap,
+                    })
+                }
+                Details::Shm(formats) => json!({ "formats": formats }),
+                Details::None => Value::Null,
+            };
+            if !details.is_null() {
+                value["details"] = details;
+            }
+            value
+        })
+        .collect();
+    json!({ "globals": globals })
+}
+
+fn main() {
+    let mut json = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("Usage: wayland-info [--json]");
+                return;
+            }
+            _ => {
+                eprintln!("Unknown argument {:?}, see --help", arg);
+                process::exit(2);
+            }
+        }
+    }
+    let globals = match collect() {
+        Ok(globals) => globals,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    if json {
+        println!("{:#}", to_json(&globals));
+    } else {
+        print_text(&globals);
+    }
+}
@@ -1,27 +1,74 @@
 use wayland_client::{
-    protocol::{wl_buffer, wl_shm},
+    protocol::{wl_buffer, wl_shm, wl_surface::WlSurface},
     Main,
 };
-use std::error::Error;
-use std::mem::size_of;
-use std::ops::{Deref, DerefMut};
+use std::cell::Cell;
 use std::os::unix::io::AsRawFd;
+use std::rc::Rc;
+use crate::canvas::{Canvas, Rect};
+use crate::error::Error;
+use crate::pixel_format::PixelFormat;
 use crate::shared_memory;
+use crate::trace;
 
+/// Whether the compositor may be reading a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Free,
+    Busy,
+    /// Dropped while busy, to be destroyed once released.
+    Orphaned,
+}
+
+/// A buffer in shared memory, mapped for drawing.
+///
+/// Once attached, the compositor may read it until it releases it, and it
+/// mustn't be drawn on meanwhile. It can be drawn on and attached again
+/// after that.
 pub struct Buffer {
     width: usize,
     height: usize,
+    stride: usize,
+    format: PixelFormat,
     wl_buffer: wl_buffer::WlBuffer,
+    status: Rc<Cell<Status>>,
     mmap: shared_memory::MemMap,
 }
 
 impl Buffer {
-    pub fn new(shm: &Main<wl_shm::WlShm>, width: usize, height: usize) -> Result<Self, Box<dyn Error>> {
-        let stride = size_of::<u32>() * width;
-        let size = height * stride;
+    /// Allocates an opaque XRGB8888 buffer of `width` by `height` pixels.
+    pub fn new(shm: &Main<wl_shm::WlShm>, width: usize, height: usize) -> Result<Self, Error> {
+        Self::with_format(shm, width, height, PixelFormat::Xrgb8888)
+    }
+
+    /// Allocates a buffer in `format`, which the compositor must support.
+    /// `Argb8888` makes translucent windows, with premultiplied alpha.
+    pub fn with_format(
+        shm: &Main<wl_shm::WlShm>,
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+    ) -> Result<Self, Error> {
+        let too_large = Error::BufferTooLarge { width, height };
+        // Rows start at multiples of 4 bytes, as compositors using pixman
+        // need, whatever the size of pixels.
+        let stride = format
+            .bytes_per_pixel()
+            .checked_mul(width)
+            .and_then(|row| row.checked_add(3))
+            .map(|row| row & !3)
+            .ok_or(too_large)?;
+        let size = stride
+            .checked_mul(height)
+            .filter(|&size| size <= i32::MAX as usize)
+            .ok_or(Error::BufferTooLarge { width, height })?;
         let mmap = shared_memory::MemMap::anon_file(size)?;
 
-        assert!(Self::is_aligned(&mmap), "{:?} is not sufficiently aligned!", mmap);
+        assert!(
+            !format.is_native() || Self::is_aligned(&mmap),
+            "{:?} is not sufficiently aligned!",
+            mmap
+        );
 
         let pool = shm
             .create_pool(mmap.backing_file().as_raw_fd(), size as i32);
@@ -30,52 +77,242 @@ impl Buffer {
             width as i32,
             height as i32,
             stride as i32,
-            wl_shm::Format::Xrgb8888,
+            format.to_shm(),
         );
         pool.destroy();
-        wl_buffer.quick_assign(|buffer, event, _| match event {
-            wl_buffer::Event::Release => buffer.destroy(),
-            _ => (),
+        let status = Rc::new(Cell::new(Status::Free));
+        wl_buffer.quick_assign({
+            let status = status.clone();
+            move |buffer, event, _| {
+                trace::event(&buffer, &event);
+                match event {
+                    wl_buffer::Event::Release => match status.get() {
+                        Status::Orphaned => buffer.destroy(),
+                        _ => status.set(Status::Free),
+                    },
+                    _ => (),
+                }
+            }
         });
 
         Ok(Self {
             width,
             height,
+            stride,
+            format,
             wl_buffer: wl_buffer.detach(),
+            status,
             mmap,
         })
     }
 
     fn is_aligned(buf: &[u8]) -> bool {
         let (prefix, _, postfix) = unsafe { buf.align_to::<u32>() };
-        prefix.len() == 0 && postfix.len() == 0
+        prefix.is_empty() && postfix.is_empty()
     }
 
+    /// The buffer to attach to a surface.
     pub fn wl_buffer(&self) -> &wl_buffer::WlBuffer {
         &self.wl_buffer
     }
 
+    /// Attaches the buffer to `surface`, for the next commit to show it.
+    /// It is busy until the compositor releases it.
+    pub fn attach_to(&self, surface: &WlSurface) {
+        surface.attach(Some(&self.wl_buffer), 0, 0);
+        self.status.set(Status::Busy);
+    }
+
+    /// Whether the compositor may still be reading the buffer.
+    pub fn is_busy(&self) -> bool {
+        self.status.get() != Status::Free
+    }
+
+    /// Width in pixels.
     pub fn width(&self) -> usize {
         self.width
     }
 
-    #[allow(dead_code)]
+    /// Height in pixels.
     pub fn height(&self) -> usize {
         self.height
     }
-}
 
+    /// The layout of the pixels.
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// The distance between rows, in bytes.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// The pixels, as laid out by the format.
+    pub fn bytes(&self) -> &[u8] {
+        &self.mmap
+    }
 
-impl Deref for Buffer {
-    type Target = [u32];
+    /// The pixels, as laid out by the format, for writing.
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.mmap
+    }
 
-    fn deref(&self) -> &Self::Target {
-        unsafe { (&self.mmap).align_to() }.1
+    /// The pixels as premultiplied ARGB8888 for a canvas to draw into,
+    /// if that is how the format lays them out.
+    pub fn pixels_mut(&mut self) -> Option<&mut [u32]> {
+        if self.format.is_native() {
+            Some(unsafe { self.mmap.align_to_mut() }.1)
+        } else {
+            None
+        }
+    }
+
+    /// Converts premultiplied ARGB8888 `pixels`, row after row, to the
+    /// format of the buffer.
+    pub fn write_argb(&mut self, pixels: &[u32]) {
+        assert_eq!(pixels.len(), self.width * self.height, "pixels don't fit the buffer");
+        let (format, width) = (self.format, self.width.max(1));
+        let size = format.bytes_per_pixel();
+        let rows = self.mmap.chunks_exact_mut(self.stride.max(1));
+        for (row, pixels) in rows.zip(pixels.chunks_exact(width)) {
+            for (bytes, &pixel) in row.chunks_exact_mut(size).zip(pixels) {
+                format.encode(pixel, bytes);
+            }
+        }
+    }
+
+    /// Reads the pixels back as premultiplied ARGB8888, row after row,
+    /// whatever the format of the buffer.
+    pub fn read_argb(&self) -> Vec<u32> {
+        let (format, width) = (self.format, self.width);
+        self.mmap
+            .chunks_exact(self.stride.max(1))
+            .flat_map(|row| row.chunks_exact(format.bytes_per_pixel()).take(width))
+            .map(|bytes| format.decode(bytes))
+            .collect()
+    }
+
+    /// Lets `draw` draw on a canvas over the whole buffer, to update the
+    /// parts within `rects`. Formats other than ARGB8888 and XRGB8888 are
+    /// drawn in `scratch`, and only those parts are converted back and
+    /// forth.
+    pub(crate) fn draw<F: FnOnce(&mut Canvas)>(
+        &mut self,
+        scratch: &mut Vec<u32>,
+        rects: &[Rect],
+        draw: F,
+    ) {
+        let (width, height) = (self.width, self.height);
+        if let Some(pixels) = self.pixels_mut() {
+            draw(&mut Canvas::new(pixels, width, height));
+            return;
+        }
+        scratch.resize(width * height, 0);
+        let format = self.format;
+        let size = format.bytes_per_pixel();
+        let stride = self.stride();
+        let spans = |&rect: &Rect| {
+            (rect.y..rect.y + rect.height).map(move |y| {
+                let start = y * width + rect.x;
+                (start..start + rect.width, y * stride + rect.x * size)
+            })
+        };
+        for (pixels, offset) in rects.iter().flat_map(spans) {
+            let bytes = &self.mmap[offset..offset + pixels.len() * size];
+            for (pixel, bytes) in scratch[pixels].iter_mut().zip(bytes.chunks_exact(size)) {
+                *pixel = format.decode(bytes);
+            }
+        }
+        draw(&mut Canvas::new(scratch, width, height));
+        for (pixels, offset) in rects.iter().flat_map(spans) {
+            let bytes = &mut self.mmap[offset..offset + pixels.len() * size];
+            for (&pixel, bytes) in scratch[pixels].iter().zip(bytes.chunks_exact_mut(size)) {
+                format.encode(pixel, bytes);
+            }
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if self.status.get() == Status::Busy {
+            self.status.set(Status::Orphaned);
+        } else {
+            self.wl_buffer.destroy();
+        }
     }
 }
 
-impl DerefMut for Buffer {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { (&mut self.mmap).align_to_mut() }.1
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_compositor::TestCompositor;
+    use wayland_client::protocol::wl_compositor::WlCompositor;
+
+    #[test]
+    fn the_compositor_sees_what_was_written() {
+        let (compositor, mut client) = TestCompositor::start();
+        let wl_compositor = client.globals().bind::<WlCompositor>(1..=4).unwrap();
+        let shm = client.globals().bind::<wl_shm::WlShm>(1..=1).unwrap();
+        let surface = wl_compositor.create_surface();
+        let mut buffer = Buffer::new(&shm, 4, 2).unwrap();
+        buffer.pixels_mut().unwrap().copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        buffer.attach_to(&surface);
+        assert!(buffer.is_busy());
+        surface.commit();
+        client.roundtrip();
+
+        let shm_buffer = compositor.last_buffer().unwrap();
+        assert_eq!(shm_buffer.format, wl_shm::Format::Xrgb8888.to_raw());
+        assert_eq!(shm_buffer.pixel(0, 0), 1);
+        assert_eq!(shm_buffer.pixel(3, 1), 8);
+        assert!(!buffer.is_busy(), "the buffer should have been released");
+        assert_eq!(compositor.log().count("wl_buffer.destroy"), 0, "released buffers are kept");
+        drop(buffer);
+        client.roundtrip();
+        assert_eq!(compositor.log().count("wl_buffer.destroy"), 1);
+    }
+
+    #[test]
+    fn pixels_read_back_through_the_format() {
+        let (_compositor, client) = TestCompositor::start();
+        let shm = client.globals().bind::<wl_shm::WlShm>(1..=1).unwrap();
+        let mut buffer = Buffer::with_format(&shm, 2, 1, PixelFormat::Rgb565).unwrap();
+        buffer.write_argb(&[0xFFFF_0000, 0x8012_3456]);
+        // 5 and 6 bits per channel round the colors, and drop the alpha.
+        assert_eq!(buffer.read_argb(), [0xFFFF_0000, 0xFF10_3552]);
+    }
+
+    #[test]
+    fn rows_of_odd_sizes_are_padded() {
+        let (compositor, mut client) = TestCompositor::start();
+        let wl_compositor = client.globals().bind::<WlCompositor>(1..=4).unwrap();
+        let shm = client.globals().bind::<wl_shm::WlShm>(1..=1).unwrap();
+        let surface = wl_compositor.create_surface();
+        for &(format, stride) in &[(PixelFormat::Rgb565, 8), (PixelFormat::Rgb888, 12)] {
+            let mut buffer = Buffer::with_format(&shm, 3, 3, format).unwrap();
+            assert_eq!(buffer.stride(), stride);
+            let colors = [0xFF00_0000, 0xFFFF_0000, 0xFF00_FFFF, 0xFFFF_FFFF];
+            let pixels: Vec<u32> = colors.iter().cycle().take(9).copied().collect();
+            buffer.write_argb(&pixels);
+            assert_eq!(buffer.read_argb(), pixels);
+            buffer.attach_to(&surface);
+            surface.commit();
+            client.roundtrip();
+            let shm_buffer = compositor.last_buffer().unwrap();
+            assert_eq!(shm_buffer.stride, stride as i32);
+            assert_eq!(shm_buffer.argb(2, 2), pixels[8]);
+        }
+    }
+
+    #[test]
+    fn refuses_buffers_too_large_for_the_protocol() {
+        let (_compositor, client) = TestCompositor::start();
+        let shm = client.globals().bind::<wl_shm::WlShm>(1..=1).unwrap();
+        assert!(matches!(
+            Buffer::new(&shm, 1 << 16, 1 << 16),
+            Err(Error::BufferTooLarge { .. })
+        ));
     }
 }
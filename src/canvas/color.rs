@@ -0,0 +1,130 @@
+//! Converting colors between sRGB, which pixels are stored in, and linear
+//! light, where mixing them matches how light adds up.
+
+use std::sync::OnceLock;
+
+/// Where colors are mixed, by blending or along gradients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// The sRGB values as stored, like most software and CSS mix them.
+    /// Mixes come out darker than light would, as in the dark seam
+    /// between red and green.
+    #[default]
+    Srgb,
+    /// Linear light, converted from and back to sRGB for every mix.
+    /// Slower, but mixes and antialiased edges look as bright as they
+    /// should.
+    LinearRgb,
+}
+
+/// Entries of the table from linear light back to sRGB. Finer than 8
+/// bits, as dark sRGB values lie close together in linear light.
+const ENCODE_STEPS: usize = 4096;
+
+/// Linear light, from 0 to 1, of every 8 bit sRGB value.
+fn decode_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for (value, linear) in table.iter_mut().enumerate() {
+            let srgb = value as f32 / 255.0;
+            *linear = if srgb <= 0.04045 {
+                srgb / 12.92
+            } else {
+                ((srgb + 0.055) / 1.055).powf(2.4)
+            };
+        }
+        table
+    })
+}
+
+fn encode_table() -> &'static [u8] {
+    static TABLE: OnceLock<Vec<u8>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        (0..ENCODE_STEPS)
+            .map(|step| {
+                let linear = step as f32 / (ENCODE_STEPS - 1) as f32;
+                let srgb = if linear <= 0.003_130_8 {
+                    linear * 12.92
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                };
+                (srgb * 255.0).round() as u8
+            })
+            .collect()
+    })
+}
+
+/// The linear light of an 8 bit sRGB channel.
+pub fn to_linear(channel: u8) -> f32 {
+    decode_table()[usize::from(channel)]
+}
+
+/// The 8 bit sRGB channel of linear light from 0 to 1.
+pub fn to_srgb(linear: f32) -> u8 {
+    let step = (linear.clamp(0.0, 1.0) * (ENCODE_STEPS - 1) as f32).round();
+    encode_table()[step as usize]
+}
+
+/// The channels of a premultiplied ARGB8888 `pixel` as premultiplied
+/// red, green, blue and alpha from 0 to 1, in `space`.
+pub fn unpack(pixel: u32, space: ColorSpace) -> [f32; 4] {
+    let [b, g, r, a] = pixel.to_le_bytes();
+    let alpha = f32::from(a) / 255.0;
+    let [r, g, b] = match space {
+        ColorSpace::Srgb => [r, g, b].map(|channel| f32::from(channel) / 255.0),
+        ColorSpace::LinearRgb if a == 0 => return [0.0; 4],
+        ColorSpace::LinearRgb => {
+            // The transfer function applies to the straight color.
+            let straight = |channel: u8| {
+                ((u32::from(channel) * 255 + u32::from(a) / 2) / u32::from(a)).min(255) as u8
+            };
+            [r, g, b].map(|channel| to_linear(straight(channel)) * alpha)
+        }
+    };
+    [r, g, b, alpha]
+}
+
+/// Packs premultiplied channels from 0 to 1 in `space` back into a
+/// premultiplied ARGB8888 pixel.
+pub fn pack(channels: [f32; 4], space: ColorSpace) -> u32 {
+    let [r, g, b, alpha] = channels.map(|channel| channel.clamp(0.0, 1.0));
+    let a = (alpha * 255.0).round() as u8;
+    let [r, g, b] = match space {
+        ColorSpace::Srgb => [r, g, b].map(|channel| (channel * 255.0).round() as u8),
+        ColorSpace::LinearRgb if a == 0 => return 0,
+        ColorSpace::LinearRgb => {
+            [r, g, b].map(|channel| {
+                let straight = to_srgb(channel / alpha);
+                ((u32::from(straight) * u32::from(a) + 127) / 255) as u8
+            })
+        }
+    };
+    u32::from_le_bytes([b, g, r, a])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_roundtrips_through_linear_light() {
+        for value in 0..=255 {
+            assert_eq!(to_srgb(to_linear(value)), value);
+        }
+        // Mid gray in sRGB is a fifth of the light of white.
+        assert!((to_linear(128) - 0.2158).abs() < 1e-3);
+        assert_eq!(to_srgb(0.5), 188);
+    }
+
+    #[test]
+    fn pixels_unpack_and_pack_in_either_space() {
+        for &pixel in &[0xFF12_3456, 0x8040_2000, 0, 0xFFFF_FFFF] {
+            for &space in &[ColorSpace::Srgb, ColorSpace::LinearRgb] {
+                assert_eq!(pack(unpack(pixel, space), space), pixel, "{:08X} in {:?}", pixel, space);
+            }
+        }
+        let [r, _, _, a] = unpack(0x8080_0000, ColorSpace::LinearRgb);
+        assert!((r - 0.5).abs() < 0.01 && (a - 0.5).abs() < 0.01);
+    }
+}
@@ -0,0 +1,760 @@
+//! Antialiased 2D drawing in software, on the pixels of a buffer.
+
+use std::ops::{Add, Mul, Sub};
+use std::slice::ChunksExactMut;
+
+mod color;
+mod paint;
+mod path;
+mod rasterizer;
+mod stroke;
+mod transform;
+
+pub use color::ColorSpace;
+pub use paint::{Gradient, Paint, Pattern, Spread};
+pub use path::Path;
+pub use transform::Transform;
+
+pub(crate) use paint::Shader;
+use rasterizer::Rasterizer;
+
+use crate::image::{Filter, Image};
+use crate::text::{self, Font, Glyph};
+
+/// A rectangle in buffer pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    /// Left edge.
+    pub x: usize,
+    /// Top edge.
+    pub y: usize,
+    /// Width in pixels.
+    pub width: usize,
+    /// Height in pixels.
+    pub height: usize,
+}
+
+impl Rect {
+    /// A rectangle with its top left corner at `x`, `y`.
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The part of both rectangles, or `None` if they don't overlap.
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        if right > x && bottom > y {
+            Some(Rect::new(x, y, right - x, bottom - y))
+        } else {
+            None
+        }
+    }
+
+    /// The smallest rectangle around both.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Rect::new(x, y, right - x, bottom - y)
+    }
+
+    /// Whether `other` lies entirely within this rectangle.
+    pub fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+impl Point {
+    fn length(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl Mul<f32> for Point {
+    type Output = Point;
+
+    fn mul(self, factor: f32) -> Point {
+        Point {
+            x: self.x * factor,
+            y: self.y * factor,
+        }
+    }
+}
+
+/// ARGB8888 pixels with premultiplied alpha to render into, row after row.
+/// The alpha is ignored in opaque windows.
+///
+/// Paths are drawn through the current transform, and only within the
+/// clip rectangle if there is one. Colors are given as ARGB8888 with
+/// straight alpha, and drawn over what is there already.
+pub struct Canvas<'a> {
+    pixels: &'a mut [u32],
+    width: usize,
+    height: usize,
+    transform: Transform,
+    clip: Option<Rect>,
+    color_space: ColorSpace,
+}
+
+impl<'a> Canvas<'a> {
+    /// A canvas over `pixels`, which must hold `height` rows of `width`
+    /// pixels.
+    pub fn new(pixels: &'a mut [u32], width: usize, height: usize) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixels don't fit {}x{}", width, height);
+        Canvas {
+            pixels,
+            width,
+            height,
+            transform: Transform::IDENTITY,
+            clip: None,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// All pixels, row after row.
+    pub fn pixels(&self) -> &[u32] {
+        self.pixels
+    }
+
+    /// All pixels, row after row, for drawing.
+    pub fn pixels_mut(&mut self) -> &mut [u32] {
+        self.pixels
+    }
+
+    /// The rows of pixels, top to bottom.
+    pub fn rows_mut(&mut self) -> ChunksExactMut<'_, u32> {
+        // chunks_exact_mut panics on 0, and there are no rows to yield then.
+        self.pixels.chunks_exact_mut(self.width.max(1))
+    }
+
+    /// Sets every pixel within the clip rectangle to `color`, replacing
+    /// what was there. Transparent black clears a transparent window.
+    pub fn clear(&mut self, color: u32) {
+        let color = premultiply(color);
+        if let Some(area) = self.area() {
+            let width = self.width;
+            for row in self.pixels.chunks_exact_mut(width).skip(area.y).take(area.height) {
+                row[area.x..area.x + area.width].fill(color);
+            }
+        }
+    }
+
+    /// The transform paths are drawn through.
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    /// Draws paths through `transform` from now on.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// The rectangle drawing is limited to, in pixels.
+    pub fn clip(&self) -> Option<Rect> {
+        self.clip
+    }
+
+    /// Limits drawing to `clip`, in pixels whatever the transform, or
+    /// lifts the limit with `None`.
+    pub fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+    }
+
+    /// The color space drawing blends in.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Blends what is drawn from now on in `space`. Gradients mix their
+    /// stops in their own color space.
+    pub fn set_color_space(&mut self, space: ColorSpace) {
+        self.color_space = space;
+    }
+
+    /// Fills the inside of `path` by the nonzero rule, closing its open
+    /// subpaths.
+    pub fn fill(&mut self, path: &Path, color: u32) {
+        self.fill_with(path, &Paint::Solid(color));
+    }
+
+    /// Fills `path` like `fill`, with `paint`.
+    pub fn fill_with(&mut self, path: &Path, paint: &Paint) {
+        let polygons: Vec<_> = path
+            .flatten(&self.transform)
+            .into_iter()
+            .map(|polyline| polyline.points)
+            .collect();
+        self.fill_polygons(&polygons, paint);
+    }
+
+    /// Draws lines `width` wide along `path`. The width grows and shrinks
+    /// with the scale of the transform.
+    pub fn stroke(&mut self, path: &Path, width: f32, color: u32) {
+        self.stroke_with(path, width, &Paint::Solid(color));
+    }
+
+    /// Draws lines along `path` like `stroke`, with `paint`.
+    pub fn stroke_with(&mut self, path: &Path, width: f32, paint: &Paint) {
+        let polylines = path.flatten(&self.transform);
+        let width = width * self.transform.scale_factor();
+        self.fill_polygons(&stroke::stroke(&polylines, width), paint);
+    }
+
+    /// Draws the lines of `text` in `font`, with the top of the first one
+    /// at `x`, `y`. The text is placed through the transform, but glyphs
+    /// aren't scaled or rotated by it.
+    pub fn draw_text(&mut self, font: &dyn Font, x: f32, y: f32, text: &str, color: u32) {
+        self.draw_lines(font, x, y, text.split('\n'), color);
+    }
+
+    /// Draws `text` like `draw_text`, broken into lines at most `max_width`
+    /// pixels wide. Returns the height of the lines.
+    pub fn draw_wrapped_text(
+        &mut self,
+        font: &dyn Font,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        text: &str,
+        color: u32,
+    ) -> f32 {
+        let lines = text::break_lines(font, text, max_width);
+        self.draw_lines(font, x, y, lines.iter().copied(), color);
+        lines.len() as f32 * font.line_height()
+    }
+
+    /// Draws `image` scaled into the rectangle at `x`, `y`, `width` by
+    /// `height`, through the transform and over what is there already.
+    pub fn draw_image(&mut self, image: &Image, x: f32, y: f32, width: f32, height: f32, filter: Filter) {
+        let source = Rect::new(0, 0, image.width(), image.height());
+        self.draw_image_part(image, source, x, y, width, height, filter);
+    }
+
+    /// Draws the part `source` of `image` like `draw_image`. Pixels
+    /// around it aren't sampled, even by the bilinear filter.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_image_part(
+        &mut self,
+        image: &Image,
+        source: Rect,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        filter: Filter,
+    ) {
+        let source = match source.intersect(&Rect::new(0, 0, image.width(), image.height())) {
+            Some(source) => source,
+            None => return,
+        };
+        // From the source pixels to the canvas, then back for sampling.
+        let placement = Transform::translate(-(source.x as f32), -(source.y as f32))
+            .then(&Transform::scale(width / source.width as f32, height / source.height as f32))
+            .then(&Transform::translate(x, y))
+            .then(&self.transform);
+        let inverse = match placement.invert() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let (left, top) = (source.x as f32, source.y as f32);
+        let (right, bottom) = (left + source.width as f32, top + source.height as f32);
+        let corners: Vec<_> = [(left, top), (right, top), (right, bottom), (left, bottom)]
+            .iter()
+            .map(|&(u, v)| placement.point(Point { x: u, y: v }))
+            .collect();
+        if !corners.iter().all(|point| point.x.is_finite() && point.y.is_finite()) {
+            return;
+        }
+        let bounds = match (bounding_box(&[&corners]), self.area()) {
+            (Some(bounds), Some(area)) => bounds.intersect(&area),
+            _ => None,
+        };
+        let bounds = match bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        for py in bounds.y..bounds.y + bounds.height {
+            for px in bounds.x..bounds.x + bounds.width {
+                // Pixels are sampled at their centers.
+                let (u, v) = inverse.apply(px as f32 + 0.5, py as f32 + 0.5);
+                if u < left || u >= right || v < top || v >= bottom {
+                    continue;
+                }
+                let color = match filter {
+                    Filter::Nearest => image.pixel(u as usize, v as usize),
+                    Filter::Bilinear => bilinear(image, &source, u, v),
+                };
+                let pixel = &mut self.pixels[py * self.width + px];
+                *pixel = match self.color_space {
+                    ColorSpace::Srgb => over(*pixel, color),
+                    space => composite(*pixel, color, 1.0, space),
+                };
+            }
+        }
+    }
+
+    fn draw_lines<'t, I>(&mut self, font: &dyn Font, x: f32, y: f32, lines: I, color: u32)
+    where
+        I: Iterator<Item = &'t str>,
+    {
+        let (x, y) = self.transform.apply(x, y);
+        let mut baseline = y + font.ascent();
+        for line in lines {
+            for (c, pen) in text::positions(font, line) {
+                if let Some(glyph) = font.glyph(c) {
+                    // Whole pixels keep glyphs as sharp as they were rendered.
+                    let left = (x + pen).round() as i64 + i64::from(glyph.left);
+                    let top = baseline.round() as i64 - i64::from(glyph.top);
+                    self.blend_glyph(left, top, &glyph, color);
+                }
+            }
+            baseline += font.line_height();
+        }
+    }
+
+    fn blend_glyph(&mut self, left: i64, top: i64, glyph: &Glyph, color: u32) {
+        let area = match self.area() {
+            Some(area) if glyph.width > 0 => area,
+            _ => return,
+        };
+        let rows = glyph.coverage.chunks_exact(glyph.width);
+        for (y, row) in (top..).zip(rows) {
+            if y < area.y as i64 || y >= (area.y + area.height) as i64 {
+                continue;
+            }
+            for (x, &coverage) in (left..).zip(row) {
+                if coverage == 0 || x < area.x as i64 || x >= (area.x + area.width) as i64 {
+                    continue;
+                }
+                let pixel = &mut self.pixels[y as usize * self.width + x as usize];
+                *pixel = blend_in(self.color_space, *pixel, color, f32::from(coverage) / 255.0);
+            }
+        }
+    }
+
+    /// The canvas, within the clip rectangle.
+    fn area(&self) -> Option<Rect> {
+        let canvas = Rect::new(0, 0, self.width, self.height);
+        match self.clip {
+            Some(clip) => clip.intersect(&canvas),
+            None => Some(canvas).filter(|canvas| canvas.width > 0 && canvas.height > 0),
+        }
+    }
+
+    fn fill_polygons(&mut self, polygons: &[Vec<Point>], paint: &Paint) {
+        // A single NaN would spoil the coverage of whole rows.
+        let finite = |point: &Point| point.x.is_finite() && point.y.is_finite();
+        let polygons: Vec<_> = polygons
+            .iter()
+            .filter(|polygon| polygon.iter().all(finite))
+            .collect();
+        let area = match self.area() {
+            Some(area) => area,
+            None => return,
+        };
+        let bounds = match bounding_box(&polygons).and_then(|bounds| bounds.intersect(&area)) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let mut rasterizer = Rasterizer::new(bounds);
+        for polygon in &polygons {
+            rasterizer.polygon(polygon);
+        }
+        let (width, space) = (self.width, self.color_space);
+        let shader = Shader::new(paint, &self.transform);
+        let pixels = &mut *self.pixels;
+        rasterizer.for_each_covered(|x, y, coverage| {
+            let pixel = &mut pixels[y * width + x];
+            *pixel = match paint {
+                Paint::Solid(color) => blend_in(space, *pixel, *color, coverage),
+                _ => composite(*pixel, shader.color(x, y), coverage, space),
+            };
+        });
+    }
+}
+
+/// The pixels touched by `polygons`, or `None` if none are.
+fn bounding_box(polygons: &[&Vec<Point>]) -> Option<Rect> {
+    let mut points = polygons.iter().copied().flatten();
+    let first = *points.next()?;
+    let (min, max) = points.fold((first, first), |(min, max), point| {
+        (
+            Point {
+                x: min.x.min(point.x),
+                y: min.y.min(point.y),
+            },
+            Point {
+                x: max.x.max(point.x),
+                y: max.y.max(point.y),
+            },
+        )
+    });
+    // Negative coordinates saturate to 0, which only drops what is
+    // outside the canvas anyway.
+    let (x, y) = (min.x.floor() as usize, min.y.floor() as usize);
+    let (right, bottom) = (max.x.ceil() as usize, max.y.ceil() as usize);
+    Some(Rect::new(x, y, right.saturating_sub(x), bottom.saturating_sub(y)))
+}
+
+/// Multiplies the color channels of ARGB8888 `color` by its alpha, the
+/// way the compositor expects them in buffers with alpha.
+pub fn premultiply(color: u32) -> u32 {
+    let alpha = color >> 24;
+    let channel = |shift: u32| (((color >> shift & 0xFF) * alpha + 127) / 255) << shift;
+    alpha << 24 | channel(16) | channel(8) | channel(0)
+}
+
+/// The premultiplied color at `u`, `v` in `image`, mixed from the four
+/// pixels around it within `source`.
+fn bilinear(image: &Image, source: &Rect, u: f32, v: f32) -> u32 {
+    let clamp = |position: f32, start: usize, length: usize| {
+        let position = (position - 0.5).max(start as f32);
+        let near = (position as usize).min(start + length - 1);
+        let far = (near + 1).min(start + length - 1);
+        (near, far, position - near as f32)
+    };
+    let (x0, x1, fx) = clamp(u, source.x, source.width);
+    let (y0, y1, fy) = clamp(v, source.y, source.height);
+    mix(&[
+        (image.pixel(x0, y0), (1.0 - fx) * (1.0 - fy)),
+        (image.pixel(x1, y0), fx * (1.0 - fy)),
+        (image.pixel(x0, y1), (1.0 - fx) * fy),
+        (image.pixel(x1, y1), fx * fy),
+    ])
+}
+
+/// The premultiplied pixels of `corners` mixed by their weights.
+fn mix(corners: &[(u32, f32); 4]) -> u32 {
+    let mut mixed = 0;
+    for shift in [0, 8, 16, 24] {
+        let channel: f32 = corners
+            .iter()
+            .map(|&(pixel, weight)| (pixel >> shift & 0xFF) as f32 * weight)
+            .sum();
+        mixed |= (channel.round() as u32).min(255) << shift;
+    }
+    mixed
+}
+
+/// Porter-Duff source-over of the premultiplied `color` on the
+/// premultiplied `pixel`.
+fn over(pixel: u32, color: u32) -> u32 {
+    let remaining = 255 - (color >> 24);
+    let mut blended = 0;
+    for shift in [0, 8, 16, 24] {
+        let channel = (color >> shift & 0xFF) + ((pixel >> shift & 0xFF) * remaining + 127) / 255;
+        blended |= channel.min(255) << shift;
+    }
+    blended
+}
+
+/// Porter-Duff source-over of straight `color`, with its alpha scaled by
+/// `coverage`, on the premultiplied `pixel`.
+fn blend(pixel: u32, color: u32, coverage: f32) -> u32 {
+    let alpha = (color >> 24) as f32 / 255.0 * coverage;
+    let mut blended = 0;
+    for shift in [0, 8, 16, 24] {
+        let source = if shift == 24 {
+            255.0
+        } else {
+            (color >> shift & 0xFF) as f32
+        };
+        let destination = (pixel >> shift & 0xFF) as f32;
+        let channel = source * alpha + destination * (1.0 - alpha);
+        blended |= (channel.round() as u32) << shift;
+    }
+    blended
+}
+
+/// Blends straight `color` on `pixel` like `blend`, in `space`.
+fn blend_in(space: ColorSpace, pixel: u32, color: u32, coverage: f32) -> u32 {
+    match space {
+        ColorSpace::Srgb => blend(pixel, color, coverage),
+        ColorSpace::LinearRgb => composite(pixel, premultiply(color), coverage, space),
+    }
+}
+
+/// Porter-Duff source-over of the premultiplied `color`, scaled by
+/// `coverage`, on the premultiplied `pixel`, mixed in `space`.
+fn composite(pixel: u32, color: u32, coverage: f32, space: ColorSpace) -> u32 {
+    let source = color::unpack(color, space);
+    let destination = color::unpack(pixel, space);
+    let remaining = 1.0 - source[3] * coverage;
+    let mut mixed = [0.0; 4];
+    for (channel, (source, destination)) in mixed.iter_mut().zip(source.iter().zip(&destination)) {
+        *channel = source * coverage + destination * remaining;
+    }
+    color::pack(mixed, space)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{assert_snapshot, Tolerance};
+
+    const WHITE: u32 = 0xFFFF_FFFF;
+    const BLACK: u32 = 0xFF00_0000;
+
+    fn canvas_test<F: FnOnce(&mut Canvas)>(width: usize, height: usize, draw: F) -> Image {
+        let mut pixels = vec![0; width * height];
+        let mut canvas = Canvas::new(&mut pixels, width, height);
+        canvas.clear(WHITE);
+        draw(&mut canvas);
+        Image::new(width, height, pixels)
+    }
+
+    #[test]
+    fn edges_are_antialiased() {
+        let image = canvas_test(4, 1, |canvas| {
+            canvas.fill(&Path::rect(0.5, 0.0, 2.0, 1.0), BLACK);
+        });
+        assert_eq!(image.pixels(), vec![0xFF80_8080, BLACK, 0xFF80_8080, WHITE]);
+    }
+
+    #[test]
+    fn translucent_colors_blend() {
+        let image = canvas_test(1, 1, |canvas| {
+            canvas.fill(&Path::rect(0.0, 0.0, 1.0, 1.0), 0x80FF_0000);
+        });
+        assert_eq!(image.pixels(), vec![0xFFFF_7F7F]);
+    }
+
+    #[test]
+    fn translucent_colors_stay_translucent_on_transparent_pixels() {
+        let image = canvas_test(3, 1, |canvas| {
+            canvas.clear(0);
+            canvas.fill(&Path::rect(0.0, 0.0, 2.0, 1.0), 0x80FF_0000);
+            canvas.fill(&Path::rect(1.0, 0.0, 2.0, 1.0), 0x8000_00FF);
+        });
+        // Red under half blue, then blue alone.
+        assert_eq!(image.pixels(), vec![0x8080_0000, 0xC040_0080, 0x8000_0080]);
+    }
+
+    #[test]
+    fn premultiplied_channels_scale_with_alpha() {
+        assert_eq!(premultiply(0xFF12_3456), 0xFF12_3456);
+        assert_eq!(premultiply(0x80FF_8000), 0x8080_4000);
+        assert_eq!(premultiply(0x00FF_FFFF), 0);
+    }
+
+    #[test]
+    fn drawing_stays_within_the_clip() {
+        let image = canvas_test(4, 4, |canvas| {
+            canvas.set_clip(Some(Rect::new(1, 1, 2, 2)));
+            canvas.fill(&Path::circle(2.0, 2.0, 10.0), BLACK);
+            canvas.set_clip(Some(Rect::new(3, 3, 5, 5)));
+            canvas.clear(0xFF12_3456);
+        });
+        let expected = [
+            [WHITE, WHITE, WHITE, WHITE],
+            [WHITE, BLACK, BLACK, WHITE],
+            [WHITE, BLACK, BLACK, WHITE],
+            [WHITE, WHITE, WHITE, 0xFF12_3456],
+        ];
+        assert_eq!(image.pixels(), expected.concat());
+    }
+
+    #[test]
+    fn shapes_off_the_canvas_are_skipped() {
+        let image = canvas_test(4, 4, |canvas| {
+            canvas.fill(&Path::rect(-10.0, -10.0, 5.0, 5.0), BLACK);
+            canvas.stroke(&Path::line(5.0, 0.0, 5.0, 4.0), 1.0, BLACK);
+            canvas.fill(&Path::rect(0.0, f32::NAN, 1.0, 1.0), BLACK);
+            canvas.fill(&Path::circle(2.0, 2.0, f32::INFINITY), BLACK);
+        });
+        assert_eq!(image.pixels(), vec![WHITE; 16]);
+    }
+
+    #[test]
+    fn shapes() {
+        let image = canvas_test(96, 64, |canvas| {
+            canvas.fill(&Path::rounded_rect(4.0, 4.0, 40.0, 24.0, 6.0), 0xFF33_66CC);
+            canvas.stroke(&Path::rounded_rect(4.5, 34.5, 40.0, 24.0, 8.0), 1.0, 0xFF20_2020);
+            canvas.fill(&Path::circle(70.0, 16.0, 11.5), 0xFFCC_3333);
+            canvas.stroke(&Path::circle(70.0, 16.0, 11.5), 3.0, 0x8000_0000);
+            canvas.stroke(&Path::line(52.0, 36.0, 92.0, 60.0), 2.5, 0xFF00_8000);
+            let mut wave = Path::new();
+            wave.move_to(52.0, 48.0);
+            wave.cubic_to(62.0, 28.0, 72.0, 68.0, 82.0, 48.0);
+            wave.quad_to(87.0, 38.0, 92.0, 48.0);
+            canvas.stroke(&wave, 1.5, 0xFF00_00CC);
+            canvas.set_transform(
+                Transform::translate(-6.0, -4.0)
+                    .then(&Transform::rotate(0.4))
+                    .then(&Transform::translate(6.0, 46.0)),
+            );
+            canvas.set_clip(Some(Rect::new(4, 34, 40, 24)));
+            canvas.fill(&Path::rect(0.0, 0.0, 12.0, 8.0), 0xC0FF_AA00);
+        });
+        let tolerance = Tolerance {
+            channel: 2,
+            pixels: 0,
+        };
+        assert_snapshot("canvas_shapes", &image, tolerance);
+    }
+
+    /// A 2 by 2 image of red, green, blue and half transparent black.
+    fn quad() -> Image {
+        Image::from_straight(2, 2, vec![0xFFFF_0000, 0xFF00_FF00, 0xFF00_00FF, 0x8000_0000])
+    }
+
+    #[test]
+    fn nearest_images_scale_by_whole_pixels() {
+        let image = canvas_test(5, 4, |canvas| {
+            canvas.draw_image(&quad(), 1.0, 0.0, 4.0, 4.0, Filter::Nearest);
+        });
+        let (red, green, blue, gray) = (0xFFFF_0000, 0xFF00_FF00, 0xFF00_00FF, 0xFF7F_7F7F);
+        let expected = [
+            [WHITE, red, red, green, green],
+            [WHITE, red, red, green, green],
+            [WHITE, blue, blue, gray, gray],
+            [WHITE, blue, blue, gray, gray],
+        ];
+        assert_eq!(image.pixels(), expected.concat());
+    }
+
+    #[test]
+    fn image_parts_stay_within_the_source_and_the_clip() {
+        let image = canvas_test(4, 2, |canvas| {
+            canvas.set_clip(Some(Rect::new(0, 0, 3, 2)));
+            // Green alone, stretched: the bilinear filter mixes in nothing else.
+            canvas.draw_image_part(&quad(), Rect::new(1, 0, 1, 1), 0.0, 0.0, 4.0, 2.0, Filter::Bilinear);
+        });
+        let green = 0xFF00_FF00;
+        assert_eq!(image.pixels(), [[green, green, green, WHITE]; 2].concat());
+    }
+
+    #[test]
+    fn images_are_smoothed_and_transformed() {
+        let mut pixels = Vec::new();
+        for y in 0..8u32 {
+            for x in 0..8u32 {
+                pixels.push(if (x + y) % 2 == 0 { 0xFF20_4080 } else { 0x80FF_C000 });
+            }
+        }
+        let checker = Image::from_straight(8, 8, pixels);
+        let image = canvas_test(64, 48, |canvas| {
+            canvas.draw_image(&checker, 2.0, 2.0, 28.0, 20.0, Filter::Bilinear);
+            canvas.set_transform(Transform::rotate(0.3).then(&Transform::translate(40.0, 8.0)));
+            canvas.draw_image(&quad(), 0.0, 0.0, 16.0, 32.0, Filter::Nearest);
+        });
+        let tolerance = Tolerance {
+            channel: 2,
+            pixels: 0,
+        };
+        assert_snapshot("canvas_images", &image, tolerance);
+    }
+
+    #[test]
+    fn text_is_drawn_line_by_line() {
+        let font = crate::text::BitmapFont::new(2);
+        let image = canvas_test(64, 40, |canvas| {
+            canvas.set_transform(Transform::translate(2.0, 1.0));
+            canvas.draw_text(&font, 0.0, 0.0, "Hi,\n  you", BLACK);
+            canvas.set_clip(Some(Rect::new(0, 35, 64, 5)));
+            let height = canvas.draw_wrapped_text(&font, 0.0, 18.0, 62.0, "two lines", 0xFFFF_0000);
+            assert_eq!(height, 36.0);
+        });
+        let dark = |x: usize, y: usize| image.pixels()[y * 64 + x] == BLACK;
+        // The left stem of the H, scaled by 2 and moved by the transform.
+        assert!((1..15).all(|y| dark(2, y) && dark(3, y)));
+        assert!(!dark(2, 0) && !dark(2, 15) && !dark(1, 5));
+        // The second line is 18 pixels down, two characters in.
+        assert!(dark(2 + 2 * 12, 1 + 18 + 4));
+        // Only the second wrapped line shows through the clip.
+        assert!(image.pixels()[..35 * 64].iter().all(|&pixel| pixel != 0xFFFF_0000));
+        assert!(image.pixels()[35 * 64..].contains(&0xFFFF_0000));
+        assert_snapshot("canvas_text", &image, Tolerance::EXACT);
+    }
+
+    #[test]
+    fn linear_light_blends_brighter() {
+        let image = canvas_test(4, 1, |canvas| {
+            canvas.set_color_space(ColorSpace::LinearRgb);
+            canvas.fill(&Path::rect(0.5, 0.0, 2.0, 1.0), BLACK);
+        });
+        // Half the light of white, rather than half its sRGB value.
+        assert_eq!(image.pixels(), vec![0xFFBC_BCBC, BLACK, 0xFFBC_BCBC, WHITE]);
+    }
+
+    #[test]
+    fn gradients_and_patterns_follow_the_transform() {
+        let stripes = Image::new(4, 1, vec![0xFFFF_0000, 0xFFFF_0000, 0xFF00_0000, 0x8000_0080]);
+        let image = canvas_test(96, 64, |canvas| {
+            let sky = Gradient::linear(0.0, 0.0, 0.0, 24.0)
+                .stop(0.0, 0xFF20_40A0)
+                .stop(0.7, 0xFFF0_A060)
+                .stop(1.0, 0x00F0_A060);
+            canvas.fill_with(&Path::rect(4.0, 4.0, 40.0, 24.0), &sky.into());
+            let glow = Gradient::radial(70.0, 16.0, 6.0)
+                .stop(0.0, 0xFFFF_FF00)
+                .stop(1.0, 0xFF00_80FF)
+                .spread(Spread::Reflect)
+                .color_space(ColorSpace::LinearRgb);
+            canvas.fill_with(&Path::circle(70.0, 16.0, 14.0), &glow.into());
+            let wheel = Gradient::conic(24.0, 46.0, 0.0)
+                .stop(0.0, 0xFFFF_0000)
+                .stop(0.5, 0xFF00_FF00)
+                .stop(1.0, 0xFFFF_0000);
+            canvas.fill_with(&Path::circle(24.0, 46.0, 14.0), &wheel.into());
+            canvas.set_transform(Transform::rotate(0.4).then(&Transform::translate(60.0, 36.0)));
+            let pattern = Pattern::new(stripes).filter(Filter::Nearest);
+            canvas.stroke_with(&Path::rect(0.0, 0.0, 24.0, 16.0), 6.0, &pattern.into());
+        });
+        let tolerance = Tolerance {
+            channel: 2,
+            pixels: 0,
+        };
+        assert_snapshot("canvas_paints", &image, tolerance);
+    }
+}
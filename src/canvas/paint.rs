@@ -0,0 +1,363 @@
+//! What shapes are filled with: solid colors, gradients and repeated
+//! images.
+
+use std::f32::consts::TAU;
+use std::rc::Rc;
+
+use super::color::{self, ColorSpace};
+use super::{mix, premultiply, Transform};
+use crate::image::{Filter, Image};
+
+/// Gradients are looked up in a table of this many colors per fill.
+const GRADIENT_STEPS: usize = 1024;
+
+/// What to fill or stroke shapes with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+    /// One ARGB8888 color with straight alpha.
+    Solid(u32),
+    /// Colors blending into each other.
+    Gradient(Gradient),
+    /// An image repeated in every direction.
+    Pattern(Pattern),
+}
+
+impl From<u32> for Paint {
+    fn from(color: u32) -> Self {
+        Paint::Solid(color)
+    }
+}
+
+impl From<Gradient> for Paint {
+    fn from(gradient: Gradient) -> Self {
+        Paint::Gradient(gradient)
+    }
+}
+
+impl From<Pattern> for Paint {
+    fn from(pattern: Pattern) -> Self {
+        Paint::Pattern(pattern)
+    }
+}
+
+/// How a gradient goes on beyond its first and last offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Spread {
+    /// The end colors go on.
+    #[default]
+    Pad,
+    /// The gradient starts over.
+    Repeat,
+    /// The gradient goes back and forth.
+    Reflect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shape {
+    Linear { x0: f32, y0: f32, x1: f32, y1: f32 },
+    Radial { cx: f32, cy: f32, radius: f32 },
+    Conic { cx: f32, cy: f32, angle: f32 },
+}
+
+/// Colors changing along a line, out from a center or around it, through
+/// color stops at offsets from 0 to 1.
+///
+/// Its coordinates are in user space, and go through the transform of the
+/// canvas like those of paths.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    shape: Shape,
+    /// Offsets and straight alpha colors, in order of offset.
+    stops: Vec<(f32, u32)>,
+    spread: Spread,
+    color_space: ColorSpace,
+}
+
+impl Gradient {
+    fn new(shape: Shape) -> Self {
+        Gradient {
+            shape,
+            stops: Vec::new(),
+            spread: Spread::Pad,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    /// From `x0`, `y0` at offset 0 to `x1`, `y1` at offset 1, with the same
+    /// color along every line across.
+    pub fn linear(x0: f32, y0: f32, x1: f32, y1: f32) -> Self {
+        Self::new(Shape::Linear { x0, y0, x1, y1 })
+    }
+
+    /// From `cx`, `cy` at offset 0 out to the circle of `radius` around it
+    /// at offset 1.
+    pub fn radial(cx: f32, cy: f32, radius: f32) -> Self {
+        Self::new(Shape::Radial { cx, cy, radius })
+    }
+
+    /// Around `cx`, `cy`, clockwise from offset 0 at `angle` radians from
+    /// the x axis back to offset 1 there.
+    pub fn conic(cx: f32, cy: f32, angle: f32) -> Self {
+        Self::new(Shape::Conic { cx, cy, angle })
+    }
+
+    /// Adds a stop of the straight alpha ARGB8888 `color` at `offset`. Two
+    /// stops at the same offset make a hard edge between their colors.
+    pub fn stop(mut self, offset: f32, color: u32) -> Self {
+        let offset = offset.clamp(0.0, 1.0);
+        let index = self.stops.iter().take_while(|&&(other, _)| other <= offset).count();
+        self.stops.insert(index, (offset, color));
+        self
+    }
+
+    /// How the gradient goes on beyond its ends, padded by default. Conic
+    /// gradients go round once either way.
+    pub fn spread(mut self, spread: Spread) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    /// Mixes the colors between stops in `space`, sRGB by default like
+    /// CSS does.
+    pub fn color_space(mut self, space: ColorSpace) -> Self {
+        self.color_space = space;
+        self
+    }
+
+    /// Where `x`, `y` is along the gradient, from 0 to 1 once spread.
+    fn offset(&self, x: f32, y: f32) -> f32 {
+        let offset = match self.shape {
+            Shape::Linear { x0, y0, x1, y1 } => {
+                let (dx, dy) = (x1 - x0, y1 - y0);
+                let length = dx * dx + dy * dy;
+                if length > 0.0 {
+                    ((x - x0) * dx + (y - y0) * dy) / length
+                } else {
+                    0.0
+                }
+            }
+            Shape::Radial { cx, cy, radius } if radius > 0.0 => (x - cx).hypot(y - cy) / radius,
+            Shape::Radial { .. } => 1.0,
+            Shape::Conic { cx, cy, angle } => {
+                return ((y - cy).atan2(x - cx) - angle).rem_euclid(TAU) / TAU;
+            }
+        };
+        match self.spread {
+            Spread::Pad => offset.clamp(0.0, 1.0),
+            Spread::Repeat => offset.rem_euclid(1.0),
+            Spread::Reflect => 1.0 - (offset.rem_euclid(2.0) - 1.0).abs(),
+        }
+    }
+
+    /// The premultiplied color at `offset`, mixed from the stops around it.
+    fn color_at(&self, offset: f32) -> u32 {
+        let after = self.stops.iter().position(|&(stop, _)| stop > offset);
+        let (start, end) = match after {
+            None => return self.stops.last().map_or(0, |&(_, color)| premultiply(color)),
+            Some(0) => return premultiply(self.stops[0].1),
+            Some(after) => (self.stops[after - 1], self.stops[after]),
+        };
+        let space = self.color_space;
+        let (from, to) = (
+            color::unpack(premultiply(start.1), space),
+            color::unpack(premultiply(end.1), space),
+        );
+        let mix = (offset - start.0) / (end.0 - start.0);
+        let mut mixed = [0.0; 4];
+        for (channel, (from, to)) in mixed.iter_mut().zip(from.iter().zip(&to)) {
+            *channel = from + (to - from) * mix;
+        }
+        color::pack(mixed, space)
+    }
+}
+
+/// An image repeated in every direction, with a copy's top left corner
+/// at the origin of user space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    image: Rc<Image>,
+    transform: Transform,
+    filter: Filter,
+}
+
+impl Pattern {
+    /// Repeats `image`, smoothed by the bilinear filter when scaled.
+    pub fn new<I: Into<Rc<Image>>>(image: I) -> Self {
+        Pattern {
+            image: image.into(),
+            transform: Transform::IDENTITY,
+            filter: Filter::Bilinear,
+        }
+    }
+
+    /// Places the image through `transform`, to move, scale or rotate it,
+    /// before the transform of the canvas.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Picks pixels with `filter` when the image is drawn at another size.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// The premultiplied color at `u`, `v` in the repeated image.
+    fn color_at(&self, u: f32, v: f32) -> u32 {
+        let image = &*self.image;
+        let (width, height) = (image.width() as i64, image.height() as i64);
+        if width == 0 || height == 0 {
+            return 0;
+        }
+        let wrap = |position: i64, size: i64| position.rem_euclid(size) as usize;
+        match self.filter {
+            Filter::Nearest => image.pixel(wrap(u.floor() as i64, width), wrap(v.floor() as i64, height)),
+            Filter::Bilinear => {
+                let (u, v) = (u - 0.5, v - 0.5);
+                let (left, top) = (u.floor(), v.floor());
+                let (fx, fy) = (u - left, v - top);
+                let (x0, y0) = (left as i64, top as i64);
+                mix(&[
+                    (image.pixel(wrap(x0, width), wrap(y0, height)), (1.0 - fx) * (1.0 - fy)),
+                    (image.pixel(wrap(x0 + 1, width), wrap(y0, height)), fx * (1.0 - fy)),
+                    (image.pixel(wrap(x0, width), wrap(y0 + 1, height)), (1.0 - fx) * fy),
+                    (image.pixel(wrap(x0 + 1, width), wrap(y0 + 1, height)), fx * fy),
+                ])
+            }
+        }
+    }
+}
+
+/// The colors of a paint at the pixels of a canvas, for one fill.
+pub(crate) enum Shader<'p> {
+    Solid(u32),
+    Gradient {
+        inverse: Transform,
+        gradient: &'p Gradient,
+        colors: Vec<u32>,
+    },
+    Pattern {
+        inverse: Transform,
+        pattern: &'p Pattern,
+    },
+    /// A transform that flattens everything leaves nothing to paint.
+    Nothing,
+}
+
+impl<'p> Shader<'p> {
+    /// Shades pixels with `paint`, whose coordinates go through `transform`.
+    pub fn new(paint: &'p Paint, transform: &Transform) -> Self {
+        match paint {
+            Paint::Solid(color) => Shader::Solid(premultiply(*color)),
+            Paint::Gradient(gradient) => match transform.invert() {
+                Some(inverse) => Shader::Gradient {
+                    inverse,
+                    gradient,
+                    colors: (0..GRADIENT_STEPS)
+                        .map(|step| gradient.color_at(step as f32 / (GRADIENT_STEPS - 1) as f32))
+                        .collect(),
+                },
+                None => Shader::Nothing,
+            },
+            Paint::Pattern(pattern) => match pattern.transform.then(transform).invert() {
+                Some(inverse) => Shader::Pattern { inverse, pattern },
+                None => Shader::Nothing,
+            },
+        }
+    }
+
+    /// The premultiplied color at the center of the pixel `x`, `y`.
+    pub fn color(&self, x: usize, y: usize) -> u32 {
+        let center = (x as f32 + 0.5, y as f32 + 0.5);
+        match self {
+            Shader::Solid(color) => *color,
+            Shader::Gradient {
+                inverse,
+                gradient,
+                colors,
+            } => {
+                let (x, y) = inverse.apply(center.0, center.1);
+                let offset = gradient.offset(x, y);
+                if offset.is_nan() {
+                    return 0;
+                }
+                colors[(offset * (GRADIENT_STEPS - 1) as f32).round() as usize]
+            }
+            Shader::Pattern { inverse, pattern } => {
+                let (u, v) = inverse.apply(center.0, center.1);
+                if !u.is_finite() || !v.is_finite() {
+                    return 0;
+                }
+                pattern.color_at(u, v)
+            }
+            Shader::Nothing => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shade(paint: &Paint, x: usize, y: usize) -> u32 {
+        Shader::new(paint, &Transform::IDENTITY).color(x, y)
+    }
+
+    #[test]
+    fn stops_are_mixed_in_order() {
+        // Added out of order, with a hard edge in the middle.
+        let gradient = Gradient::linear(0.0, 0.0, 100.0, 0.0)
+            .stop(1.0, 0xFF00_00FF)
+            .stop(0.0, 0xFFFF_0000)
+            .stop(0.5, 0xFF00_0000)
+            .stop(0.5, 0xFFFF_FFFF);
+        assert_eq!(gradient.color_at(0.0), 0xFFFF_0000);
+        assert_eq!(gradient.color_at(0.25), 0xFF80_0000);
+        assert_eq!(gradient.color_at(0.5), 0xFFFF_FFFF);
+        assert_eq!(gradient.color_at(0.75), 0xFF80_80FF);
+        assert_eq!(gradient.color_at(1.0), 0xFF00_00FF);
+        assert_eq!(Gradient::radial(0.0, 0.0, 1.0).color_at(0.5), 0);
+    }
+
+    #[test]
+    fn linear_light_mixes_brighter() {
+        let gradient = Gradient::linear(0.0, 0.0, 1.0, 0.0).stop(0.0, 0xFF00_0000).stop(1.0, 0xFFFF_FFFF);
+        assert_eq!(gradient.color_at(0.5), 0xFF80_8080);
+        let gradient = gradient.color_space(ColorSpace::LinearRgb);
+        assert_eq!(gradient.color_at(0.5), 0xFFBC_BCBC);
+    }
+
+    #[test]
+    fn gradients_spread_beyond_their_ends() {
+        let offsets = |spread| {
+            let gradient = Gradient::linear(10.0, 0.0, 20.0, 0.0).spread(spread);
+            [5.0, 15.0, 25.0, 28.0].map(|x| (gradient.offset(x, 7.0) * 10.0).round() / 10.0)
+        };
+        assert_eq!(offsets(Spread::Pad), [0.0, 0.5, 1.0, 1.0]);
+        assert_eq!(offsets(Spread::Repeat), [0.5, 0.5, 0.5, 0.8]);
+        assert_eq!(offsets(Spread::Reflect), [0.5, 0.5, 0.5, 0.2]);
+        let radial = Gradient::radial(0.0, 0.0, 10.0);
+        assert_eq!(radial.offset(6.0, 8.0), 1.0);
+        assert_eq!(radial.offset(3.0, 4.0), 0.5);
+        // Clockwise from straight up, as y grows downwards.
+        let conic = Gradient::conic(0.0, 0.0, -TAU / 4.0);
+        assert_eq!(conic.offset(1.0, 0.0), 0.25);
+        assert_eq!(conic.offset(-1.0, 0.0), 0.75);
+    }
+
+    #[test]
+    fn patterns_repeat_every_way() {
+        let image = Image::new(2, 1, vec![0xFF00_0000, 0xFFFF_FFFF]);
+        let pattern = Paint::from(Pattern::new(image).filter(Filter::Nearest));
+        let row: Vec<_> = (0..5).map(|x| shade(&pattern, x, 3)).collect();
+        assert_eq!(row, [0xFF00_0000, 0xFFFF_FFFF, 0xFF00_0000, 0xFFFF_FFFF, 0xFF00_0000]);
+        let moved = match pattern {
+            Paint::Pattern(pattern) => Paint::from(pattern.transform(Transform::translate(-1.0, 0.0))),
+            _ => unreachable!(),
+        };
+        assert_eq!(shade(&moved, 0, 0), 0xFFFF_FFFF);
+        let flat = Paint::from(Gradient::linear(0.0, 0.0, 1.0, 0.0).stop(0.0, 0xFFFF_FFFF));
+        assert_eq!(Shader::new(&flat, &Transform::scale(0.0, 1.0)).color(0, 0), 0);
+    }
+}
@@ -0,0 +1,239 @@
+use super::{Point, Transform};
+
+/// How far flattened curves may stray from the real ones, in pixels.
+const TOLERANCE: f32 = 0.1;
+
+/// The control points of cubic Béziers approximating a quarter circle
+/// are this far along the tangents, as a fraction of the radius.
+const KAPPA: f32 = 0.552_284_8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    MoveTo(Point),
+    LineTo(Point),
+    QuadTo(Point, Point),
+    CubicTo(Point, Point, Point),
+    Close,
+}
+
+/// Outlines made of lines and Bézier curves, in user space.
+///
+/// Each `move_to` starts a new subpath. Filling closes subpaths on its
+/// own, stroking only strokes the closing line of those that were `close`d.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    commands: Vec<Command>,
+}
+
+/// A subpath flattened into line segments.
+pub(super) struct Polyline {
+    pub points: Vec<Point>,
+    pub closed: bool,
+}
+
+impl Path {
+    /// An empty path.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A rectangle with its top left corner at `x`, `y`.
+    pub fn rect(x: f32, y: f32, width: f32, height: f32) -> Self {
+        let mut path = Path::new();
+        path.move_to(x, y);
+        path.line_to(x + width, y);
+        path.line_to(x + width, y + height);
+        path.line_to(x, y + height);
+        path.close();
+        path
+    }
+
+    /// A rectangle with corners rounded by `radius`, which is at most half
+    /// the shorter side.
+    pub fn rounded_rect(x: f32, y: f32, width: f32, height: f32, radius: f32) -> Self {
+        let r = radius.min(width / 2.0).min(height / 2.0).max(0.0);
+        let k = r * (1.0 - KAPPA);
+        let (right, bottom) = (x + width, y + height);
+        let mut path = Path::new();
+        path.move_to(x + r, y);
+        path.line_to(right - r, y);
+        path.cubic_to(right - k, y, right, y + k, right, y + r);
+        path.line_to(right, bottom - r);
+        path.cubic_to(right, bottom - k, right - k, bottom, right - r, bottom);
+        path.line_to(x + r, bottom);
+        path.cubic_to(x + k, bottom, x, bottom - k, x, bottom - r);
+        path.line_to(x, y + r);
+        path.cubic_to(x, y + k, x + k, y, x + r, y);
+        path.close();
+        path
+    }
+
+    /// A circle around `x`, `y`.
+    pub fn circle(x: f32, y: f32, radius: f32) -> Self {
+        let (r, k) = (radius, radius * KAPPA);
+        let mut path = Path::new();
+        path.move_to(x + r, y);
+        path.cubic_to(x + r, y + k, x + k, y + r, x, y + r);
+        path.cubic_to(x - k, y + r, x - r, y + k, x - r, y);
+        path.cubic_to(x - r, y - k, x - k, y - r, x, y - r);
+        path.cubic_to(x + k, y - r, x + r, y - k, x + r, y);
+        path.close();
+        path
+    }
+
+    /// A straight line, to be stroked.
+    pub fn line(x0: f32, y0: f32, x1: f32, y1: f32) -> Self {
+        let mut path = Path::new();
+        path.move_to(x0, y0);
+        path.line_to(x1, y1);
+        path
+    }
+
+    /// Starts a new subpath at `x`, `y`.
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.commands.push(Command::MoveTo(Point { x, y }));
+    }
+
+    /// Adds a line to `x`, `y`.
+    pub fn line_to(&mut self, x: f32, y: f32) {
+        self.commands.push(Command::LineTo(Point { x, y }));
+    }
+
+    /// Adds a quadratic Bézier curve through the control point `cx`, `cy`
+    /// to `x`, `y`.
+    pub fn quad_to(&mut self, cx: f32, cy: f32, x: f32, y: f32) {
+        self.commands
+            .push(Command::QuadTo(Point { x: cx, y: cy }, Point { x, y }));
+    }
+
+    /// Adds a cubic Bézier curve through two control points to `x`, `y`.
+    pub fn cubic_to(&mut self, c1x: f32, c1y: f32, c2x: f32, c2y: f32, x: f32, y: f32) {
+        self.commands.push(Command::CubicTo(
+            Point { x: c1x, y: c1y },
+            Point { x: c2x, y: c2y },
+            Point { x, y },
+        ));
+    }
+
+    /// Closes the subpath with a line back to where it started.
+    pub fn close(&mut self) {
+        self.commands.push(Command::Close);
+    }
+
+    /// The subpaths as line segments through transformed points.
+    pub(super) fn flatten(&self, transform: &Transform) -> Vec<Polyline> {
+        let mut polylines = Vec::new();
+        let mut current: Vec<Point> = Vec::new();
+        let finish = |points: &mut Vec<Point>, closed, polylines: &mut Vec<Polyline>| {
+            if points.len() > 1 {
+                polylines.push(Polyline {
+                    points: std::mem::take(points),
+                    closed,
+                });
+            }
+            points.clear();
+        };
+        for command in &self.commands {
+            match *command {
+                Command::MoveTo(point) => {
+                    finish(&mut current, false, &mut polylines);
+                    current.push(transform.point(point));
+                }
+                Command::LineTo(point) => {
+                    let point = transform.point(point);
+                    start_if_empty(&mut current, point);
+                    current.push(point);
+                }
+                Command::QuadTo(control, end) => {
+                    let (control, end) = (transform.point(control), transform.point(end));
+                    start_if_empty(&mut current, control);
+                    let start = *current.last().unwrap();
+                    let dd = (start - control * 2.0 + end).length();
+                    let segments = segments(2.0 * dd);
+                    for i in 1..=segments {
+                        let t = i as f32 / segments as f32;
+                        let mt = 1.0 - t;
+                        current.push(start * (mt * mt) + control * (2.0 * mt * t) + end * (t * t));
+                    }
+                }
+                Command::CubicTo(control1, control2, end) => {
+                    let control1 = transform.point(control1);
+                    let (control2, end) = (transform.point(control2), transform.point(end));
+                    start_if_empty(&mut current, control1);
+                    let start = *current.last().unwrap();
+                    let dd = (start - control1 * 2.0 + control2)
+                        .length()
+                        .max((control1 - control2 * 2.0 + end).length());
+                    let segments = segments(6.0 * dd);
+                    for i in 1..=segments {
+                        let t = i as f32 / segments as f32;
+                        let mt = 1.0 - t;
+                        current.push(
+                            start * (mt * mt * mt)
+                                + control1 * (3.0 * mt * mt * t)
+                                + control2 * (3.0 * mt * t * t)
+                                + end * (t * t * t),
+                        );
+                    }
+                }
+                Command::Close => {
+                    let start = current.first().copied();
+                    finish(&mut current, true, &mut polylines);
+                    // Drawing on after a close starts where it was closed.
+                    current.extend(start);
+                }
+            }
+        }
+        finish(&mut current, false, &mut polylines);
+        polylines
+    }
+}
+
+/// A subpath without a `move_to` starts at its first point.
+fn start_if_empty(points: &mut Vec<Point>, point: Point) {
+    if points.is_empty() {
+        points.push(point);
+    }
+}
+
+/// How many lines approximate a curve whose second derivative is at most
+/// `max_second_derivative` long within `TOLERANCE`.
+fn segments(max_second_derivative: f32) -> usize {
+    let segments = (max_second_derivative / (8.0 * TOLERANCE)).sqrt().ceil();
+    if segments.is_finite() {
+        (segments as usize).clamp(1, 1000)
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flattened_circle_stays_within_tolerance() {
+        let transform = Transform::scale(2.0, 2.0);
+        let polylines = Path::circle(10.0, 10.0, 50.0).flatten(&transform);
+        assert_eq!(polylines.len(), 1);
+        assert!(polylines[0].closed);
+        let points = &polylines[0].points;
+        assert!(points.len() > 16);
+        for pair in points.windows(2) {
+            let middle = (pair[0] + pair[1]) * 0.5;
+            let distance = (middle - Point { x: 20.0, y: 20.0 }).length();
+            assert!((distance - 100.0).abs() < 2.0 * TOLERANCE, "{}", distance);
+        }
+    }
+
+    #[test]
+    fn subpaths_are_split_at_move_to() {
+        let mut path = Path::rect(0.0, 0.0, 1.0, 1.0);
+        path.line_to(5.0, 5.0);
+        path.move_to(3.0, 3.0);
+        let polylines = path.flatten(&Transform::IDENTITY);
+        let shapes: Vec<_> = polylines.iter().map(|p| (p.points.len(), p.closed)).collect();
+        // The lonely move_to has nothing to draw.
+        assert_eq!(shapes, vec![(4, true), (2, false)]);
+    }
+}
@@ -0,0 +1,191 @@
+//! Antialiased scan conversion by accumulating signed areas, the way
+//! font-rs does: every edge adds the area it covers to the cells it crosses,
+//! and running sums along each row turn that into coverage.
+
+use super::{Point, Rect};
+
+/// Coverage of polygons within `bounds`, by the nonzero winding rule.
+pub(super) struct Rasterizer {
+    bounds: Rect,
+    /// Two more cells than `bounds` is wide per row, as edges on the right
+    /// border spill into the cells after it.
+    stride: usize,
+    cells: Vec<f32>,
+}
+
+impl Rasterizer {
+    pub fn new(bounds: Rect) -> Self {
+        let stride = bounds.width + 2;
+        Rasterizer {
+            bounds,
+            stride,
+            cells: vec![0.0; stride * bounds.height],
+        }
+    }
+
+    /// Adds a closed polygon through `points`.
+    pub fn polygon(&mut self, points: &[Point]) {
+        for (index, &start) in points.iter().enumerate() {
+            let end = points[(index + 1) % points.len()];
+            self.line(start, end);
+        }
+    }
+
+    /// Adds an edge, split where it leaves the bounds on the left or right.
+    /// The parts outside become vertical edges along the border, which
+    /// cover the same pixels inside.
+    fn line(&mut self, start: Point, end: Point) {
+        let origin = Point {
+            x: self.bounds.x as f32,
+            y: self.bounds.y as f32,
+        };
+        let (start, end) = (start - origin, end - origin);
+        let right = self.bounds.width as f32;
+        let mut cuts = [0.0, 1.0, 1.0, 1.0];
+        let mut count = 1;
+        for border in [0.0, right] {
+            let t = (border - start.x) / (end.x - start.x);
+            if t > 0.0 && t < 1.0 {
+                cuts[count] = t;
+                count += 1;
+            }
+        }
+        cuts[..count].sort_by(f32::total_cmp);
+        cuts[count] = 1.0;
+        for pair in cuts[..=count].windows(2) {
+            let clamp = |t: f32| {
+                let point = start + (end - start) * t;
+                Point {
+                    x: point.x.clamp(0.0, right),
+                    y: point.y,
+                }
+            };
+            self.clamped_line(clamp(pair[0]), clamp(pair[1]));
+        }
+    }
+
+    fn clamped_line(&mut self, p0: Point, p1: Point) {
+        if p0.y == p1.y {
+            return;
+        }
+        let (direction, p0, p1) = if p0.y < p1.y {
+            (1.0, p0, p1)
+        } else {
+            (-1.0, p1, p0)
+        };
+        let dxdy = (p1.x - p0.x) / (p1.y - p0.y);
+        let mut x = p0.x;
+        if p0.y < 0.0 {
+            x -= p0.y * dxdy;
+        }
+        // Negative rows saturate to 0.
+        let first_row = p0.y as usize;
+        let end_row = (p1.y.ceil() as usize).min(self.bounds.height);
+        for y in first_row..end_row {
+            let row = &mut self.cells[y * self.stride..(y + 1) * self.stride];
+            let dy = ((y + 1) as f32).min(p1.y) - (y as f32).max(p0.y);
+            let x_next = x + dxdy * dy;
+            let d = dy * direction;
+            let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+            let x0_floor = x0.floor();
+            let x0i = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1i = x1_ceil as usize;
+            if x1i <= x0i + 1 {
+                // Within one cell: split by where the edge crosses its middle.
+                let xmf = 0.5 * (x + x_next) - x0_floor;
+                row[x0i] += d - d * xmf;
+                row[x0i + 1] += d * xmf;
+            } else {
+                let s = (x1 - x0).recip();
+                let x0f = x0 - x0_floor;
+                let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+                let x1f = x1 - x1_ceil + 1.0;
+                let am = 0.5 * s * x1f * x1f;
+                row[x0i] += d * a0;
+                if x1i == x0i + 2 {
+                    row[x0i + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0f);
+                    row[x0i + 1] += d * (a1 - a0);
+                    for cell in &mut row[x0i + 2..x1i - 1] {
+                        *cell += d * s;
+                    }
+                    let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                    row[x1i - 1] += d * (1.0 - a2 - am);
+                }
+                row[x1i] += d * am;
+            }
+            x = x_next;
+        }
+    }
+
+    /// Calls `blend` with the offset of each pixel within `bounds` and
+    /// how much of it is covered, from 0 to 1, skipping uncovered pixels.
+    pub fn for_each_covered<F: FnMut(usize, usize, f32)>(&self, mut blend: F) {
+        for (y, row) in self.cells.chunks_exact(self.stride).enumerate() {
+            let mut sum = 0.0;
+            for (x, cell) in row[..self.bounds.width].iter().enumerate() {
+                sum += cell;
+                let coverage = sum.abs().min(1.0);
+                // Sums of many cells are a bit off where they should be 0.
+                if coverage > 1.0 / 512.0 {
+                    blend(self.bounds.x + x, self.bounds.y + y, coverage);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(bounds: Rect, points: &[(f32, f32)]) -> Vec<f32> {
+        let mut rasterizer = Rasterizer::new(bounds);
+        let points: Vec<_> = points.iter().map(|&(x, y)| Point { x, y }).collect();
+        rasterizer.polygon(&points);
+        let mut coverage = vec![0.0; bounds.width * bounds.height];
+        rasterizer.for_each_covered(|x, y, c| {
+            coverage[(y - bounds.y) * bounds.width + x - bounds.x] = c
+        });
+        coverage
+    }
+
+    #[test]
+    fn half_covered_pixels_are_half_covered() {
+        let square = [(0.5, 0.0), (2.0, 0.0), (2.0, 1.5), (0.5, 1.5)];
+        let coverage = coverage(Rect::new(0, 0, 2, 2), &square);
+        assert_eq!(coverage, vec![0.5, 1.0, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn either_winding_covers_the_same() {
+        let triangle = [(1.0, 1.0), (7.0, 2.0), (3.0, 6.0)];
+        let mut reversed = triangle;
+        reversed.reverse();
+        let bounds = Rect::new(0, 0, 8, 8);
+        let coverage = coverage(bounds, &triangle);
+        for (a, b) in coverage.iter().zip(self::coverage(bounds, &reversed)) {
+            assert!((a - b).abs() < 1e-5);
+        }
+        // Area of the triangle: half the cross product of two sides.
+        let total: f32 = coverage.iter().sum();
+        assert!((total - 14.0).abs() < 1e-3, "{}", total);
+    }
+
+    #[test]
+    fn shapes_are_clipped_to_the_bounds() {
+        // A square from -2 to 6 seen through the pixels from 2 to 4.
+        let square = [(-2.0, -2.0), (6.0, -2.0), (6.0, 6.0), (-2.0, 6.0)];
+        let coverage = coverage(Rect::new(2, 2, 2, 2), &square);
+        assert_eq!(coverage, vec![1.0; 4]);
+        let diamond = [(3.0, -5.0), (11.0, 3.0), (3.0, 11.0), (-5.0, 3.0)];
+        let inside = self::coverage(Rect::new(0, 0, 6, 6), &diamond);
+        let clipped = self::coverage(Rect::new(2, 2, 2, 2), &diamond);
+        for (index, &c) in clipped.iter().enumerate() {
+            let (x, y) = (2 + index % 2, 2 + index / 2);
+            assert!((c - inside[y * 6 + x]).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::f32::consts::PI;
+
+use super::path::Polyline;
+use super::Point;
+
+/// Outlines of lines `width` pixels wide along `polylines`, as polygons
+/// to fill by the nonzero rule.
+///
+/// Joints are rounded on their outer side, ends are cut square at the end
+/// points. Open polylines become one polygon going out along one side and
+/// back along the other, closed ones a ring of two polygons.
+pub(super) fn stroke(polylines: &[Polyline], width: f32) -> Vec<Vec<Point>> {
+    let half = width / 2.0;
+    let step = arc_step(half);
+    let mut polygons = Vec::new();
+    for polyline in polylines {
+        let mut points = polyline.points.clone();
+        let same = |a: Point, b: Point| (a - b).length() < 1e-4;
+        points.dedup_by(|a, b| same(*a, *b));
+        if polyline.closed && points.len() > 2 && same(points[0], points[points.len() - 1]) {
+            points.pop();
+        }
+        if points.len() < 2 {
+            continue;
+        }
+        if polyline.closed {
+            polygons.push(side(&points, half, step, true));
+            points.reverse();
+            polygons.push(side(&points, half, step, true));
+        } else {
+            let mut outline = side(&points, half, step, false);
+            points.reverse();
+            outline.extend(side(&points, half, step, false));
+            polygons.push(outline);
+        }
+    }
+    polygons
+}
+
+/// The edge of a stroke on the left of `points`, seen along them.
+fn side(points: &[Point], half: f32, step: f32, closed: bool) -> Vec<Point> {
+    let count = points.len();
+    let segments = if closed { count } else { count - 1 };
+    let normal = |index: usize| {
+        let (start, end) = (points[index % count], points[(index + 1) % count]);
+        let along = (end - start) * (half / (end - start).length());
+        Point {
+            x: along.y,
+            y: -along.x,
+        }
+    };
+    let mut outline = Vec::new();
+    if !closed {
+        outline.push(points[0] + normal(0));
+    }
+    let joints = if closed { 0..count } else { 1..count - 1 };
+    for joint in joints {
+        let before = normal((joint + segments - 1) % segments);
+        let after = normal(joint % segments);
+        let center = points[joint];
+        let turn = before.x * after.y - before.y * after.x;
+        if turn >= 0.0 {
+            // The outer side: round it off from one segment to the next.
+            let start = before.y.atan2(before.x);
+            let angle = turn.atan2(before.x * after.x + before.y * after.y);
+            let steps = (angle / step).ceil().max(1.0) as usize;
+            for i in 0..=steps {
+                let (sin, cos) = (start + angle * i as f32 / steps as f32).sin_cos();
+                outline.push(center + Point { x: cos, y: sin } * half);
+            }
+        } else {
+            // The inner side: the edges cross, going through the joint
+            // keeps the winding the same everywhere within the stroke.
+            outline.push(center + before);
+            outline.push(center);
+            outline.push(center + after);
+        }
+    }
+    if !closed {
+        outline.push(points[count - 1] + normal(count - 2));
+    }
+    outline
+}
+
+/// The angle between points on an arc of `radius` pixels for it to be
+/// within a tenth of a pixel.
+fn arc_step(radius: f32) -> f32 {
+    let step = 2.0 * (1.0 - 0.1 / radius.max(0.1)).acos();
+    step.max(PI / 64.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doubled_area(polygon: &[Point]) -> f32 {
+        polygon
+            .iter()
+            .zip(polygon.iter().cycle().skip(1))
+            .map(|(a, b)| a.x * b.y - b.x * a.y)
+            .sum()
+    }
+
+    #[test]
+    fn straight_stroke_is_a_rectangle() {
+        let line = Polyline {
+            points: vec![Point { x: 0.0, y: 0.0 }, Point { x: 10.0, y: 0.0 }],
+            closed: false,
+        };
+        let polygons = stroke(&[line], 4.0);
+        assert_eq!(polygons.len(), 1);
+        assert!((doubled_area(&polygons[0]).abs() - 80.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn closed_strokes_are_rings() {
+        let square = Polyline {
+            points: vec![
+                Point { x: 0.0, y: 0.0 },
+                Point { x: 10.0, y: 0.0 },
+                Point { x: 10.0, y: 10.0 },
+                Point { x: 0.0, y: 10.0 },
+            ],
+            closed: true,
+        };
+        let polygons = stroke(&[square], 2.0);
+        assert_eq!(polygons.len(), 2);
+        let (outer, inner) = (doubled_area(&polygons[0]), doubled_area(&polygons[1]));
+        // Opposite windings cut the inside out of the ring.
+        assert!(outer * inner < 0.0);
+        let ring = (outer.abs() - inner.abs()) / 2.0;
+        // 4 sides of 10 by 2, and 4 quarter circles on the outer corners,
+        // which are polygons a little inside the circle.
+        assert!(ring < 80.0 + PI && ring > 80.0 + PI - 0.4, "{}", ring);
+    }
+}
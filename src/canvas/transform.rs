@@ -0,0 +1,147 @@
+use super::Point;
+
+/// An affine transform from user space to canvas pixels:
+/// `x' = a * x + c * y + e` and `y' = b * x + d * y + f`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    /// Scale and shear of x.
+    pub a: f32,
+    /// Shear of y by x.
+    pub b: f32,
+    /// Shear of x by y.
+    pub c: f32,
+    /// Scale and shear of y.
+    pub d: f32,
+    /// Translation along x.
+    pub e: f32,
+    /// Translation along y.
+    pub f: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    /// Leaves points where they are.
+    pub const IDENTITY: Transform = Transform {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    /// Moves points by `x`, `y`.
+    pub fn translate(x: f32, y: f32) -> Self {
+        Transform {
+            e: x,
+            f: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Scales around the origin.
+    pub fn scale(x: f32, y: f32) -> Self {
+        Transform {
+            a: x,
+            d: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Rotates around the origin by `angle` radians, clockwise since y
+    /// grows downwards.
+    pub fn rotate(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Transform {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    /// Where the point `x`, `y` ends up.
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+
+    /// The transform undoing this one, or `None` if it flattens the plane
+    /// onto a line or a point.
+    pub fn invert(&self) -> Option<Transform> {
+        let determinant = self.a * self.d - self.b * self.c;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let (a, b, c, d) = (
+            self.d / determinant,
+            -self.b / determinant,
+            -self.c / determinant,
+            self.a / determinant,
+        );
+        Some(Transform {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        })
+    }
+
+    pub(super) fn point(&self, point: Point) -> Point {
+        let (x, y) = self.apply(point.x, point.y);
+        Point { x, y }
+    }
+
+    /// How much lengths grow on average, to flatten curves finely enough.
+    pub(super) fn scale_factor(&self) -> f32 {
+        (self.a * self.d - self.b * self.c).abs().sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transforms_compose_in_order() {
+        let transform = Transform::scale(2.0, 3.0).then(&Transform::translate(10.0, 20.0));
+        assert_eq!(transform.apply(1.0, 1.0), (12.0, 23.0));
+        let transform = Transform::translate(10.0, 20.0).then(&Transform::scale(2.0, 3.0));
+        assert_eq!(transform.apply(1.0, 1.0), (22.0, 63.0));
+        let (x, y) = Transform::rotate(std::f32::consts::FRAC_PI_2).apply(1.0, 0.0);
+        assert!(x.abs() < 1e-6 && (y - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn inverted_transforms_undo() {
+        let transform = Transform::rotate(0.5)
+            .then(&Transform::scale(2.0, 3.0))
+            .then(&Transform::translate(10.0, 20.0));
+        let (x, y) = transform.apply(4.0, 5.0);
+        let (x, y) = transform.invert().unwrap().apply(x, y);
+        assert!((x - 4.0).abs() < 1e-4 && (y - 5.0).abs() < 1e-4);
+        assert_eq!(Transform::scale(1.0, 0.0).invert(), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Recording the frames a window sends to the compositor, as a sequence
+//! of PNG files or a Y4M video, see [`Window::record`](crate::Window::record).
+//!
+//! Frames are read back from the shared memory buffers, converted from
+//! whatever pixel format the window renders in, so they are what the
+//! compositor got.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::error::Error;
+use crate::image::Image;
+
+/// Writes every frame of a window, or every few, to files.
+///
+/// Only frames the window draws are recorded: a window that isn't
+/// continuous only adds one when it redraws.
+pub struct Recorder {
+    output: Output,
+    every: usize,
+    /// Frames seen since the recording started, recorded or not.
+    seen: usize,
+    written: usize,
+}
+
+enum Output {
+    #[cfg(feature = "png")]
+    Png(PathBuf),
+    Y4m {
+        path: PathBuf,
+        fps: u32,
+        /// Opened at the first frame, with its size.
+        file: Option<(BufWriter<File>, usize, usize)>,
+        /// Whether frames of another size were skipped already.
+        warned: bool,
+    },
+}
+
+impl Recorder {
+    /// Records frames into `dir` as `frame-000000.png`, `frame-000001.png`
+    /// and so on. The directory is created if needed. Needs the `png`
+    /// feature.
+    #[cfg(feature = "png")]
+    pub fn png_sequence<P: Into<PathBuf>>(dir: P) -> Self {
+        Self::new(Output::Png(dir.into()))
+    }
+
+    /// Records frames into a Y4M video at `path`, played at `fps` frames
+    /// per second, for video tools like ffmpeg to encode. The video has
+    /// the size of the first frame, later frames of another size are left
+    /// out.
+    pub fn y4m<P: Into<PathBuf>>(path: P, fps: u32) -> Self {
+        Self::new(Output::Y4m {
+            path: path.into(),
+            fps: fps.max(1),
+            file: None,
+            warned: false,
+        })
+    }
+
+    fn new(output: Output) -> Self {
+        Recorder {
+            output,
+            every: 1,
+            seen: 0,
+            written: 0,
+        }
+    }
+
+    /// Records only every `frames`th frame, starting with the first.
+    pub fn every(mut self, frames: usize) -> Self {
+        self.every = frames.max(1);
+        self
+    }
+
+    /// How many frames were written so far.
+    pub fn frames_written(&self) -> usize {
+        self.written
+    }
+
+    /// Counts a frame, and records what `read` reads back if it is one
+    /// to record.
+    pub(crate) fn frame<F: FnOnce() -> Image>(&mut self, read: F) -> Result<(), Error> {
+        let due = self.seen.is_multiple_of(self.every);
+        self.seen += 1;
+        if !due {
+            return Ok(());
+        }
+        let image = read();
+        match &mut self.output {
+            #[cfg(feature = "png")]
+            Output::Png(dir) => {
+                std::fs::create_dir_all(&dir).map_err(Error::SaveImage)?;
+                image.save_png(dir.join(format!("frame-{:06}.png", self.written)))?;
+            }
+            Output::Y4m {
+                path,
+                fps,
+                file,
+                warned,
+            } => {
+                if file.is_none() {
+                    let mut out = BufWriter::new(File::create(&path).map_err(Error::SaveImage)?);
+                    write_y4m_header(&mut out, image.width(), image.height(), *fps).map_err(Error::SaveImage)?;
+                    *file = Some((out, image.width(), image.height()));
+                }
+                let (out, width, height) = file.as_mut().unwrap();
+                if (image.width(), image.height()) != (*width, *height) {
+                    if !*warned {
+                        eprintln!(
+                            "warning: leaving {}x{} frames out of the {}x{} video {}",
+                            image.width(),
+                            image.height(),
+                            width,
+                            height,
+                            path.display()
+                        );
+                        *warned = true;
+                    }
+                    return Ok(());
+                }
+                write_y4m_frame(out, &image).map_err(Error::SaveImage)?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Writes out what is still buffered. Dropping a recorder does too,
+    /// but loses the error if that fails.
+    pub fn finish(mut self) -> Result<(), Error> {
+        match &mut self.output {
+            Output::Y4m {
+                file: Some((out, _, _)),
+                ..
+            } => out.flush().map_err(Error::SaveImage),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn write_y4m_header<W: Write>(out: &mut W, width: usize, height: usize, fps: u32) -> io::Result<()> {
+    // Progressive, square pixels, and full resolution chroma.
+    writeln!(out, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", width, height, fps)
+}
+
+/// Writes `image` as a Y4M frame: the planes of Y, Cb and Cr in BT.601
+/// video range. Translucent pixels show as if over black.
+fn write_y4m_frame<W: Write>(out: &mut W, image: &Image) -> io::Result<()> {
+    let count = image.pixels().len();
+    let mut planes = vec![0; count * 3];
+    for (index, &pixel) in image.pixels().iter().enumerate() {
+        let [b, g, r, _] = pixel.to_le_bytes();
+        let (r, g, b) = (f32::from(r), f32::from(g), f32::from(b));
+        let y = 16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0;
+        let cb = 128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0;
+        let cr = 128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0;
+        planes[index] = y.round() as u8;
+        planes[count + index] = cb.round() as u8;
+        planes[2 * count + index] = cr.round() as u8;
+    }
+    out.write_all(b"FRAME\n")?;
+    out.write_all(&planes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn y4m_frames_are_planes_in_video_range() {
+        let image = Image::new(3, 1, vec![0xFF00_0000, 0xFFFF_FFFF, 0xFFFF_0000]);
+        let mut y4m = Vec::new();
+        write_y4m_header(&mut y4m, 3, 1, 30).unwrap();
+        write_y4m_frame(&mut y4m, &image).unwrap();
+        let header = b"YUV4MPEG2 W3 H1 F30:1 Ip A1:1 C444\nFRAME\n";
+        assert_eq!(&y4m[..header.len()], header);
+        // Black, white and red.
+        assert_eq!(&y4m[header.len()..], [16, 235, 81, 128, 128, 90, 128, 128, 240]);
+    }
+
+    #[test]
+    fn only_every_few_frames_of_the_first_size_are_recorded() {
+        let path = std::env::temp_dir().join(format!("wayland-book-rust-{}.y4m", std::process::id()));
+        let mut recorder = Recorder::y4m(&path, 60).every(2);
+        let mut reads = 0;
+        for size in [2, 2, 2, 2, 1, 2] {
+            recorder
+                .frame(|| {
+                    reads += 1;
+                    Image::new(size, 1, vec![0xFF00_0000; size])
+                })
+                .unwrap();
+        }
+        // The 1st, 3rd and 5th frames are read, and the 5th left out.
+        assert_eq!(reads, 3);
+        assert_eq!(recorder.frames_written(), 2);
+        recorder.finish().unwrap();
+        let y4m = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let header = "YUV4MPEG2 W2 H1 F60:1 Ip A1:1 C444\n".len();
+        assert_eq!(y4m.len(), header + 2 * ("FRAME\n".len() + 2 * 3));
+    }
+}
@@ -0,0 +1,173 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use wayland_client::{
+    protocol::{
+        wl_compositor::WlCompositor,
+        wl_shm::{self, WlShm},
+    },
+    Display, EventQueue, Main,
+};
+use wayland_protocols::xdg_shell::client::xdg_wm_base::{self, XdgWmBase};
+
+use crate::error::Error;
+use crate::event_loop::{EventLoop, LoopHandle};
+use crate::globals::Globals;
+use crate::pixel_format::PixelFormat;
+use crate::trace;
+
+/// A connection to the compositor, with the globals every window needs
+/// bound already.
+///
+/// Events are dispatched by `dispatch`, which also waits on the sources of
+/// the event loop, like the pipes clipboard contents arrive through.
+pub struct Connection {
+    display: Display,
+    event_queue: EventQueue,
+    globals: Globals,
+    event_loop: EventLoop,
+    compositor: Main<WlCompositor>,
+    xdg_wm_base: Main<XdgWmBase>,
+    shm: Main<WlShm>,
+    shm_formats: Rc<RefCell<Vec<wl_shm::Format>>>,
+}
+
+impl Connection {
+    /// Connects to the compositor named by `WAYLAND_DISPLAY`.
+    pub fn connect() -> Result<Self, Error> {
+        Self::from_display(Display::connect_to_env()?)
+    }
+
+    /// Sets up a connection made otherwise, for example from a socket
+    /// inherited from the compositor.
+    pub fn from_display(display: Display) -> Result<Self, Error> {
+        let mut event_queue = display.create_event_queue();
+        let attached = display.attach(event_queue.token());
+        let globals = Globals::new(&attached);
+        event_queue
+            .sync_roundtrip(&mut (), |_, _, _| unreachable!())
+            .map_err(|error| Error::from_connection(&display, error))?;
+
+        // Each in the newest version we know how to use.
+        // damage_buffer needs wl_compositor 4.
+        let compositor = globals.bind::<WlCompositor>(1..=4)?;
+        let xdg_wm_base = globals.bind::<XdgWmBase>(1..=2)?;
+        let shm = globals.bind::<WlShm>(1..=1)?;
+        xdg_wm_base.quick_assign(|xdg_wm_base, event, _| {
+            trace::event(&xdg_wm_base, &event);
+            match event {
+                xdg_wm_base::Event::Ping { serial } => xdg_wm_base.pong(serial),
+                _ => (),
+            }
+        });
+        // Every compositor must accept these two, announced or not.
+        let shm_formats = Rc::new(RefCell::new(vec![
+            wl_shm::Format::Argb8888,
+            wl_shm::Format::Xrgb8888,
+        ]));
+        let formats = shm_formats.clone();
+        shm.quick_assign(move |shm, event, _| {
+            trace::event(&shm, &event);
+            if let wl_shm::Event::Format { format } = event {
+                let mut formats = formats.borrow_mut();
+                if !formats.contains(&format) {
+                    formats.push(format);
+                }
+            }
+        });
+        // The formats are sent right after binding.
+        event_queue
+            .sync_roundtrip(&mut (), |event, object, _| trace::raw_event(&event, &object))
+            .map_err(|error| Error::from_connection(&display, error))?;
+
+        Ok(Connection {
+            display,
+            event_queue,
+            globals,
+            event_loop: EventLoop::new(),
+            compositor,
+            xdg_wm_base,
+            shm,
+            shm_formats,
+        })
+    }
+
+    /// The connection itself, for what this crate doesn't wrap.
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// The globals of the compositor, to bind optional ones or follow
+    /// outputs and seats.
+    pub fn globals(&self) -> &Globals {
+        &self.globals
+    }
+
+    /// A handle to add sources to the event loop `dispatch` runs.
+    pub fn loop_handle(&self) -> LoopHandle {
+        self.event_loop.handle()
+    }
+
+    /// Waits until the compositor has handled all requests so far, and
+    /// dispatches the events it sent meanwhile.
+    pub fn roundtrip(&mut self) -> Result<(), Error> {
+        self.event_queue
+            .sync_roundtrip(&mut (), |event, object, _| trace::raw_event(&event, &object))
+            .map(|_| ())
+            .map_err(|error| Error::from_connection(&self.display, error))
+    }
+
+    /// Waits for events from the compositor or the event loop's sources,
+    /// and dispatches them.
+    pub fn dispatch(&mut self) -> Result<(), Error> {
+        self.event_loop
+            .dispatch(&mut self.event_queue)
+            .map_err(|error| Error::from_connection(&self.display, error))
+    }
+
+    /// The formats the compositor accepts shm buffers in: ARGB8888 and
+    /// XRGB8888, which are mandatory, then the others in the order it
+    /// announced them.
+    pub fn shm_formats(&self) -> Vec<wl_shm::Format> {
+        self.shm_formats.borrow().clone()
+    }
+
+    /// Whether the compositor accepts buffers in `format`.
+    pub fn supports(&self, format: PixelFormat) -> bool {
+        self.shm_formats.borrow().contains(&format.to_shm())
+    }
+
+    pub(crate) fn shm_formats_handle(&self) -> Rc<RefCell<Vec<wl_shm::Format>>> {
+        self.shm_formats.clone()
+    }
+
+    pub(crate) fn compositor(&self) -> &Main<WlCompositor> {
+        &self.compositor
+    }
+
+    pub(crate) fn xdg_wm_base(&self) -> &Main<XdgWmBase> {
+        &self.xdg_wm_base
+    }
+
+    pub(crate) fn shm(&self) -> &Main<WlShm> {
+        &self.shm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_compositor::TestCompositor;
+
+    #[test]
+    fn records_the_advertised_shm_formats() {
+        let (_compositor, client) = TestCompositor::start();
+        let connection = &client.connection;
+        let formats = connection.shm_formats();
+        assert_eq!(&formats[..2], [wl_shm::Format::Argb8888, wl_shm::Format::Xrgb8888]);
+        // Announcing the mandatory formats again doesn't list them twice.
+        assert!(!formats[2..].contains(&wl_shm::Format::Argb8888));
+        assert!(connection.supports(PixelFormat::Rgb565));
+        assert!(connection.supports(PixelFormat::Xrgb2101010));
+        assert!(!connection.supports(PixelFormat::Bgr888));
+    }
+}
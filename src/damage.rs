@@ -0,0 +1,159 @@
+//! What changed from one frame to the next, and what buffers drawn some
+//! frames ago missed since.
+
+use std::collections::VecDeque;
+
+use crate::canvas::Rect;
+
+/// Rectangles are merged into their bounding box beyond this many, as
+/// each costs a request and a pass over the canvas.
+const MAX_RECTS: usize = 8;
+
+/// The parts of a canvas that changed, in buffer pixels.
+///
+/// Renderers report what they are going to change into it before each
+/// frame, and only that is repainted and sent to the compositor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Damage {
+    bounds: Rect,
+    rects: Vec<Rect>,
+}
+
+impl Damage {
+    /// No damage yet on a canvas of `width` by `height` pixels.
+    pub fn new(width: usize, height: usize) -> Self {
+        Damage {
+            bounds: Rect::new(0, 0, width, height),
+            rects: Vec::new(),
+        }
+    }
+
+    /// The whole canvas.
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    /// Adds `rect`, clipped to the canvas.
+    pub fn add(&mut self, rect: Rect) {
+        let rect = match rect.intersect(&self.bounds) {
+            Some(rect) => rect,
+            None => return,
+        };
+        if self.rects.iter().any(|damaged| damaged.contains(&rect)) {
+            return;
+        }
+        self.rects.retain(|damaged| !rect.contains(damaged));
+        if self.rects.len() < MAX_RECTS {
+            self.rects.push(rect);
+        } else {
+            let union = self.rects.iter().fold(rect, |union, damaged| union.union(damaged));
+            self.rects = vec![union];
+        }
+    }
+
+    /// Damages the whole canvas.
+    pub fn add_all(&mut self) {
+        self.rects = vec![self.bounds];
+    }
+
+    /// Adds everything `other` damaged.
+    pub fn extend(&mut self, other: &Damage) {
+        for &rect in &other.rects {
+            self.add(rect);
+        }
+    }
+
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// The damaged rectangles. They may overlap.
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects
+    }
+}
+
+/// The damage of the last few frames, newest first.
+pub(crate) struct DamageHistory {
+    frames: VecDeque<Damage>,
+}
+
+impl DamageHistory {
+    /// Buffers older than this are repainted entirely.
+    const LENGTH: usize = 4;
+
+    pub fn new() -> Self {
+        DamageHistory {
+            frames: VecDeque::with_capacity(Self::LENGTH),
+        }
+    }
+
+    pub fn push(&mut self, damage: Damage) {
+        if self.frames.len() == Self::LENGTH {
+            self.frames.pop_back();
+        }
+        self.frames.push_front(damage);
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// What a buffer needs repainted to show the frame damaged by
+    /// `damage`, if it holds the frame from `age` frames ago. Age 0 means
+    /// the buffer holds nothing yet.
+    pub fn repaint(&self, age: usize, damage: &Damage) -> Damage {
+        let mut repaint = damage.clone();
+        if age == 0 || age > self.frames.len() + 1 {
+            repaint.add_all();
+        } else {
+            for missed in self.frames.iter().take(age - 1) {
+                repaint.extend(missed);
+            }
+        }
+        repaint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rects_are_clipped_and_merged() {
+        let mut damage = Damage::new(100, 50);
+        damage.add(Rect::new(90, 40, 20, 20));
+        damage.add(Rect::new(200, 0, 10, 10));
+        damage.add(Rect::new(92, 42, 2, 2));
+        assert_eq!(damage.rects(), [Rect::new(90, 40, 10, 10)]);
+        damage.add(Rect::new(80, 30, 20, 20));
+        assert_eq!(damage.rects(), [Rect::new(80, 30, 20, 20)]);
+        for i in 0..MAX_RECTS {
+            damage.add(Rect::new(i * 2, 0, 1, 1));
+        }
+        assert_eq!(damage.rects(), [Rect::new(0, 0, 100, 50)]);
+    }
+
+    #[test]
+    fn older_buffers_repaint_what_they_missed() {
+        let mut history = DamageHistory::new();
+        let frame = |x| {
+            let mut damage = Damage::new(100, 100);
+            damage.add(Rect::new(x, 0, 1, 1));
+            damage
+        };
+        history.push(frame(1));
+        history.push(frame(2));
+        let current = frame(3);
+
+        assert_eq!(history.repaint(1, &current).rects(), [Rect::new(3, 0, 1, 1)]);
+        assert_eq!(
+            history.repaint(3, &current).rects(),
+            [Rect::new(3, 0, 1, 1), Rect::new(2, 0, 1, 1), Rect::new(1, 0, 1, 1)]
+        );
+        let everything = [Rect::new(0, 0, 100, 100)];
+        assert_eq!(history.repaint(0, &current).rects(), everything);
+        assert_eq!(history.repaint(4, &current).rects(), everything);
+    }
+}
@@ -0,0 +1,152 @@
+use std::io;
+use std::rc::Rc;
+
+use wayland_client::{
+    protocol::{wl_data_device_manager::DndAction, wl_surface::WlSurface},
+    Main,
+};
+
+use super::DataOffer;
+use crate::buffer::Buffer;
+use crate::connection::Connection;
+use crate::error::Error;
+use crate::event_loop::LoopHandle;
+use crate::canvas::Rect;
+use crate::renderer::{Frame, Renderer};
+use crate::window;
+
+/// An offer dragged over one of our surfaces.
+///
+/// After a drop, keep a clone around until the transfer is done and
+/// `finish` has been called.
+#[derive(Clone)]
+pub struct DragOffer {
+    offer: Rc<DataOffer>,
+    serial: u32,
+}
+
+impl DragOffer {
+    pub(super) fn new(offer: DataOffer, serial: u32) -> Self {
+        Self {
+            offer: Rc::new(offer),
+            serial,
+        }
+    }
+
+    /// The MIME types the data is offered in.
+    pub fn mime_types(&self) -> Vec<String> {
+        self.offer.mime_types()
+    }
+
+    /// Whether the data is offered as `mime_type`.
+    pub fn has_mime_type(&self, mime_type: &str) -> bool {
+        self.offer.has_mime_type(mime_type)
+    }
+
+    /// The actions the source of the drag supports.
+    pub fn source_actions(&self) -> DndAction {
+        self.offer.source_actions()
+    }
+
+    /// The action negotiated so far, to be honoured on drop.
+    pub fn action(&self) -> DndAction {
+        self.offer.action()
+    }
+
+    /// Tells the source which MIME type a drop at the current position
+    /// would take, or `None` if it would be refused.
+    pub fn accept(&self, mime_type: Option<&str>) {
+        self.offer.accept(self.serial, mime_type);
+    }
+
+    /// Sets the actions a drop at the current position supports.
+    pub fn set_actions(&self, actions: DndAction, preferred: DndAction) {
+        self.offer.set_actions(actions, preferred);
+    }
+
+    /// Reads the data as `mime_type`, and calls `callback` with it once
+    /// it has all arrived.
+    pub fn receive<F>(&self, mime_type: &str, handle: &LoopHandle, callback: F) -> io::Result<()>
+    where
+        F: FnOnce(io::Result<Vec<u8>>) + 'static,
+    {
+        self.offer.receive(mime_type, handle, callback)
+    }
+
+    /// Tells the source that all transfers after the drop are done.
+    pub fn finish(&self) {
+        self.offer.finish();
+    }
+}
+
+/// Drag and drop events for one surface.
+///
+/// The offer is `None` for drags started by this client without a data source.
+pub enum DropEvent {
+    /// A drag entered the surface at `x`, `y`.
+    Enter {
+        /// The data being dragged.
+        offer: Option<DragOffer>,
+        /// Surface-local position.
+        x: f64,
+        /// Surface-local position.
+        y: f64,
+    },
+    /// The drag moved within the surface.
+    Motion {
+        /// The data being dragged.
+        offer: Option<DragOffer>,
+        /// Timestamp in milliseconds.
+        time: u32,
+        /// Surface-local position.
+        x: f64,
+        /// Surface-local position.
+        y: f64,
+    },
+    /// The drag left the surface, or was cancelled.
+    Leave,
+    /// The data was dropped on the surface.
+    Drop {
+        /// The data dropped, to receive and then finish.
+        offer: Option<DragOffer>,
+    },
+}
+
+/// A surface drawn by a `Renderer` that follows the pointer during a drag.
+pub struct DragIcon {
+    surface: Main<WlSurface>,
+    buffer: Buffer,
+}
+
+impl DragIcon {
+    /// An icon of `width` by `height` pixels, rendered once by `renderer`.
+    pub fn new(
+        connection: &Connection,
+        width: usize,
+        height: usize,
+        renderer: &mut dyn Renderer,
+    ) -> Result<Self, Error> {
+        let mut buffer = Buffer::new(connection.shm(), width, height)?;
+        let frame = Frame {
+            time: None,
+            scale: 1,
+            damage: vec![Rect::new(0, 0, width, height)],
+        };
+        buffer.draw(&mut Vec::new(), &frame.damage, |canvas| renderer.render(canvas, &frame));
+        Ok(Self {
+            surface: connection.compositor().create_surface(),
+            buffer,
+        })
+    }
+
+    pub(super) fn surface(&self) -> &WlSurface {
+        &self.surface
+    }
+
+    /// Commits the drawing, once the surface has its drag icon role.
+    pub(super) fn show(self) {
+        self.buffer.attach_to(&self.surface);
+        window::damage_all(&self.surface);
+        self.surface.commit();
+    }
+}
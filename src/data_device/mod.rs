@@ -0,0 +1,293 @@
+//! The clipboard and drag and drop, through `wl_data_device`.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::rc::Rc;
+
+use wayland_client::{
+    protocol::{
+        wl_data_device::{Event, WlDataDevice},
+        wl_data_device_manager::{DndAction, WlDataDeviceManager},
+        wl_seat::WlSeat,
+        wl_surface::WlSurface,
+    },
+    Main,
+};
+
+use crate::event_loop::LoopHandle;
+use crate::seat::InputSerials;
+
+mod dnd;
+pub(crate) mod offer;
+pub(crate) mod selection;
+pub(crate) mod source;
+
+pub use dnd::{DragIcon, DragOffer, DropEvent};
+pub use offer::DataOffer;
+pub use source::SourceEvent;
+use selection::Selection;
+use crate::trace;
+
+type DropHandler = Box<dyn FnMut(DropEvent)>;
+
+struct Drag {
+    surface: WlSurface,
+    offer: Option<DragOffer>,
+    dropped: bool,
+}
+
+#[derive(Default)]
+struct State {
+    drag: Option<Drag>,
+    drop_handlers: Vec<(WlSurface, DropHandler)>,
+}
+
+/// Calls the drop handler of `surface` without keeping `state` borrowed,
+/// so the handler is free to use the data device.
+fn send_drop_event(state: &RefCell<State>, surface: &WlSurface, event: DropEvent) {
+    let handler = {
+        let mut state = state.borrow_mut();
+        let index = state
+            .drop_handlers
+            .iter()
+            .position(|(handler_surface, _)| handler_surface == surface);
+        index.map(|index| state.drop_handlers.swap_remove(index))
+    };
+    if let Some((surface, mut handler)) = handler {
+        handler(event);
+        let mut state = state.borrow_mut();
+        if !state.drop_handlers.iter().any(|(other, _)| *other == surface) {
+            state.drop_handlers.push((surface, handler));
+        }
+    }
+}
+
+fn handle_event(state: &RefCell<State>, selection: &RefCell<Selection<DataOffer>>, event: Event) {
+    match event {
+        Event::DataOffer { id } => offer::handle(&id),
+        Event::Selection { id } => {
+            Selection::set(selection, id.map(DataOffer::new));
+        }
+        Event::Enter {
+            serial,
+            surface,
+            x,
+            y,
+            id,
+        } => {
+            let offer = id.map(|id| DragOffer::new(DataOffer::new(id), serial));
+            state.borrow_mut().drag = Some(Drag {
+                surface: surface.clone(),
+                offer: offer.clone(),
+                dropped: false,
+            });
+            send_drop_event(state, &surface, DropEvent::Enter { offer, x, y });
+        }
+        Event::Motion { time, x, y } => {
+            let drag = state
+                .borrow()
+                .drag
+                .as_ref()
+                .map(|drag| (drag.surface.clone(), drag.offer.clone()));
+            if let Some((surface, offer)) = drag {
+                send_drop_event(state, &surface, DropEvent::Motion { offer, time, x, y });
+            }
+        }
+        Event::Drop => {
+            let drag = state.borrow_mut().drag.as_mut().map(|drag| {
+                drag.dropped = true;
+                (drag.surface.clone(), drag.offer.clone())
+            });
+            if let Some((surface, offer)) = drag {
+                send_drop_event(state, &surface, DropEvent::Drop { offer });
+            }
+        }
+        Event::Leave => {
+            // After a drop the handler owns the offer, and the leave
+            // that follows is of no interest to it.
+            let drag = state.borrow_mut().drag.take();
+            if let Some(Drag {
+                surface,
+                dropped: false,
+                ..
+            }) = drag
+            {
+                send_drop_event(state, &surface, DropEvent::Leave);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Copy and paste, and drag and drop, through the seat's `wl_data_device`.
+#[derive(Clone)]
+pub struct DataDevice {
+    manager: Main<WlDataDeviceManager>,
+    device: Main<WlDataDevice>,
+    serials: InputSerials,
+    handle: LoopHandle,
+    state: Rc<RefCell<State>>,
+    selection: Rc<RefCell<Selection<DataOffer>>>,
+}
+
+impl DataDevice {
+    /// Offers the selection as `mime_types`, using the serial of the
+    /// latest key press. `send` writes the data as the requested type.
+    ///
+    /// Returns `false` if the seat has no keyboard focus to take a serial from.
+    pub fn set_selection<F>(&self, mime_types: &[&str], send: F) -> bool
+    where
+        F: FnMut(&str, File) + 'static,
+    {
+        let serial = match self.serials.keyboard.get() {
+            Some(serial) => serial,
+            None => return false,
+        };
+        let source = source::create(self.manager.create_data_source(), mime_types, send);
+        self.device.set_selection(Some(&source), serial);
+        true
+    }
+
+    /// Clears our selection. Returns `false` without a keyboard serial to
+    /// do it with.
+    pub fn clear_selection(&self) -> bool {
+        match self.serials.keyboard.get() {
+            Some(serial) => {
+                self.device.set_selection(None, serial);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The MIME types of the current selection, empty if there is none.
+    pub fn selection_mime_types(&self) -> Vec<String> {
+        self.selection
+            .borrow()
+            .offer()
+            .map(DataOffer::mime_types)
+            .unwrap_or_default()
+    }
+
+    /// Receives the current selection as `mime_type` on the event loop.
+    pub fn receive_selection<F>(&self, mime_type: &str, callback: F) -> io::Result<()>
+    where
+        F: FnOnce(io::Result<Vec<u8>>) + 'static,
+    {
+        match self.selection.borrow().offer() {
+            Some(offer) => offer.receive(mime_type, &self.handle, callback),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no selection")),
+        }
+    }
+
+    /// Calls `callback` every time the selection changes, with `None` when
+    /// it was cleared. It is only sent while one of our surfaces has
+    /// keyboard focus.
+    pub fn on_selection<F>(&self, callback: F)
+    where
+        F: FnMut(Option<&DataOffer>) + 'static,
+    {
+        self.selection.borrow_mut().on_change(callback);
+    }
+
+    /// Makes `surface` a drop target, with `handler` receiving the drag and
+    /// drop events while a drag is over it.
+    ///
+    /// The handler should `accept` a MIME type and `set_actions` on enter,
+    /// and `receive` and `finish` the offer on drop.
+    pub fn set_drop_handler<F>(&self, surface: &WlSurface, handler: F)
+    where
+        F: FnMut(DropEvent) + 'static,
+    {
+        let mut state = self.state.borrow_mut();
+        state.drop_handlers.retain(|(other, _)| other != surface);
+        state.drop_handlers.push((surface.clone(), Box::new(handler)));
+    }
+
+    /// Stops `surface` from being a drop target.
+    pub fn remove_drop_handler(&self, surface: &WlSurface) {
+        self.state
+            .borrow_mut()
+            .drop_handlers
+            .retain(|(other, _)| other != surface);
+    }
+
+    /// Starts dragging data offered as `mime_types` from `origin`, using
+    /// the serial of the pointer button still held down.
+    ///
+    /// `send` writes the data as the type requested by the target, and
+    /// `on_event` follows the rest of the drag. The icon is destroyed
+    /// once the drag is over.
+    ///
+    /// Returns `false` if no button is pressed to take a serial from.
+    pub fn start_drag<F, G>(
+        &self,
+        origin: &WlSurface,
+        mime_types: &[&str],
+        actions: DndAction,
+        icon: Option<DragIcon>,
+        send: F,
+        mut on_event: G,
+    ) -> bool
+    where
+        F: FnMut(&str, File) + 'static,
+        G: FnMut(SourceEvent) + 'static,
+    {
+        let serial = match self.serials.button.get() {
+            Some(serial) => serial,
+            None => return false,
+        };
+
+        let icon_surface = icon.as_ref().map(|icon| icon.surface().clone());
+        let source = source::create_with_events(self.manager.create_data_source(), mime_types, send, {
+            let icon_surface = icon_surface.clone();
+            move |event| {
+                let done = matches!(event, SourceEvent::Finished | SourceEvent::Cancelled);
+                on_event(event);
+                if done {
+                    if let Some(surface) = icon_surface.as_ref() {
+                        surface.destroy();
+                    }
+                }
+            }
+        });
+        source.set_actions(actions.to_raw());
+        self.device
+            .start_drag(Some(&source), origin, icon_surface.as_ref(), serial);
+        if let Some(icon) = icon {
+            icon.show();
+        }
+        true
+    }
+}
+
+/// Follows the selection and drags of `seat`.
+pub fn setup(
+    manager: &Main<WlDataDeviceManager>,
+    seat: &Main<WlSeat>,
+    serials: &InputSerials,
+    handle: &LoopHandle,
+) -> DataDevice {
+    let device = manager.get_data_device(seat);
+    let state = Rc::new(RefCell::new(State::default()));
+    let selection = Rc::new(RefCell::new(Selection::default()));
+
+    device.quick_assign({
+        let state = state.clone();
+        let selection = selection.clone();
+        move |device, event, _| {
+            trace::event(&device, &event);
+            handle_event(&state, &selection, event)
+        }
+    });
+
+    DataDevice {
+        manager: manager.clone(),
+        device,
+        serials: serials.clone(),
+        handle: handle.clone(),
+        state,
+        selection,
+    }
+}
@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use wayland_client::{
+    protocol::{
+        wl_data_device_manager::DndAction,
+        wl_data_offer::{Event, WlDataOffer},
+    },
+    Interface, Main, MessageGroup, Proxy, ProxyMap,
+};
+
+use crate::event_loop::{pipe, LoopHandle};
+use crate::trace::{self, TracedEvent};
+
+/// What another client announces about an offer before it is used.
+pub(crate) enum Announcement {
+    MimeType(String),
+    SourceActions(DndAction),
+    Action(DndAction),
+}
+
+/// The offer interfaces of the clipboard and the primary selection. They
+/// only differ in name, and in the drag and drop of `wl_data_offer`.
+pub(crate) trait OfferProxy: Interface + AsRef<Proxy<Self>> + From<Proxy<Self>> + Sized {
+    fn announcement(event: Self::Event) -> Option<Announcement>;
+    fn receive(&self, mime_type: String, fd: RawFd);
+    fn destroy(&self);
+}
+
+impl OfferProxy for WlDataOffer {
+    fn announcement(event: Event) -> Option<Announcement> {
+        match event {
+            Event::Offer { mime_type } => Some(Announcement::MimeType(mime_type)),
+            Event::SourceActions { source_actions } => Some(Announcement::SourceActions(
+                DndAction::from_bits_truncate(source_actions),
+            )),
+            Event::Action { dnd_action } => Some(Announcement::Action(
+                DndAction::from_bits_truncate(dnd_action),
+            )),
+            _ => None,
+        }
+    }
+
+    fn receive(&self, mime_type: String, fd: RawFd) {
+        WlDataOffer::receive(self, mime_type, fd);
+    }
+
+    fn destroy(&self) {
+        WlDataOffer::destroy(self);
+    }
+}
+
+pub(crate) struct OfferData {
+    pub mime_types: Vec<String>,
+    pub source_actions: DndAction,
+    pub action: DndAction,
+}
+
+impl Default for OfferData {
+    fn default() -> Self {
+        Self {
+            mime_types: Vec::new(),
+            source_actions: DndAction::None,
+            action: DndAction::None,
+        }
+    }
+}
+
+/// Starts collecting what is announced about a newly introduced offer.
+pub(crate) fn handle<P>(offer: &Main<P>)
+where
+    P: OfferProxy + Sync,
+    P::Event: MessageGroup<Map = ProxyMap> + TracedEvent,
+{
+    offer.as_ref().user_data().set(RefCell::<OfferData>::default);
+    offer.quick_assign(|offer, event, _| {
+        trace::event(&offer, &event);
+        let data = match offer.as_ref().user_data().get::<RefCell<OfferData>>() {
+            Some(data) => data,
+            None => return,
+        };
+        let mut data = data.borrow_mut();
+        match P::announcement(event) {
+            Some(Announcement::MimeType(mime_type)) => data.mime_types.push(mime_type),
+            Some(Announcement::SourceActions(actions)) => data.source_actions = actions,
+            Some(Announcement::Action(action)) => data.action = action,
+            None => (),
+        }
+    });
+}
+
+/// An offer of either kind, destroyed when dropped.
+pub(crate) struct Offer<P: OfferProxy> {
+    proxy: P,
+}
+
+impl<P: OfferProxy> Offer<P> {
+    pub fn new(proxy: P) -> Self {
+        Self { proxy }
+    }
+
+    pub fn proxy(&self) -> &P {
+        &self.proxy
+    }
+
+    pub fn data<T, F: FnOnce(&OfferData) -> T>(&self, f: F) -> T {
+        match self.proxy.as_ref().user_data().get::<RefCell<OfferData>>() {
+            Some(data) => f(&data.borrow()),
+            None => f(&OfferData::default()),
+        }
+    }
+
+    pub fn mime_types(&self) -> Vec<String> {
+        self.data(|data| data.mime_types.clone())
+    }
+
+    pub fn has_mime_type(&self, mime_type: &str) -> bool {
+        self.data(|data| data.mime_types.iter().any(|offered| offered == mime_type))
+    }
+
+    pub fn receive<F>(&self, mime_type: &str, handle: &LoopHandle, callback: F) -> io::Result<()>
+    where
+        F: FnOnce(io::Result<Vec<u8>>) + 'static,
+    {
+        let (read, write) = pipe()?;
+        self.proxy.receive(mime_type.to_owned(), write.as_raw_fd());
+        // The request holds its own duplicate of the write end. Ours has to be
+        // closed for the read end to see end of file.
+        drop(write);
+        handle.read_to_end(read, callback)
+    }
+}
+
+impl<P: OfferProxy> Drop for Offer<P> {
+    fn drop(&mut self) {
+        self.proxy.destroy();
+    }
+}
+
+/// Data offered by another client, e.g. as the current selection.
+///
+/// The offer is destroyed when dropped.
+pub struct DataOffer {
+    offer: Offer<WlDataOffer>,
+}
+
+impl DataOffer {
+    pub(crate) fn new(offer: WlDataOffer) -> Self {
+        Self {
+            offer: Offer::new(offer),
+        }
+    }
+
+    /// The MIME types the data is offered in.
+    pub fn mime_types(&self) -> Vec<String> {
+        self.offer.mime_types()
+    }
+
+    /// Whether the data is offered as `mime_type`.
+    pub fn has_mime_type(&self, mime_type: &str) -> bool {
+        self.offer.has_mime_type(mime_type)
+    }
+
+    /// The actions the source side of a drag supports.
+    pub fn source_actions(&self) -> DndAction {
+        self.offer.data(|data| data.source_actions)
+    }
+
+    /// The action the compositor selected for a drag, from the source
+    /// actions and those set with `set_actions`.
+    pub fn action(&self) -> DndAction {
+        self.offer.data(|data| data.action)
+    }
+
+    /// Tells the source which MIME type a drop would take, if any.
+    pub fn accept(&self, serial: u32, mime_type: Option<&str>) {
+        self.offer.proxy().accept(serial, mime_type.map(str::to_owned));
+    }
+
+    /// Sets the actions a drop supports, and the one preferred.
+    pub fn set_actions(&self, actions: DndAction, preferred: DndAction) {
+        self.offer.proxy().set_actions(actions.to_raw(), preferred.to_raw());
+    }
+
+    /// Tells the source that all transfers after a drop are done.
+    pub fn finish(&self) {
+        self.offer.proxy().finish();
+    }
+
+    /// Asks the offering client for the data as `mime_type`, and calls
+    /// `callback` with all of it once the client closes the pipe.
+    pub fn receive<F>(&self, mime_type: &str, handle: &LoopHandle, callback: F) -> io::Result<()>
+    where
+        F: FnOnce(io::Result<Vec<u8>>) + 'static,
+    {
+        self.offer.receive(mime_type, handle, callback)
+    }
+}
@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+
+type Callback<O> = Box<dyn FnMut(Option<&O>)>;
+
+/// The current selection of a data device, and who to tell when it changes.
+pub(crate) struct Selection<O> {
+    offer: Option<O>,
+    on_change: Option<Callback<O>>,
+}
+
+impl<O> Default for Selection<O> {
+    fn default() -> Self {
+        Self {
+            offer: None,
+            on_change: None,
+        }
+    }
+}
+
+impl<O> Selection<O> {
+    pub fn offer(&self) -> Option<&O> {
+        self.offer.as_ref()
+    }
+
+    pub fn on_change<F>(&mut self, callback: F)
+    where
+        F: FnMut(Option<&O>) + 'static,
+    {
+        self.on_change = Some(Box::new(callback));
+    }
+
+    /// Replaces the selection, and calls the callback without keeping
+    /// `selection` borrowed, so it is free to look at the selection again.
+    pub fn set(selection: &RefCell<Self>, offer: Option<O>) {
+        let callback = {
+            let mut selection = selection.borrow_mut();
+            selection.offer = offer;
+            selection.on_change.take()
+        };
+        if let Some(mut callback) = callback {
+            callback(selection.borrow().offer());
+            selection.borrow_mut().on_change.get_or_insert(callback);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn callback_sees_the_new_selection() {
+        let selection = Rc::new(RefCell::new(Selection::default()));
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        selection.borrow_mut().on_change({
+            let selection = selection.clone();
+            let seen = seen.clone();
+            move |offer: Option<&&str>| {
+                // The selection can be looked at again from the callback.
+                assert_eq!(selection.borrow().offer(), offer);
+                seen.borrow_mut().push(offer.copied());
+            }
+        });
+        Selection::set(&selection, Some("text"));
+        Selection::set(&selection, None);
+        assert_eq!(seen.borrow().as_slice(), &[Some("text"), None]);
+    }
+}
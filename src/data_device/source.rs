@@ -0,0 +1,118 @@
+use std::fs::File;
+use std::os::unix::io::{FromRawFd, RawFd};
+
+use wayland_client::{
+    protocol::{
+        wl_data_device_manager::DndAction,
+        wl_data_source::{Event, WlDataSource},
+    },
+    Interface, Main, MessageGroup, Proxy, ProxyMap,
+};
+use crate::trace::{self, TracedEvent};
+
+/// What happens to a data source used for a drag, besides sending data.
+#[derive(Debug)]
+pub enum SourceEvent {
+    /// The MIME type the target under the pointer accepts, if any.
+    Target(Option<String>),
+    /// The action the compositor selected.
+    Action(DndAction),
+    /// The user dropped, though the target may not have taken the data yet.
+    DropPerformed,
+    /// The target is done with the data. If the action was a move,
+    /// the source should now delete it.
+    Finished,
+    /// The source was replaced or the drag was cancelled.
+    Cancelled,
+}
+
+/// A source event, or a request to send the data.
+pub(crate) enum SourceMessage {
+    Send { mime_type: String, fd: RawFd },
+    Event(SourceEvent),
+}
+
+/// The source interfaces of the clipboard and the primary selection. They
+/// only differ in name, and in the drag and drop of `wl_data_source`.
+pub(crate) trait SourceProxy: Interface + AsRef<Proxy<Self>> + From<Proxy<Self>> + Sized {
+    fn message(event: Self::Event) -> Option<SourceMessage>;
+    fn offer(&self, mime_type: String);
+    fn destroy(&self);
+}
+
+impl SourceProxy for WlDataSource {
+    fn message(event: Event) -> Option<SourceMessage> {
+        let event = match event {
+            Event::Send { mime_type, fd } => return Some(SourceMessage::Send { mime_type, fd }),
+            Event::Target { mime_type } => SourceEvent::Target(mime_type),
+            Event::Action { dnd_action } => {
+                SourceEvent::Action(DndAction::from_bits_truncate(dnd_action))
+            }
+            Event::DndDropPerformed => SourceEvent::DropPerformed,
+            Event::DndFinished => SourceEvent::Finished,
+            Event::Cancelled => SourceEvent::Cancelled,
+            _ => return None,
+        };
+        Some(SourceMessage::Event(event))
+    }
+
+    fn offer(&self, mime_type: String) {
+        WlDataSource::offer(self, mime_type);
+    }
+
+    fn destroy(&self) {
+        WlDataSource::destroy(self);
+    }
+}
+
+/// Offers the newly created `source` as `mime_types`.
+///
+/// `send` is called with the requested MIME type and the write end of a
+/// pipe whenever another client receives the data. The pipe is closed when
+/// the `File` is dropped. Writing blocks the event loop, so large payloads
+/// should be written from a separate thread.
+pub(crate) fn create<P, F>(source: Main<P>, mime_types: &[&str], send: F) -> Main<P>
+where
+    P: SourceProxy + Sync,
+    P::Event: MessageGroup<Map = ProxyMap> + TracedEvent,
+    F: FnMut(&str, File) + 'static,
+{
+    create_with_events(source, mime_types, send, |_| ())
+}
+
+/// Like `create`, also reporting the other source events to `on_event`.
+/// The source is destroyed after `Finished` or `Cancelled`.
+pub(crate) fn create_with_events<P, F, G>(
+    source: Main<P>,
+    mime_types: &[&str],
+    mut send: F,
+    mut on_event: G,
+) -> Main<P>
+where
+    P: SourceProxy + Sync,
+    P::Event: MessageGroup<Map = ProxyMap> + TracedEvent,
+    F: FnMut(&str, File) + 'static,
+    G: FnMut(SourceEvent) + 'static,
+{
+    for mime_type in mime_types {
+        source.offer((*mime_type).to_owned());
+    }
+    source.quick_assign(move |source, event, _| {
+        trace::event(&source, &event);
+        match P::message(event) {
+            Some(SourceMessage::Send { mime_type, fd }) => {
+                let file = unsafe { File::from_raw_fd(fd) };
+                send(&mime_type, file);
+            }
+            Some(SourceMessage::Event(event)) => {
+                let done = matches!(event, SourceEvent::Finished | SourceEvent::Cancelled);
+                on_event(event);
+                if done {
+                    source.destroy();
+                }
+            }
+            None => (),
+        }
+    });
+    source
+}
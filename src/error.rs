@@ -0,0 +1,204 @@
+use std::fmt;
+use std::io;
+
+use wayland_client::{ConnectError, Display, GlobalError, Interface, ProtocolError};
+
+use crate::keybindings::ConfigError;
+use crate::seat::KeymapError;
+
+/// Everything that can go wrong talking to the compositor.
+#[derive(Debug)]
+pub enum Error {
+    /// No Wayland compositor could be connected to.
+    Connect(ConnectError),
+    /// The compositor does not offer a global we can't do without.
+    MissingGlobal {
+        /// The interface name.
+        interface: &'static str,
+    },
+    /// The compositor offers a global, but not in the version we need.
+    GlobalTooOld {
+        /// The interface name.
+        interface: &'static str,
+        /// The version offered.
+        version: u32,
+        /// The oldest version we can use.
+        required: u32,
+    },
+    /// A buffer too large for a shm pool was asked for.
+    BufferTooLarge {
+        /// In pixels.
+        width: usize,
+        /// In pixels.
+        height: usize,
+    },
+    /// Creating the anonymous file backing a shm pool failed.
+    Memfd(nix::Error),
+    /// Resizing or mapping the file backing a shm pool failed.
+    Mmap(io::Error),
+    /// The keyboard is recovering from keymap errors on its own, by
+    /// falling back to a default keymap or raw keycodes.
+    Keymap(KeymapError),
+    /// The key bindings could not be loaded.
+    Keybindings(ConfigError),
+    /// The file in `WAYLAND_TRACE_FILE` could not be opened.
+    TraceFile(io::Error),
+    /// A font file could not be read.
+    FontFile(io::Error),
+    /// A font could not be parsed, for the reason given.
+    InvalidFont(String),
+    /// No installed font matches the pattern.
+    FontNotFound(String),
+    /// An image file could not be read.
+    ImageFile(io::Error),
+    /// An image could not be decoded, for the reason given.
+    InvalidImage(String),
+    /// An image or a recording could not be written.
+    SaveImage(io::Error),
+    /// The compositor killed the connection over a request it didn't accept.
+    Protocol {
+        /// The object the error is about.
+        object_id: u32,
+        /// The interface of that object.
+        interface: &'static str,
+        /// The error code, from the enum of that interface.
+        code: u32,
+        /// What the compositor says went wrong.
+        message: String,
+    },
+    /// Reading from or writing to the connection failed.
+    Io(io::Error),
+}
+
+impl Error {
+    /// Turns the error from binding the global `I` with at least `version`
+    /// into an `Error`, for use with `map_err`.
+    pub fn global<I: Interface>(version: u32) -> impl FnOnce(GlobalError) -> Self {
+        move |error| match error {
+            GlobalError::Missing => Error::MissingGlobal { interface: I::NAME },
+            GlobalError::VersionTooLow(available) => Error::GlobalTooOld {
+                interface: I::NAME,
+                version: available,
+                required: version,
+            },
+        }
+    }
+
+    /// Turns an error from reading or writing the connection into a
+    /// protocol error, if that is what broke the connection.
+    pub fn from_connection(display: &Display, error: io::Error) -> Self {
+        match display.protocol_error() {
+            Some(error) => error.into(),
+            None => Error::Io(error),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Connect(error) => write!(f, "Failed to connect to the compositor: {}", error),
+            Error::MissingGlobal { interface } => {
+                write!(f, "The compositor does not support {}", interface)
+            }
+            Error::GlobalTooOld {
+                interface,
+                version,
+                required,
+            } => write!(
+                f,
+                "The compositor supports {} version {}, but version {} is needed",
+                interface, version, required
+            ),
+            Error::BufferTooLarge { width, height } => {
+                write!(f, "A {}x{} buffer is too large", width, height)
+            }
+            Error::Memfd(error) => write!(f, "Failed to create shared memory: {}", error),
+            Error::Mmap(error) => write!(f, "Failed to map shared memory: {}", error),
+            Error::Keymap(error) => error.fmt(f),
+            Error::Keybindings(error) => error.fmt(f),
+            Error::TraceFile(error) => write!(f, "Failed to open the trace file: {}", error),
+            Error::FontFile(error) => write!(f, "Failed to read the font: {}", error),
+            Error::InvalidFont(reason) => write!(f, "Not a usable font: {}", reason),
+            Error::FontNotFound(pattern) => write!(f, "No installed font matches {}", pattern),
+            Error::ImageFile(error) => write!(f, "Failed to read the image: {}", error),
+            Error::InvalidImage(reason) => write!(f, "Not a usable image: {}", reason),
+            Error::SaveImage(error) => write!(f, "Failed to save the image: {}", error),
+            Error::Protocol {
+                object_id,
+                interface,
+                code,
+                message,
+            } => write!(
+                f,
+                "Protocol error {} on {}@{}: {}",
+                code, interface, object_id, message
+            ),
+            Error::Io(error) => write!(f, "Connection to the compositor failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Connect(error) => Some(error),
+            Error::Memfd(error) => Some(error),
+            Error::Mmap(error)
+            | Error::TraceFile(error)
+            | Error::FontFile(error)
+            | Error::ImageFile(error)
+            | Error::SaveImage(error)
+            | Error::Io(error) => Some(error),
+            Error::Keymap(error) => Some(error),
+            Error::Keybindings(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConnectError> for Error {
+    fn from(error: ConnectError) -> Self {
+        Error::Connect(error)
+    }
+}
+
+impl From<ProtocolError> for Error {
+    fn from(error: ProtocolError) -> Self {
+        Error::Protocol {
+            object_id: error.object_id,
+            interface: error.object_interface,
+            code: error.code,
+            message: error.message,
+        }
+    }
+}
+
+impl From<KeymapError> for Error {
+    fn from(error: KeymapError) -> Self {
+        Error::Keymap(error)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(error: ConfigError) -> Self {
+        Error::Keybindings(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wayland_client::protocol::wl_compositor::WlCompositor;
+
+    #[test]
+    fn global_errors_name_the_interface() {
+        let error = Error::global::<WlCompositor>(4)(GlobalError::VersionTooLow(3));
+        assert_eq!(
+            error.to_string(),
+            "The compositor supports wl_compositor version 3, but version 4 is needed"
+        );
+        let error = Error::global::<WlCompositor>(4)(GlobalError::Missing);
+        assert!(matches!(error, Error::MissingGlobal { interface: "wl_compositor" }));
+    }
+}
@@ -0,0 +1,213 @@
+//! Waits on the Wayland connection together with other file descriptors,
+//! like the pipes clipboard contents arrive through.
+
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::unistd::pipe2;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::rc::Rc;
+
+use wayland_client::EventQueue;
+use crate::trace;
+
+/// What to do with a source after its callback has run.
+pub enum PostAction {
+    /// Keep waiting on the source.
+    Continue,
+    /// Close the source.
+    Remove,
+}
+
+struct Source {
+    file: File,
+    callback: Box<dyn FnMut(&mut File) -> PostAction>,
+}
+
+/// Waits on the wayland connection together with other file descriptors,
+/// like the pipes data offers are received through.
+pub struct EventLoop {
+    handle: LoopHandle,
+}
+
+/// A cloneable handle for adding sources to an `EventLoop`,
+/// also from within event callbacks.
+#[derive(Clone, Default)]
+pub struct LoopHandle {
+    sources: Rc<RefCell<Vec<Source>>>,
+}
+
+impl LoopHandle {
+    /// Calls `callback` every time `file` becomes readable, until it
+    /// returns `PostAction::Remove`.
+    pub fn insert_source<F>(&self, file: File, callback: F)
+    where
+        F: FnMut(&mut File) -> PostAction + 'static,
+    {
+        self.sources.borrow_mut().push(Source {
+            file,
+            callback: Box::new(callback),
+        });
+    }
+
+    /// Reads `file` without blocking until end of file, and hands the
+    /// collected bytes to `callback`.
+    pub fn read_to_end<F>(&self, file: File, callback: F) -> io::Result<()>
+    where
+        F: FnOnce(io::Result<Vec<u8>>) + 'static,
+    {
+        fcntl(file.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK)).map_err(nix_to_io)?;
+        let mut data = Vec::new();
+        let mut callback = Some(callback);
+        self.insert_source(file, move |file| {
+            let mut chunk = [0u8; 4096];
+            loop {
+                match file.read(&mut chunk) {
+                    Ok(0) => {
+                        if let Some(callback) = callback.take() {
+                            callback(Ok(mem::take(&mut data)));
+                        }
+                        return PostAction::Remove;
+                    }
+                    Ok(n) => data.extend_from_slice(&chunk[..n]),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        return PostAction::Continue;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(e) => {
+                        if let Some(callback) = callback.take() {
+                            callback(Err(e));
+                        }
+                        return PostAction::Remove;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Default for EventLoop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventLoop {
+    /// An event loop without sources.
+    pub fn new() -> Self {
+        Self {
+            handle: LoopHandle::default(),
+        }
+    }
+
+    /// A handle to add sources with, also from within callbacks.
+    pub fn handle(&self) -> LoopHandle {
+        self.handle.clone()
+    }
+
+    /// Dispatches pending wayland events, then blocks until either the
+    /// connection or one of the sources is readable and dispatches those.
+    pub fn dispatch(&mut self, event_queue: &mut EventQueue) -> io::Result<()> {
+        event_queue.dispatch_pending(&mut (), |event, object, _| trace::raw_event(&event, &object))?;
+        match event_queue.display().flush() {
+            Err(e) if e.kind() != io::ErrorKind::WouldBlock => return Err(e),
+            _ => (),
+        }
+        let guard = match event_queue.prepare_read() {
+            Some(guard) => guard,
+            None => return Ok(()),
+        };
+
+        let sources = mem::take(&mut *self.handle.sources.borrow_mut());
+        let mut fds = Vec::with_capacity(sources.len() + 1);
+        fds.push(PollFd::new(
+            event_queue.display().get_connection_fd(),
+            PollFlags::POLLIN,
+        ));
+        fds.extend(
+            sources
+                .iter()
+                .map(|source| PollFd::new(source.file.as_raw_fd(), PollFlags::POLLIN)),
+        );
+
+        let result = poll(&mut fds, -1);
+        let ready: Vec<bool> = fds
+            .iter()
+            .map(|fd| fd.revents().is_some_and(|r| !r.is_empty()))
+            .collect();
+
+        let result = match result {
+            Ok(_) if ready[0] => guard.read_events().or_else(|e| match e.kind() {
+                io::ErrorKind::WouldBlock => Ok(()),
+                _ => Err(e),
+            }),
+            Ok(_) | Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => Ok(()),
+            Err(e) => Err(nix_to_io(e)),
+        };
+
+        let mut remaining = Vec::with_capacity(sources.len());
+        for (mut source, ready) in sources.into_iter().zip(ready.into_iter().skip(1)) {
+            if ready {
+                if let PostAction::Remove = (source.callback)(&mut source.file) {
+                    continue;
+                }
+            }
+            remaining.push(source);
+        }
+        let mut sources = self.handle.sources.borrow_mut();
+        remaining.append(&mut sources);
+        *sources = remaining;
+        drop(sources);
+
+        result?;
+        event_queue.dispatch_pending(&mut (), |event, object, _| trace::raw_event(&event, &object))?;
+        Ok(())
+    }
+}
+
+/// Creates a pipe, returning its read and write ends.
+pub(crate) fn pipe() -> io::Result<(File, File)> {
+    let (read, write) = pipe2(OFlag::O_CLOEXEC).map_err(nix_to_io)?;
+    Ok(unsafe { (File::from_raw_fd(read), File::from_raw_fd(write)) })
+}
+
+pub(crate) fn nix_to_io(error: nix::Error) -> io::Error {
+    match error {
+        nix::Error::Sys(errno) => io::Error::from_raw_os_error(errno as i32),
+        error => io::Error::other(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn read_to_end_collects_until_eof() {
+        let handle = LoopHandle::default();
+        let (read, mut write) = pipe().unwrap();
+        let received = Rc::new(RefCell::new(None));
+        handle
+            .read_to_end(read, {
+                let received = received.clone();
+                move |data| {
+                    received.replace(Some(data.unwrap()));
+                }
+            })
+            .unwrap();
+
+        write.write_all(b"hello").unwrap();
+        let mut source = handle.sources.borrow_mut().pop().unwrap();
+        assert!(matches!((source.callback)(&mut source.file), PostAction::Continue));
+        assert!(received.borrow().is_none());
+
+        drop(write);
+        assert!(matches!((source.callback)(&mut source.file), PostAction::Remove));
+        assert_eq!(received.borrow().as_deref(), Some(&b"hello"[..]));
+    }
+}
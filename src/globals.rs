@@ -0,0 +1,258 @@
+use std::cell::RefCell;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use wayland_client::{
+    protocol::{wl_display::WlDisplay, wl_registry::WlRegistry},
+    Attached, GlobalError, GlobalEvent, GlobalManager, Interface, Main, Proxy,
+};
+
+use crate::error::Error;
+use crate::trace::{self, Level, Value};
+
+/// A global advertised by the compositor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalInfo {
+    /// The numeric name the registry knows the global by.
+    pub name: u32,
+    /// The interface name, like `wl_output`.
+    pub interface: String,
+    /// The newest version the compositor supports.
+    pub version: u32,
+}
+
+/// A global of interface `I` coming or going, as seen by `Globals::on_global`.
+pub enum GlobalChange<I: Interface + AsRef<Proxy<I>> + From<Proxy<I>>> {
+    /// The global was announced, or was there already, and is bound.
+    Added {
+        /// The numeric name of the global.
+        name: u32,
+        /// The global, bound in the newest version both sides support.
+        global: Main<I>,
+    },
+    /// The global is gone. Objects created from it should be destroyed
+    /// or released.
+    Removed {
+        /// The numeric name of the global.
+        name: u32,
+    },
+}
+
+type Subscriber = Box<dyn FnMut(&Attached<WlRegistry>, &GlobalEvent)>;
+
+#[derive(Default)]
+struct State {
+    /// Known after the first global is announced.
+    registry: Option<Attached<WlRegistry>>,
+    live: Vec<GlobalInfo>,
+    bound: Vec<GlobalInfo>,
+    subscribers: Vec<Subscriber>,
+}
+
+/// Binds globals in the newest version both we and the compositor
+/// support, and remembers which version that was.
+///
+/// Features added in later versions must be checked for before use, with
+/// `version` here or the `version` of a proxy created from the global.
+///
+/// Globals that may come and go at runtime, like outputs and seats, are
+/// followed with `on_global` instead of bound once.
+pub struct Globals {
+    manager: GlobalManager,
+    state: Rc<RefCell<State>>,
+}
+
+impl Globals {
+    /// Starts listening for globals. They are known after a roundtrip.
+    pub fn new(display: &Attached<WlDisplay>) -> Self {
+        let state = Rc::new(RefCell::new(State::default()));
+        let manager = GlobalManager::new_with_cb(display, {
+            let state = state.clone();
+            move |event, registry, _| update(&state, &registry, &event)
+        });
+        Self { manager, state }
+    }
+
+    /// Binds the global `I` in the newest version within `versions`.
+    pub fn bind<I>(&self, versions: RangeInclusive<u32>) -> Result<Main<I>, Error>
+    where
+        I: Interface + AsRef<Proxy<I>> + From<Proxy<I>>,
+    {
+        let (min, max) = versions.into_inner();
+        let global = self
+            .manager
+            .instantiate_range::<I>(min, max)
+            .map_err(Error::global::<I>(min))?;
+        let mut state = self.state.borrow_mut();
+        // The manager binds the first global of the interface.
+        let name = state
+            .live
+            .iter()
+            .find(|info| info.interface == I::NAME)
+            .map_or(0, |info| info.name);
+        state.bound.retain(|info| info.interface != I::NAME);
+        state.bound.push(GlobalInfo {
+            name,
+            interface: I::NAME.to_owned(),
+            version: global.as_ref().version(),
+        });
+        Ok(global)
+    }
+
+    /// Binds a global the client can do without, telling why if it can't.
+    pub fn bind_optional<I>(&self, versions: RangeInclusive<u32>) -> Option<Main<I>>
+    where
+        I: Interface + AsRef<Proxy<I>> + From<Proxy<I>>,
+    {
+        match self.bind(versions) {
+            Ok(global) => Some(global),
+            Err(error) => {
+                eprintln!("warning: {}, continuing without it", error);
+                None
+            }
+        }
+    }
+
+    /// The version the global `I` was bound with by `bind`, if it was.
+    pub fn version<I: Interface>(&self) -> Option<u32> {
+        self.state
+            .borrow()
+            .bound
+            .iter()
+            .find(|info| info.interface == I::NAME)
+            .map(|info| info.version)
+    }
+
+    /// The globals bound by `bind` so far.
+    pub fn bound(&self) -> Vec<GlobalInfo> {
+        self.state.borrow().bound.clone()
+    }
+
+    /// The globals the compositor currently advertises.
+    pub fn live(&self) -> Vec<GlobalInfo> {
+        self.state.borrow().live.clone()
+    }
+
+    /// The globals of interface `I` the compositor currently advertises.
+    pub fn live_of<I: Interface>(&self) -> Vec<GlobalInfo> {
+        self.state
+            .borrow()
+            .live
+            .iter()
+            .filter(|info| info.interface == I::NAME)
+            .cloned()
+            .collect()
+    }
+
+    /// Binds every global of interface `I`, now and whenever one is added,
+    /// in the newest version within `versions`, and passes it to `callback`.
+    /// `callback` is told as well when one of them is removed.
+    ///
+    /// Globals older than `versions` are skipped with a warning.
+    pub fn on_global<I, F>(&self, versions: RangeInclusive<u32>, mut callback: F)
+    where
+        I: Interface + AsRef<Proxy<I>> + From<Proxy<I>>,
+        F: FnMut(GlobalChange<I>) + 'static,
+    {
+        let (min, max) = versions.into_inner();
+        let mut bound = Vec::new();
+        let mut subscriber = move |registry: &Attached<WlRegistry>, event: &GlobalEvent| match event {
+            GlobalEvent::New {
+                id,
+                interface,
+                version,
+            } if interface == I::NAME => {
+                if *version < min {
+                    let error = Error::global::<I>(min)(GlobalError::VersionTooLow(*version));
+                    eprintln!("warning: {}, skipping it", error);
+                    return;
+                }
+                bound.push(*id);
+                let global = registry.bind::<I>((*version).min(max), *id);
+                callback(GlobalChange::Added { name: *id, global });
+            }
+            GlobalEvent::Removed { id, interface } if interface == I::NAME => {
+                if let Some(index) = bound.iter().position(|name| name == id) {
+                    bound.swap_remove(index);
+                    callback(GlobalChange::Removed { name: *id });
+                }
+            }
+            _ => (),
+        };
+
+        let (registry, live) = {
+            let state = self.state.borrow();
+            (state.registry.clone(), state.live.clone())
+        };
+        if let Some(registry) = registry {
+            for info in live {
+                let event = GlobalEvent::New {
+                    id: info.name,
+                    interface: info.interface,
+                    version: info.version,
+                };
+                subscriber(&registry, &event);
+            }
+        }
+        self.state
+            .borrow_mut()
+            .subscribers
+            .push(Box::new(subscriber));
+    }
+}
+
+/// Keeps the list of live globals up to date and tells the subscribers,
+/// without keeping them borrowed so they are free to subscribe more.
+fn update(state: &RefCell<State>, registry: &Attached<WlRegistry>, event: &GlobalEvent) {
+    trace_event(registry, event);
+    let mut subscribers = {
+        let mut state = state.borrow_mut();
+        state.registry.get_or_insert_with(|| registry.clone());
+        match event {
+            GlobalEvent::New {
+                id,
+                interface,
+                version,
+            } => state.live.push(GlobalInfo {
+                name: *id,
+                interface: interface.clone(),
+                version: *version,
+            }),
+            GlobalEvent::Removed { id, .. } => state.live.retain(|info| info.name != *id),
+        }
+        std::mem::take(&mut state.subscribers)
+    };
+    for subscriber in subscribers.iter_mut() {
+        subscriber(registry, event);
+    }
+    let mut state = state.borrow_mut();
+    subscribers.append(&mut state.subscribers);
+    state.subscribers = subscribers;
+}
+
+/// The registry's events reach us through the `GlobalManager`, so are
+/// traced here rather than where they are dispatched.
+fn trace_event(registry: &Attached<WlRegistry>, event: &GlobalEvent) {
+    let (message, args) = match event {
+        GlobalEvent::New {
+            id,
+            interface,
+            version,
+        } => (
+            "global",
+            vec![
+                ("name", Value::Int(i64::from(*id))),
+                ("interface", Value::Str(interface.clone())),
+                ("version", Value::Int(i64::from(*version))),
+            ],
+        ),
+        GlobalEvent::Removed { id, .. } => ("global_remove", vec![("name", Value::Int(i64::from(*id)))]),
+    };
+    trace::log(&trace::Record {
+        level: Level::Debug,
+        interface: WlRegistry::NAME,
+        id: registry.as_ref().id(),
+        message,
+        args: &args,
+    });
+}
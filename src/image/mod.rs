@@ -0,0 +1,161 @@
+//! Images decoded from files, to draw on a [`Canvas`](crate::Canvas).
+
+use std::fs;
+#[cfg(feature = "png")]
+use std::fs::File;
+#[cfg(feature = "png")]
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::canvas::premultiply;
+use crate::error::Error;
+
+#[cfg(feature = "png")]
+mod png;
+pub(crate) mod ppm;
+mod qoi;
+
+/// Larger images are refused rather than risking running out of memory
+/// over a corrupt header.
+const MAX_PIXELS: usize = 1 << 28;
+
+/// Pixels in ARGB8888 with premultiplied alpha, like those of a canvas,
+/// row after row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+}
+
+/// How pixels are picked from an image drawn at another size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// The nearest pixel, which keeps edges sharp, like for pixel art.
+    Nearest,
+    /// The four nearest pixels mixed by distance, which is smoother. It
+    /// still skips pixels when shrinking to less than half the size.
+    Bilinear,
+}
+
+impl Image {
+    /// An image of `pixels`, premultiplied, which must hold `height` rows
+    /// of `width` pixels.
+    pub fn new(width: usize, height: usize, pixels: Vec<u32>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixels don't fit {}x{}", width, height);
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// An image of straight alpha ARGB8888 `pixels`, premultiplied here.
+    pub fn from_straight(width: usize, height: usize, mut pixels: Vec<u32>) -> Self {
+        for pixel in &mut pixels {
+            *pixel = premultiply(*pixel);
+        }
+        Self::new(width, height, pixels)
+    }
+
+    /// Decodes a PNG, a PPM or PGM, or a QOI image, telling which from the
+    /// first bytes. PNG needs the `png` feature, on by default.
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            #[cfg(feature = "png")]
+            return png::decode(data);
+            #[cfg(not(feature = "png"))]
+            return Err(Error::InvalidImage("PNG support is not built in".to_owned()));
+        }
+        if data.starts_with(b"qoif") {
+            return qoi::decode(data);
+        }
+        if matches!(data, [b'P', b'2' | b'3' | b'5' | b'6', ..]) {
+            return ppm::decode(data);
+        }
+        Err(Error::InvalidImage("unknown format".to_owned()))
+    }
+
+    /// Reads and decodes the image at `path`, see `decode`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::decode(&fs::read(path).map_err(Error::ImageFile)?)
+    }
+
+    /// Encodes the image as PNG into `out`. Needs the `png` feature.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, out: W) -> Result<(), Error> {
+        png::encode(self, out)
+    }
+
+    /// Saves the image as a PNG file at `path`, see `write_png`.
+    #[cfg(feature = "png")]
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let file = File::create(path).map_err(Error::SaveImage)?;
+        let mut out = BufWriter::new(file);
+        self.write_png(&mut out)?;
+        out.flush().map_err(Error::SaveImage)
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// All pixels, row after row.
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    /// The pixel at `x`, `y`, which must be within the image.
+    pub(crate) fn pixel(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// The pixel count of a `width` by `height` image, or an error if it is
+/// empty or unreasonably large.
+fn checked_size(width: usize, height: usize) -> Result<usize, Error> {
+    match width.checked_mul(height) {
+        Some(0) => Err(Error::InvalidImage("the image is empty".to_owned())),
+        Some(pixels) if pixels <= MAX_PIXELS => Ok(pixels),
+        _ => Err(Error::InvalidImage(format!("{}x{} is too large", width, height))),
+    }
+}
+
+fn truncated() -> Error {
+    Error::InvalidImage("the image data is truncated".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_are_told_by_their_first_bytes() {
+        let ppm = Image::decode(b"P6 1 1 255 \x10\x20\x30").unwrap();
+        assert_eq!(ppm.pixels(), [0xFF10_2030]);
+        let qoi = b"qoif\0\0\0\x01\0\0\0\x01\x03\0\xFE\x10\x20\x30\0\0\0\0\0\0\0\x01";
+        assert_eq!(Image::decode(qoi).unwrap(), ppm);
+        let error = Image::decode(b"GIF89a").unwrap_err();
+        assert_eq!(error.to_string(), "Not a usable image: unknown format");
+    }
+
+    #[test]
+    fn huge_or_empty_images_are_refused() {
+        assert!(matches!(Image::decode(b"P6 0 4 255 "), Err(Error::InvalidImage(_))));
+        let huge = b"qoif\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x04\0";
+        let error = Image::decode(huge).unwrap_err();
+        assert_eq!(error.to_string(), "Not a usable image: 4294967295x4294967295 is too large");
+    }
+
+    #[test]
+    fn straight_pixels_are_premultiplied() {
+        let image = Image::from_straight(2, 1, vec![0x80FF_0000, 0xFF00_FF00]);
+        assert_eq!(image.pixels(), [0x8080_0000, 0xFF00_FF00]);
+    }
+}
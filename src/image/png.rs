@@ -0,0 +1,144 @@
+//! PNG images, decoded and encoded by the `png` crate.
+
+use std::io::Write;
+
+use ::png::{BitDepth, ColorType, Decoder, EncodingError, Encoder, Transformations};
+
+use super::{checked_size, truncated, Image};
+use crate::error::Error;
+
+/// Decodes the first frame of a PNG image, in any of its color types and
+/// bit depths. Gamma and color profiles are ignored.
+pub fn decode(data: &[u8]) -> Result<Image, Error> {
+    let invalid = |error: ::png::DecodingError| Error::InvalidImage(error.to_string());
+    let mut decoder = Decoder::new(data);
+    // Palettes, low bit depths and transparency chunks become plain 8 bit
+    // gray or RGB, with or without alpha.
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let (width, height) = reader.info().size();
+    checked_size(width as usize, height as usize)?;
+    // Deflate expands at most 1032 times, and normalizing one bit pixels to
+    // RGBA another 32 times. A header asking for more than that is lying
+    // about the size, and must not get the allocation.
+    if reader.output_buffer_size() > data.len().saturating_mul(1032 * 32) {
+        return Err(truncated());
+    }
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(invalid)?;
+    let argb = |r: u8, g: u8, b: u8, a: u8| u32::from_be_bytes([a, r, g, b]);
+    let rows = buffer.chunks_exact(info.line_size).take(info.height as usize);
+    let samples = rows.flat_map(|row| row[..info.width as usize * info.color_type.samples()].iter());
+    let samples: Vec<u8> = samples.copied().collect();
+    let pixels = match info.color_type {
+        ColorType::Grayscale => samples.iter().map(|&v| argb(v, v, v, 255)).collect(),
+        ColorType::GrayscaleAlpha => samples.chunks_exact(2).map(|p| argb(p[0], p[0], p[0], p[1])).collect(),
+        ColorType::Rgb => samples.chunks_exact(3).map(|p| argb(p[0], p[1], p[2], 255)).collect(),
+        ColorType::Rgba => samples.chunks_exact(4).map(|p| argb(p[0], p[1], p[2], p[3])).collect(),
+        ColorType::Indexed => unreachable!("palettes are expanded"),
+    };
+    Ok(Image::from_straight(info.width as usize, info.height as usize, pixels))
+}
+
+/// Encodes `image` as an 8 bit PNG, without alpha if it is opaque.
+pub fn encode<W: Write>(image: &Image, out: W) -> Result<(), Error> {
+    let opaque = image.pixels().iter().all(|&pixel| pixel >> 24 == 0xFF);
+    let mut encoder = Encoder::new(out, image.width() as u32, image.height() as u32);
+    encoder.set_color(if opaque { ColorType::Rgb } else { ColorType::Rgba });
+    encoder.set_depth(BitDepth::Eight);
+    let mut data = Vec::with_capacity(image.pixels().len() * 4);
+    for &pixel in image.pixels() {
+        let [b, g, r, a] = unpremultiply(pixel).to_le_bytes();
+        data.extend_from_slice(&[r, g, b]);
+        if !opaque {
+            data.push(a);
+        }
+    }
+    let failed = |error: EncodingError| Error::SaveImage(error.into());
+    let mut writer = encoder.write_header().map_err(failed)?;
+    writer.write_image_data(&data).map_err(failed)?;
+    writer.finish().map_err(failed)
+}
+
+/// The straight alpha ARGB8888 color of the premultiplied `color`.
+fn unpremultiply(color: u32) -> u32 {
+    let alpha = color >> 24;
+    if alpha == 0 {
+        return 0;
+    }
+    let channel = |shift: u32| (((color >> shift & 0xFF) * 255 + alpha / 2) / alpha) << shift;
+    alpha << 24 | channel(16) | channel(8) | channel(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(width: u32, color: ColorType, depth: BitDepth, palette: Option<(&[u8], &[u8])>, data: &[u8]) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, width, 1);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        if let Some((palette, transparency)) = palette {
+            encoder.set_palette(palette);
+            encoder.set_trns(transparency);
+        }
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        png
+    }
+
+    #[test]
+    fn rgba_is_premultiplied() {
+        let png = encode(2, ColorType::Rgba, BitDepth::Eight, None, &[255, 0, 0, 128, 1, 2, 3, 255]);
+        let image = Image::decode(&png).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_eq!(image.pixels(), [0x8080_0000, 0xFF01_0203]);
+    }
+
+    #[test]
+    fn other_color_types_are_expanded() {
+        let png = encode(2, ColorType::Grayscale, BitDepth::Sixteen, None, &[0x12, 0x34, 0xFF, 0xFF]);
+        assert_eq!(Image::decode(&png).unwrap().pixels(), [0xFF12_1212, 0xFFFF_FFFF]);
+        // Two bits per pixel, with the second of three colors transparent.
+        let palette = [10, 20, 30, 40, 50, 60, 70, 80, 90];
+        let png = encode(3, ColorType::Indexed, BitDepth::Two, Some((&palette, &[255, 0])), &[0b00_01_10_00]);
+        assert_eq!(Image::decode(&png).unwrap().pixels(), [0xFF0A_141E, 0, 0xFF46_505A]);
+    }
+
+    #[test]
+    fn encoded_images_decode_the_same() {
+        let translucent = Image::new(3, 1, vec![0xFF12_3456, 0x8040_2000, 0]);
+        let opaque = Image::new(1, 2, vec![0xFF00_0000, 0xFFFF_FFFF]);
+        for image in [translucent, opaque] {
+            let mut png = Vec::new();
+            image.write_png(&mut png).unwrap();
+            assert_eq!(Image::decode(&png).unwrap(), image);
+        }
+    }
+
+    #[test]
+    fn huge_headers_are_refused_before_allocating() {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 1 << 14, 1 << 14);
+        encoder.set_color(ColorType::Rgb);
+        let mut writer = encoder.write_header().unwrap();
+        // An empty zlib stream, where 768 megabytes of pixels should be.
+        let empty = [0x78, 0x9C, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01];
+        writer.write_chunk(::png::chunk::IDAT, &empty).unwrap();
+        drop(writer);
+        // Refused for its size, not only later for the missing pixels.
+        match Image::decode(&png) {
+            Err(Error::InvalidImage(message)) => assert!(message.contains("truncated"), "{}", message),
+            _ => panic!("decoded an image without pixel data"),
+        }
+    }
+
+    #[test]
+    fn corrupt_files_are_errors() {
+        let mut png = encode(1, ColorType::Rgb, BitDepth::Eight, None, &[1, 2, 3]);
+        png.truncate(png.len() - 20);
+        assert!(matches!(Image::decode(&png), Err(Error::InvalidImage(_))));
+    }
+}
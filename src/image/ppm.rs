@@ -0,0 +1,133 @@
+//! Netpbm PPM and PGM images, in binary or plain text.
+
+use super::{checked_size, truncated, Image};
+use crate::error::Error;
+
+/// Decodes a PPM (`P3` or `P6`) or PGM (`P2` or `P5`) image.
+pub fn decode(data: &[u8]) -> Result<Image, Error> {
+    let mut reader = Reader { data, position: 2 };
+    let width = reader.number()?;
+    let height = reader.number()?;
+    let max = reader.number()?;
+    if max == 0 || max > 0xFFFF {
+        return Err(invalid(format!("a maximum value of {}", max)));
+    }
+    let count = checked_size(width, height)?;
+    let (channels, plain) = match data[1] {
+        b'2' => (1, true),
+        b'3' => (3, true),
+        b'5' => (1, false),
+        _ => (3, false),
+    };
+    let samples = if plain {
+        (0..count * channels)
+            .map(|_| reader.number())
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        // A single whitespace character ends the header of binary images.
+        let bytes = if max > 0xFF { 2 } else { 1 };
+        let start = reader.position + 1;
+        let raster = data
+            .get(start..)
+            .and_then(|raster| raster.get(..count * channels * bytes))
+            .ok_or_else(truncated)?;
+        raster
+            .chunks_exact(bytes)
+            .map(|sample| sample.iter().fold(0, |value, &byte| value << 8 | usize::from(byte)))
+            .collect()
+    };
+    if samples.iter().any(|&sample| sample > max) {
+        return Err(invalid("a sample above the maximum value".to_owned()));
+    }
+    let scale = |sample: usize| ((sample * 255 + max / 2) / max) as u32;
+    let pixels = samples
+        .chunks_exact(channels)
+        .map(|pixel| match *pixel {
+            [gray] => 0xFF00_0000 | (scale(gray) * 0x01_0101),
+            [r, g, b] => 0xFF00_0000 | scale(r) << 16 | scale(g) << 8 | scale(b),
+            _ => unreachable!(),
+        })
+        .collect();
+    Ok(Image::new(width, height, pixels))
+}
+
+/// Encodes the color of `image` as a binary PPM, dropping alpha.
+#[cfg(test)]
+pub(crate) fn encode(image: &Image) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", image.width(), image.height()).into_bytes();
+    for &pixel in image.pixels() {
+        ppm.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+    }
+    ppm
+}
+
+fn invalid(what: String) -> Error {
+    Error::InvalidImage(format!("PPM with {}", what))
+}
+
+/// Reads the whitespace separated numbers of the header and of plain
+/// images, skipping comments.
+struct Reader<'d> {
+    data: &'d [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn number(&mut self) -> Result<usize, Error> {
+        loop {
+            match self.data.get(self.position) {
+                Some(c) if c.is_ascii_whitespace() => self.position += 1,
+                Some(b'#') => {
+                    while self.data.get(self.position).is_some_and(|&c| c != b'\n') {
+                        self.position += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        let start = self.position;
+        while self.data.get(self.position).is_some_and(u8::is_ascii_digit) {
+            self.position += 1;
+        }
+        let digits = std::str::from_utf8(&self.data[start..self.position]).unwrap_or_default();
+        match digits.parse() {
+            Ok(number) => Ok(number),
+            Err(_) if start == self.data.len() => Err(truncated()),
+            Err(_) => Err(invalid(format!("{:?} where a number should be", self.data[start] as char))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_images_may_have_wide_samples() {
+        let image = decode(b"P6\n# a comment\n2 1\n255\n\x01\x02\x03\xFF\x80\x00").unwrap();
+        assert_eq!(image.pixels(), [0xFF01_0203, 0xFFFF_8000]);
+        let image = decode(b"P5 2 1 65535\n\x00\x00\xFF\xFF").unwrap();
+        assert_eq!(image.pixels(), [0xFF00_0000, 0xFFFF_FFFF]);
+        assert!(decode(b"P6 2 1 255\n\x01\x02\x03").is_err());
+    }
+
+    #[test]
+    fn encoded_images_decode_the_same() {
+        let pixels = (0..6).map(|i| 0xFF00_0000 | (i * 40) << 8).collect();
+        let image = Image::new(3, 2, pixels);
+        assert_eq!(decode(&encode(&image)).unwrap(), image);
+    }
+
+    #[test]
+    fn plain_images_are_scaled_to_8_bits() {
+        let image = decode(b"P3 2 1 15  15 0 0  0 7 15").unwrap();
+        assert_eq!(image.pixels(), [0xFFFF_0000, 0xFF00_77FF]);
+        let image = decode(b"P2 1 2 1 0 1").unwrap();
+        assert_eq!((image.width(), image.height()), (1, 2));
+        assert_eq!(image.pixels(), [0xFF00_0000, 0xFFFF_FFFF]);
+        let error = decode(b"P2 1 1 1 2").unwrap_err();
+        assert_eq!(error.to_string(), "Not a usable image: PPM with a sample above the maximum value");
+        let error = decode(b"P2 1 1 x").unwrap_err();
+        assert_eq!(error.to_string(), "Not a usable image: PPM with 'x' where a number should be");
+    }
+}
@@ -0,0 +1,129 @@
+//! The Quite OK Image format, see <https://qoiformat.org/qoi-specification.pdf>.
+
+use super::{checked_size, truncated, Image};
+use crate::error::Error;
+
+const HEADER: usize = 14;
+
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xC0;
+
+/// Decodes a QOI image. Whether its channels are sRGB or linear isn't
+/// told apart, like everywhere else.
+pub fn decode(data: &[u8]) -> Result<Image, Error> {
+    let header = data.get(..HEADER).ok_or_else(truncated)?;
+    let number = |at: usize| u32::from_be_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]]);
+    let (width, height) = (number(4) as usize, number(8) as usize);
+    if !matches!(header[12], 3 | 4) {
+        return Err(Error::InvalidImage(format!("QOI with {} channels", header[12])));
+    }
+    let count = checked_size(width, height)?;
+
+    // A corrupt header mustn't allocate more than the data could fill, a
+    // run of 62 pixels per byte at most.
+    let mut pixels = Vec::with_capacity(count.min((data.len() - HEADER).saturating_mul(62)));
+    // Pixels are RGBA here, straight.
+    let mut seen = [[0u8; 4]; 64];
+    let mut pixel = [0, 0, 0, 255];
+    let mut bytes = data[HEADER..].iter().copied();
+    let mut next = || bytes.next().ok_or_else(truncated);
+    while pixels.len() < count {
+        let op = next()?;
+        let mut run = 1;
+        match op {
+            OP_RGB => {
+                pixel = [next()?, next()?, next()?, pixel[3]];
+            }
+            OP_RGBA => {
+                pixel = [next()?, next()?, next()?, next()?];
+            }
+            _ => match op & 0xC0 {
+                OP_INDEX => pixel = seen[usize::from(op)],
+                OP_DIFF => {
+                    let diff = |shift: u8| (op >> shift & 0x03).wrapping_sub(2);
+                    pixel[0] = pixel[0].wrapping_add(diff(4));
+                    pixel[1] = pixel[1].wrapping_add(diff(2));
+                    pixel[2] = pixel[2].wrapping_add(diff(0));
+                }
+                OP_LUMA => {
+                    let green = (op & 0x3F).wrapping_sub(32);
+                    let byte = next()?;
+                    pixel[0] = pixel[0].wrapping_add(green.wrapping_add(byte >> 4).wrapping_sub(8));
+                    pixel[1] = pixel[1].wrapping_add(green);
+                    pixel[2] = pixel[2].wrapping_add(green.wrapping_add(byte & 0x0F).wrapping_sub(8));
+                }
+                _ => run = usize::from(op - OP_RUN) + 1,
+            },
+        }
+        let [r, g, b, a] = pixel.map(usize::from);
+        seen[(r * 3 + g * 5 + b * 7 + a * 11) % 64] = pixel;
+        let argb = u32::from_be_bytes([pixel[3], pixel[0], pixel[1], pixel[2]]);
+        let run = run.min(count - pixels.len());
+        pixels.extend(std::iter::repeat_n(argb, run));
+    }
+    Ok(Image::from_straight(width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qoi(width: u8, ops: &[u8]) -> Vec<u8> {
+        let mut data = b"qoif\0\0\0".to_vec();
+        data.extend_from_slice(&[width, 0, 0, 0, 1, 4, 0]);
+        data.extend_from_slice(ops);
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        data
+    }
+
+    #[test]
+    fn every_op_is_decoded() {
+        #[rustfmt::skip]
+        let ops = [
+            OP_RGBA, 0x10, 0x20, 0x30, 0x80,
+            // One each more red and less blue.
+            OP_DIFF | 0b11_10_01,
+            // Green 4 more, red 5 and blue 6 more.
+            OP_LUMA | 36, 0x9A,
+            OP_RUN | 1,
+            // The first pixel, hashed to (16 * 3 + 32 * 5 + 48 * 7 + 128 * 11) % 64.
+            OP_INDEX | 32,
+            OP_RGB, 0xFF, 0xFF, 0xFF,
+        ];
+        let image = decode(&qoi(7, &ops)).unwrap();
+        let straight = [
+            0x8010_2030,
+            0x8011_202F,
+            0x8016_2435,
+            0x8016_2435,
+            0x8016_2435,
+            0x8010_2030,
+            0x80FF_FFFF,
+        ];
+        let expected = Image::from_straight(7, 1, straight.to_vec());
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn runs_stop_at_the_last_pixel() {
+        let image = decode(&qoi(2, &[OP_RUN | 61])).unwrap();
+        assert_eq!(image.pixels(), [0xFF00_0000; 2]);
+        let mut truncated = qoi(2, &[OP_RGB, 1]);
+        truncated.truncate(truncated.len() - 8);
+        assert!(matches!(decode(&truncated), Err(Error::InvalidImage(_))));
+    }
+
+    #[test]
+    fn huge_headers_allocate_only_what_the_data_can_fill() {
+        let mut header = qoi(1, &[]);
+        header.truncate(HEADER);
+        header[4..12].copy_from_slice(&[0, 0, 0x40, 0, 0, 0, 0x40, 0]);
+        header.push(OP_RUN | 61);
+        // 2^28 pixels would be a gigabyte, refused only at the end of the data.
+        assert!(matches!(decode(&header), Err(Error::InvalidImage(_))));
+    }
+}
@@ -0,0 +1,463 @@
+//! Keyboard shortcuts, configurable from a file, mapped to actions.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use wayland_client::protocol::wl_surface::WlSurface;
+
+use crate::seat::{KeyEvent, Modifiers};
+
+/// A key together with the modifiers held, like `Ctrl+Shift+T`.
+///
+/// The key is matched on its first-level keysym, so Shift+t is written
+/// `Shift+T` or `Shift+t`, but never matches a plain `T`. Failing that, it
+/// is matched on the keysym typed, with Shift left out, so `Ctrl++` fires
+/// on Ctrl+Shift+= on a US layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCombo {
+    /// The modifiers that must be held, and no others.
+    pub modifiers: Modifiers,
+    /// The key, as its first-level keysym or the keysym typed.
+    pub keysym: xkb::Keysym,
+}
+
+/// Why a key combo could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// There was no key.
+    Empty,
+    /// Modifiers ending in a `+`, with no key after it.
+    MissingKey(String),
+    /// A modifier other than Ctrl, Shift, Alt or Super.
+    UnknownModifier(String),
+    /// A name that is no keysym.
+    UnknownKey(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Empty key binding"),
+            ParseError::MissingKey(combo) => write!(f, "No key after {:?}", combo),
+            ParseError::UnknownModifier(name) => write!(f, "Unknown modifier {:?}", name),
+            ParseError::UnknownKey(name) => write!(f, "Unknown key {:?}", name),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn parse_modifier(name: &str) -> Result<Modifiers, ParseError> {
+    match name.to_ascii_lowercase().as_str() {
+        "ctrl" | "control" => Ok(Modifiers::CTRL),
+        "shift" => Ok(Modifiers::SHIFT),
+        "alt" | "mod1" => Ok(Modifiers::ALT),
+        "super" | "logo" | "mod4" => Ok(Modifiers::LOGO),
+        _ => Err(ParseError::UnknownModifier(name.to_owned())),
+    }
+}
+
+fn parse_keysym(name: &str) -> Result<xkb::Keysym, ParseError> {
+    // Single characters name the key, not what it types with Shift.
+    let mut chars = name.chars();
+    let name = match (chars.next(), chars.next()) {
+        (Some(c), None) => c.to_lowercase().collect(),
+        _ => name.to_owned(),
+    };
+    name.parse().map_err(|()| ParseError::UnknownKey(name))
+}
+
+impl FromStr for KeyCombo {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The last part is the key, so "Ctrl++" binds the plus key but
+        // "Ctrl+" has no key at all.
+        let s = s.trim();
+        let (modifiers, key) = if s == "+" {
+            ("", "plus")
+        } else if let Some(modifiers) = s.strip_suffix("++") {
+            (modifiers, "plus")
+        } else if s.ends_with('+') {
+            return Err(ParseError::MissingKey(s.to_owned()));
+        } else {
+            match s.rfind('+') {
+                Some(index) => (&s[..index], &s[index + 1..]),
+                None => ("", s),
+            }
+        };
+        if key.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let modifiers = modifiers
+            .split('+')
+            .filter(|name| !name.is_empty())
+            .map(parse_modifier)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .fold(Modifiers::empty(), |all, modifier| all | modifier);
+        Ok(KeyCombo {
+            modifiers,
+            keysym: parse_keysym(key)?,
+        })
+    }
+}
+
+/// Parses a whitespace separated chord of key combos, like "Ctrl+K Ctrl+S".
+pub fn parse_sequence(s: &str) -> Result<Vec<KeyCombo>, ParseError> {
+    let sequence = s
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if sequence.is_empty() {
+        return Err(ParseError::Empty);
+    }
+    Ok(sequence)
+}
+
+/// Keysyms of keys that only change modifiers, from Shift_L to Hyper_R
+/// and the ISO level and group shifts.
+fn is_modifier_key(keysym: xkb::Keysym) -> bool {
+    (0xffe1..=0xffee).contains(&keysym.0) || (0xfe01..=0xfe13).contains(&keysym.0)
+}
+
+/// Why key bindings could not be loaded. Lines count from 1.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// A line is not of the form `<keys> = <action>`.
+    Syntax {
+        /// The line number.
+        line: usize,
+    },
+    /// The keys of a line could not be parsed.
+    Binding {
+        /// The line number.
+        line: usize,
+        /// What is wrong with the keys.
+        error: ParseError,
+    },
+    /// The action of a line is not known.
+    Action {
+        /// The line number.
+        line: usize,
+        /// The action as written.
+        action: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "Failed to read key bindings: {}", error),
+            ConfigError::Syntax { line } => {
+                write!(f, "Line {}: expected `<keys> = <action>`", line)
+            }
+            ConfigError::Binding { line, error } => write!(f, "Line {}: {}", line, error),
+            ConfigError::Action { line, action } => {
+                write!(f, "Line {}: unknown action {:?}", line, action)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+/// The result of feeding a key event to `Keybindings`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyMatch<A> {
+    /// A binding was completed.
+    Action(A),
+    /// The key started or continued a chord, and should not be used otherwise.
+    Pending,
+    /// No binding uses the key.
+    Unbound,
+}
+
+struct Binding<A> {
+    sequence: Vec<KeyCombo>,
+    /// The window the binding is limited to, or `None` for all windows.
+    surface: Option<WlSurface>,
+    action: A,
+}
+
+/// Maps key combos and chords to actions, globally or per window.
+pub struct Keybindings<A> {
+    bindings: Vec<Binding<A>>,
+    pending: Vec<KeyCombo>,
+}
+
+impl<A: Clone> Default for Keybindings<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Clone> Keybindings<A> {
+    /// No bindings yet.
+    pub fn new() -> Self {
+        Self {
+            bindings: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Binds `keys`, like "Ctrl+Shift+T" or "Ctrl+K Ctrl+S", in all windows.
+    pub fn bind(&mut self, keys: &str, action: A) -> Result<(), ParseError> {
+        self.insert(keys, None, action)
+    }
+
+    /// Binds `keys` in the window of `surface` only. It takes precedence
+    /// over a global binding of the same keys.
+    pub fn bind_for(&mut self, surface: &WlSurface, keys: &str, action: A) -> Result<(), ParseError> {
+        self.insert(keys, Some(surface.clone()), action)
+    }
+
+    fn insert(&mut self, keys: &str, surface: Option<WlSurface>, action: A) -> Result<(), ParseError> {
+        let sequence = parse_sequence(keys)?;
+        self.bindings
+            .retain(|binding| binding.sequence != sequence || binding.surface != surface);
+        self.bindings.push(Binding {
+            sequence,
+            surface,
+            action,
+        });
+        Ok(())
+    }
+
+    /// Removes all bindings limited to the window of `surface`.
+    pub fn unbind_all_for(&mut self, surface: &WlSurface) {
+        self.bindings
+            .retain(|binding| binding.surface.as_ref() != Some(surface));
+    }
+
+    /// Adds global bindings from lines of `<keys> = <action>`. Blank lines
+    /// and lines starting with `#` are skipped.
+    pub fn load_str(&mut self, config: &str) -> Result<(), ConfigError>
+    where
+        A: FromStr,
+    {
+        for (index, line) in config.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keys, action) = match line.rfind('=') {
+                Some(index) => (&line[..index], line[index + 1..].trim()),
+                None => return Err(ConfigError::Syntax { line: line_number }),
+            };
+            let action = action.parse().map_err(|_| ConfigError::Action {
+                line: line_number,
+                action: action.to_owned(),
+            })?;
+            self.bind(keys, action).map_err(|error| ConfigError::Binding {
+                line: line_number,
+                error,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Adds global bindings from a file, see `load_str`.
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ConfigError>
+    where
+        A: FromStr,
+    {
+        self.load_str(&fs::read_to_string(path)?)
+    }
+
+    /// Matches a key event against the bindings of its window and the
+    /// global ones. Only presses of non-modifier keys count.
+    pub fn handle(&mut self, event: &KeyEvent) -> KeyMatch<A> {
+        let keysym = match event.base_keysym {
+            Some(keysym) if event.pressed && !is_modifier_key(keysym) => keysym,
+            _ => return KeyMatch::Unbound,
+        };
+        let combo = KeyCombo {
+            modifiers: event.modifiers,
+            keysym,
+        };
+        // Shift went into typing the keysym, so it is no longer a modifier.
+        let typed = event
+            .keysym
+            .filter(|&typed| typed != keysym)
+            .map(|typed| KeyCombo {
+                modifiers: event.modifiers - Modifiers::SHIFT,
+                keysym: typed,
+            });
+        let combos = std::iter::once(combo).chain(typed);
+        let surface = event.surface.as_ref();
+
+        let pending = std::mem::take(&mut self.pending);
+        for combo in combos.clone() {
+            let mut sequence = pending.clone();
+            sequence.push(combo);
+            match self.lookup(&sequence, surface) {
+                KeyMatch::Unbound => (),
+                result => return result,
+            }
+        }
+        // A key breaking off a chord may still start another one.
+        if !pending.is_empty() {
+            for combo in combos {
+                match self.lookup(&[combo], surface) {
+                    KeyMatch::Unbound => (),
+                    result => return result,
+                }
+            }
+        }
+        KeyMatch::Unbound
+    }
+
+    fn lookup(&mut self, sequence: &[KeyCombo], surface: Option<&WlSurface>) -> KeyMatch<A> {
+        let applies = |binding: &&Binding<A>| match binding.surface.as_ref() {
+            Some(binding_surface) => Some(binding_surface) == surface,
+            None => true,
+        };
+        let complete = self
+            .bindings
+            .iter()
+            .filter(applies)
+            .filter(|binding| binding.sequence == sequence)
+            .max_by_key(|binding| binding.surface.is_some());
+        if let Some(binding) = complete {
+            return KeyMatch::Action(binding.action.clone());
+        }
+        let is_prefix = self
+            .bindings
+            .iter()
+            .filter(applies)
+            .any(|binding| binding.sequence.starts_with(sequence));
+        if is_prefix {
+            self.pending = sequence.to_vec();
+            KeyMatch::Pending
+        } else {
+            KeyMatch::Unbound
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keysym(name: &str) -> xkb::Keysym {
+        name.parse().unwrap()
+    }
+
+    fn press(modifiers: Modifiers, name: &str) -> KeyEvent {
+        KeyEvent {
+            surface: None,
+            time: 0,
+            keycode: 0,
+            pressed: true,
+            keysym: Some(keysym(name)),
+            base_keysym: Some(keysym(name)),
+            utf8: None,
+            modifiers,
+        }
+    }
+
+    #[test]
+    fn parse_combo_uses_level_independent_keysym() {
+        let combo: KeyCombo = "Ctrl+Shift+T".parse().unwrap();
+        assert_eq!(combo.modifiers, Modifiers::CTRL | Modifiers::SHIFT);
+        assert_eq!(combo.keysym, keysym("t"));
+    }
+
+    #[test]
+    fn parse_plus_key() {
+        let combo: KeyCombo = "Ctrl++".parse().unwrap();
+        assert_eq!(combo.modifiers, Modifiers::CTRL);
+        assert_eq!(combo.keysym, keysym("plus"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_sequence("  "), Err(ParseError::Empty));
+        assert_eq!(
+            parse_sequence("Ctrl+"),
+            Err(ParseError::MissingKey("Ctrl+".to_owned()))
+        );
+        assert_eq!(
+            parse_sequence("Hyper+T"),
+            Err(ParseError::UnknownModifier("Hyper".to_owned()))
+        );
+        assert_eq!(
+            parse_sequence("Ctrl+NoSuchKey"),
+            Err(ParseError::UnknownKey("NoSuchKey".to_owned()))
+        );
+    }
+
+    #[test]
+    fn single_combo_matches() {
+        let mut bindings = Keybindings::new();
+        bindings.bind("Ctrl+Shift+T", "new-tab").unwrap();
+        let event = press(Modifiers::CTRL | Modifiers::SHIFT, "t");
+        assert_eq!(bindings.handle(&event), KeyMatch::Action("new-tab"));
+        assert_eq!(bindings.handle(&press(Modifiers::CTRL, "t")), KeyMatch::Unbound);
+    }
+
+    #[test]
+    fn shifted_key_matches_on_the_keysym_typed() {
+        let mut bindings = Keybindings::new();
+        bindings.bind("Ctrl++", "zoom-in").unwrap();
+        bindings.bind("Ctrl+equal", "reset-zoom").unwrap();
+        let mut event = press(Modifiers::CTRL | Modifiers::SHIFT, "plus");
+        event.base_keysym = Some(keysym("equal"));
+        assert_eq!(bindings.handle(&event), KeyMatch::Action("zoom-in"));
+        assert_eq!(
+            bindings.handle(&press(Modifiers::CTRL, "equal")),
+            KeyMatch::Action("reset-zoom")
+        );
+    }
+
+    #[test]
+    fn chord_matches_in_sequence() {
+        let mut bindings = Keybindings::new();
+        bindings.bind("Ctrl+K Ctrl+S", "save-all").unwrap();
+        assert_eq!(bindings.handle(&press(Modifiers::CTRL, "k")), KeyMatch::Pending);
+        assert_eq!(bindings.handle(&press(Modifiers::empty(), "Control_L")), KeyMatch::Unbound);
+        assert_eq!(
+            bindings.handle(&press(Modifiers::CTRL, "s")),
+            KeyMatch::Action("save-all")
+        );
+        assert_eq!(bindings.handle(&press(Modifiers::CTRL, "s")), KeyMatch::Unbound);
+    }
+
+    #[test]
+    fn broken_chord_can_start_another() {
+        let mut bindings = Keybindings::new();
+        bindings.bind("Ctrl+K Ctrl+S", "save-all").unwrap();
+        bindings.bind("Ctrl+W", "close").unwrap();
+        assert_eq!(bindings.handle(&press(Modifiers::CTRL, "k")), KeyMatch::Pending);
+        assert_eq!(bindings.handle(&press(Modifiers::CTRL, "w")), KeyMatch::Action("close"));
+    }
+
+    #[test]
+    fn load_config() {
+        let mut bindings = Keybindings::<String>::new();
+        bindings
+            .load_str("# comment\n\nCtrl+Q = quit\nCtrl+K Ctrl+S = save-all\n")
+            .unwrap();
+        assert_eq!(
+            bindings.handle(&press(Modifiers::CTRL, "q")),
+            KeyMatch::Action("quit".to_owned())
+        );
+        assert!(matches!(
+            bindings.load_str("Ctrl+Q quit"),
+            Err(ConfigError::Syntax { line: 1 })
+        ));
+    }
+}
@@ -0,0 +1,76 @@
+//! A small Wayland client toolkit: windows drawn in software into shared
+//! memory buffers, with input, outputs, the clipboard and drag and drop.
+//!
+//! Start with a [`Connection`], open a [`Window`] with a [`Renderer`] that
+//! draws its contents, and dispatch events until the window is closed.
+//! Windows only redraw when asked to, unless they are continuous like this
+//! animated one:
+//!
+//! ```no_run
+//! use wayland_book_rust::{Checkerboard, Connection, Window};
+//!
+//! # fn main() -> Result<(), wayland_book_rust::Error> {
+//! let mut connection = Connection::connect()?;
+//! let window = Window::new(&connection, "Example", Checkerboard::new());
+//! window.set_continuous(true);
+//! while !window.is_closed() {
+//!     connection.dispatch()?;
+//!     if let Some(error) = window.take_error() {
+//!         return Err(error);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Renderers draw on a [`Canvas`], filling and stroking [`Path`]s with
+//! antialiasing, drawing text in the fonts of the [`text`] module, drawing
+//! decoded [`Image`]s, or setting its pixels directly.
+//!
+//! Input comes from the seats followed with [`seat::watch`], monitors from
+//! [`output::watch`].
+//!
+//! [`Window::screenshot`] saves what a window shows, and
+//! [`Window::record`] records it with a [`capture::Recorder`].
+//!
+//! Set `WAYLAND_TRACE` and call [`trace::init_from_env`] to log protocol
+//! events, see the [`trace`] module.
+
+#![warn(missing_docs)]
+#![allow(clippy::single_match)]
+
+mod buffer;
+mod canvas;
+pub mod capture;
+mod connection;
+mod damage;
+pub mod data_device;
+mod error;
+pub mod event_loop;
+mod globals;
+mod image;
+pub mod keybindings;
+pub mod output;
+mod pixel_format;
+pub mod primary_selection;
+mod renderer;
+pub mod seat;
+mod shared_memory;
+#[cfg(test)]
+mod snapshot;
+#[cfg(test)]
+mod test_compositor;
+pub mod text;
+pub mod trace;
+mod window;
+
+pub use buffer::Buffer;
+pub use canvas::{premultiply, Canvas, ColorSpace, Gradient, Paint, Path, Pattern, Rect, Spread, Transform};
+pub use connection::Connection;
+pub use damage::Damage;
+pub use error::Error;
+pub use globals::{GlobalChange, GlobalInfo, Globals};
+pub use image::{Filter, Image};
+pub use pixel_format::PixelFormat;
+pub use renderer::{Checkerboard, Frame, Renderer};
+pub use window::Window;
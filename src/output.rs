@@ -0,0 +1,229 @@
+//! The outputs of the compositor, usually monitors, and their properties.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wayland_client::{
+    protocol::wl_output::{self, Event, Subpixel, Transform, WlOutput},
+    Main,
+};
+
+use crate::globals::{GlobalChange, Globals};
+use crate::trace;
+
+/// A video mode a monitor supports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputMode {
+    /// Width in hardware pixels.
+    pub width: i32,
+    /// Height in hardware pixels.
+    pub height: i32,
+    /// In mHz.
+    pub refresh: i32,
+    /// Whether the monitor is in this mode.
+    pub current: bool,
+    /// Whether this is the mode the monitor prefers.
+    pub preferred: bool,
+}
+
+/// What the compositor told us about a monitor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputInfo {
+    /// The name of the `wl_output` global.
+    pub name: u32,
+    /// The manufacturer.
+    pub make: String,
+    /// The model name.
+    pub model: String,
+    /// Position within the global compositor space.
+    pub x: i32,
+    /// Position within the global compositor space.
+    pub y: i32,
+    /// In millimeters.
+    pub physical_width: i32,
+    /// In millimeters.
+    pub physical_height: i32,
+    /// How the color components of a pixel are laid out.
+    pub subpixel: Subpixel,
+    /// How the contents are rotated or flipped on the monitor.
+    pub transform: Transform,
+    /// How many buffer pixels make one surface unit on this monitor.
+    pub scale: i32,
+    /// The modes announced so far.
+    pub modes: Vec<OutputMode>,
+}
+
+impl OutputInfo {
+    fn new(name: u32) -> Self {
+        Self {
+            name,
+            make: String::new(),
+            model: String::new(),
+            x: 0,
+            y: 0,
+            physical_width: 0,
+            physical_height: 0,
+            subpixel: Subpixel::Unknown,
+            transform: Transform::Normal,
+            scale: 1,
+            modes: Vec::new(),
+        }
+    }
+
+    /// The mode the monitor is in, if announced.
+    pub fn current_mode(&self) -> Option<&OutputMode> {
+        self.modes.iter().find(|mode| mode.current)
+    }
+
+    fn update(&mut self, event: Event) {
+        match event {
+            Event::Geometry {
+                x,
+                y,
+                physical_width,
+                physical_height,
+                subpixel,
+                make,
+                model,
+                transform,
+            } => {
+                self.x = x;
+                self.y = y;
+                self.physical_width = physical_width;
+                self.physical_height = physical_height;
+                self.subpixel = subpixel;
+                self.make = make;
+                self.model = model;
+                self.transform = transform;
+            }
+            Event::Mode {
+                flags,
+                width,
+                height,
+                refresh,
+            } => {
+                let current = flags.contains(wl_output::Mode::Current);
+                if current {
+                    for mode in self.modes.iter_mut() {
+                        mode.current = false;
+                    }
+                }
+                let mode = OutputMode {
+                    width,
+                    height,
+                    refresh,
+                    current,
+                    preferred: flags.contains(wl_output::Mode::Preferred),
+                };
+                let same_size = |other: &OutputMode| {
+                    (other.width, other.height, other.refresh) == (width, height, refresh)
+                };
+                match self.modes.iter_mut().find(|other| same_size(other)) {
+                    Some(other) => *other = mode,
+                    None => self.modes.push(mode),
+                }
+            }
+            Event::Scale { factor } => self.scale = factor,
+            _ => (),
+        }
+    }
+}
+
+/// A change to the monitors, as passed to the callback of `watch`.
+pub enum OutputEvent {
+    /// A monitor was plugged in, or was there already.
+    Added(OutputInfo),
+    /// Properties of a monitor changed, like its mode or scale.
+    Changed(OutputInfo),
+    /// A monitor was unplugged. Carries the last known info.
+    Removed(OutputInfo),
+}
+
+struct Output {
+    output: Main<WlOutput>,
+    info: Rc<RefCell<OutputInfo>>,
+}
+
+/// Follows the outputs of the compositor as they are plugged in, changed
+/// and unplugged, and calls `callback` once each change is complete.
+pub fn watch<F>(globals: &Globals, callback: F)
+where
+    F: FnMut(&OutputEvent) + 'static,
+{
+    let callback = Rc::new(RefCell::new(callback));
+    let mut outputs = Vec::<Output>::new();
+    globals.on_global::<WlOutput, _>(1..=3, move |change| match change {
+        GlobalChange::Added { name, global } => {
+            let info = Rc::new(RefCell::new(OutputInfo::new(name)));
+            // Before version 2 there are no `done` events to wait for.
+            let has_done = global.as_ref().version() >= wl_output::EVT_DONE_SINCE;
+            let mut announced = false;
+            global.quick_assign({
+                let info = info.clone();
+                let callback = callback.clone();
+                move |output, event, _| {
+                    trace::event(&output, &event);
+                    let done = !has_done || matches!(event, Event::Done);
+                    info.borrow_mut().update(event);
+                    if done {
+                        let info = info.borrow().clone();
+                        let event = if announced {
+                            OutputEvent::Changed(info)
+                        } else {
+                            OutputEvent::Added(info)
+                        };
+                        announced = true;
+                        (callback.borrow_mut())(&event);
+                    }
+                }
+            });
+            outputs.push(Output {
+                output: global,
+                info,
+            });
+        }
+        GlobalChange::Removed { name } => {
+            if let Some(index) = outputs.iter().position(|output| output.info.borrow().name == name) {
+                let output = outputs.swap_remove(index);
+                if output.output.as_ref().version() >= wl_output::REQ_RELEASE_SINCE {
+                    output.output.release();
+                }
+                let info = output.info.borrow().clone();
+                (callback.borrow_mut())(&OutputEvent::Removed(info));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(flags: wl_output::Mode, width: i32) -> Event {
+        Event::Mode {
+            flags,
+            width,
+            height: 1080,
+            refresh: 60000,
+        }
+    }
+
+    #[test]
+    fn only_the_latest_current_mode_is_current() {
+        let mut info = OutputInfo::new(1);
+        info.update(mode(wl_output::Mode::Current | wl_output::Mode::Preferred, 1920));
+        info.update(mode(wl_output::Mode::Current, 1280));
+        assert_eq!(info.modes.len(), 2);
+        assert_eq!(info.current_mode().map(|mode| mode.width), Some(1280));
+        assert!(info.modes[0].preferred);
+    }
+
+    #[test]
+    fn repeated_modes_are_updated_in_place() {
+        let mut info = OutputInfo::new(1);
+        info.update(mode(wl_output::Mode::empty(), 1920));
+        info.update(mode(wl_output::Mode::Current, 1920));
+        assert_eq!(info.modes.len(), 1);
+        assert!(info.modes[0].current);
+    }
+}
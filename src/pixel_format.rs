@@ -0,0 +1,251 @@
+use wayland_client::protocol::wl_shm::Format;
+
+/// The layouts buffers can be allocated in.
+///
+/// Drawing always happens in premultiplied ARGB8888, which is converted
+/// to the format of the buffer when a frame is done. Formats without alpha
+/// drop it, and fewer bits per channel round the colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 32 bits, 8 per channel, with alpha. Always supported.
+    Argb8888,
+    /// 32 bits, 8 per color channel, opaque. Always supported.
+    Xrgb8888,
+    /// 32 bits with red and blue swapped, with alpha.
+    Abgr8888,
+    /// 32 bits with red and blue swapped, opaque.
+    Xbgr8888,
+    /// 24 bits, 8 per channel, opaque.
+    Rgb888,
+    /// 24 bits with red and blue swapped, opaque.
+    Bgr888,
+    /// 16 bits: 5 for red, 6 for green and 5 for blue, opaque. Half the
+    /// memory and bandwidth of 32 bit formats.
+    Rgb565,
+    /// 32 bits, 10 per color channel and 2 for alpha.
+    Argb2101010,
+    /// 32 bits, 10 per color channel, opaque.
+    Xrgb2101010,
+    /// 32 bits, 10 per color channel with red and blue swapped, and 2
+    /// for alpha.
+    Abgr2101010,
+    /// 32 bits, 10 per color channel with red and blue swapped, opaque.
+    Xbgr2101010,
+}
+
+impl PixelFormat {
+    /// The format as `wl_shm` knows it.
+    pub fn to_shm(self) -> Format {
+        match self {
+            PixelFormat::Argb8888 => Format::Argb8888,
+            PixelFormat::Xrgb8888 => Format::Xrgb8888,
+            PixelFormat::Abgr8888 => Format::Abgr8888,
+            PixelFormat::Xbgr8888 => Format::Xbgr8888,
+            PixelFormat::Rgb888 => Format::Rgb888,
+            PixelFormat::Bgr888 => Format::Bgr888,
+            PixelFormat::Rgb565 => Format::Rgb565,
+            PixelFormat::Argb2101010 => Format::Argb2101010,
+            PixelFormat::Xrgb2101010 => Format::Xrgb2101010,
+            PixelFormat::Abgr2101010 => Format::Abgr2101010,
+            PixelFormat::Xbgr2101010 => Format::Xbgr2101010,
+        }
+    }
+
+    /// The `wl_shm` format, if it is one we can draw in.
+    pub fn from_shm(format: Format) -> Option<Self> {
+        let format = match format {
+            Format::Argb8888 => PixelFormat::Argb8888,
+            Format::Xrgb8888 => PixelFormat::Xrgb8888,
+            Format::Abgr8888 => PixelFormat::Abgr8888,
+            Format::Xbgr8888 => PixelFormat::Xbgr8888,
+            Format::Rgb888 => PixelFormat::Rgb888,
+            Format::Bgr888 => PixelFormat::Bgr888,
+            Format::Rgb565 => PixelFormat::Rgb565,
+            Format::Argb2101010 => PixelFormat::Argb2101010,
+            Format::Xrgb2101010 => PixelFormat::Xrgb2101010,
+            Format::Abgr2101010 => PixelFormat::Abgr2101010,
+            Format::Xbgr2101010 => PixelFormat::Xbgr2101010,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// The size of a pixel.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb565 => 2,
+            PixelFormat::Rgb888 | PixelFormat::Bgr888 => 3,
+            _ => 4,
+        }
+    }
+
+    /// Whether pixels can be translucent.
+    pub fn has_alpha(self) -> bool {
+        matches!(
+            self,
+            PixelFormat::Argb8888
+                | PixelFormat::Abgr8888
+                | PixelFormat::Argb2101010
+                | PixelFormat::Abgr2101010
+        )
+    }
+
+    /// Whether pixels are laid out like the `u32` a canvas draws in, so
+    /// that it can draw into the buffer directly.
+    pub(crate) fn is_native(self) -> bool {
+        // wl_shm formats are little endian.
+        cfg!(target_endian = "little")
+            && matches!(self, PixelFormat::Argb8888 | PixelFormat::Xrgb8888)
+    }
+
+    /// Writes the premultiplied ARGB8888 `color` into the bytes of a
+    /// pixel, `bytes_per_pixel` long.
+    pub fn encode(self, color: u32, pixel: &mut [u8]) {
+        let [b, g, r, a] = color.to_le_bytes();
+        let value = match self {
+            PixelFormat::Argb8888 | PixelFormat::Xrgb8888 => color,
+            PixelFormat::Abgr8888 | PixelFormat::Xbgr8888 => {
+                u32::from_le_bytes([r, g, b, a])
+            }
+            PixelFormat::Rgb888 => u32::from_le_bytes([b, g, r, 0]),
+            PixelFormat::Bgr888 => u32::from_le_bytes([r, g, b, 0]),
+            PixelFormat::Rgb565 => scale(r, 5) << 11 | scale(g, 6) << 5 | scale(b, 5),
+            PixelFormat::Argb2101010 | PixelFormat::Xrgb2101010 => {
+                scale(a, 2) << 30 | scale(r, 10) << 20 | scale(g, 10) << 10 | scale(b, 10)
+            }
+            PixelFormat::Abgr2101010 | PixelFormat::Xbgr2101010 => {
+                scale(a, 2) << 30 | scale(b, 10) << 20 | scale(g, 10) << 10 | scale(r, 10)
+            }
+        };
+        let bytes = value.to_le_bytes();
+        pixel.copy_from_slice(&bytes[..self.bytes_per_pixel()]);
+    }
+
+    /// Reads the bytes of a pixel back into premultiplied ARGB8888.
+    /// Formats without alpha read as opaque.
+    pub fn decode(self, pixel: &[u8]) -> u32 {
+        let mut bytes = [0; 4];
+        bytes[..pixel.len()].copy_from_slice(pixel);
+        let value = u32::from_le_bytes(bytes);
+        let [b0, b1, b2, b3] = bytes;
+        let [b, g, r, a] = match self {
+            PixelFormat::Argb8888 => [b0, b1, b2, b3],
+            PixelFormat::Xrgb8888 => [b0, b1, b2, 0xFF],
+            PixelFormat::Abgr8888 => [b2, b1, b0, b3],
+            PixelFormat::Xbgr8888 => [b2, b1, b0, 0xFF],
+            PixelFormat::Rgb888 => [b0, b1, b2, 0xFF],
+            PixelFormat::Bgr888 => [b2, b1, b0, 0xFF],
+            PixelFormat::Rgb565 => [
+                unscale(value, 0, 5),
+                unscale(value, 5, 6),
+                unscale(value, 11, 5),
+                0xFF,
+            ],
+            PixelFormat::Argb2101010 | PixelFormat::Xrgb2101010 => [
+                unscale(value, 0, 10),
+                unscale(value, 10, 10),
+                unscale(value, 20, 10),
+                unscale(value, 30, 2),
+            ],
+            PixelFormat::Abgr2101010 | PixelFormat::Xbgr2101010 => [
+                unscale(value, 20, 10),
+                unscale(value, 10, 10),
+                unscale(value, 0, 10),
+                unscale(value, 30, 2),
+            ],
+        };
+        let a = if self.has_alpha() { a } else { 0xFF };
+        u32::from_le_bytes([b, g, r, a])
+    }
+}
+
+/// An 8 bit channel rounded to `bits`.
+fn scale(channel: u8, bits: u32) -> u32 {
+    let max = (1 << bits) - 1;
+    (u32::from(channel) * max + 127) / 255
+}
+
+/// The channel of `bits` at `shift` in `value`, rounded to 8 bits.
+fn unscale(value: u32, shift: u32, bits: u32) -> u8 {
+    let max = (1 << bits) - 1;
+    (((value >> shift & max) * 255 + max / 2) / max) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [PixelFormat; 11] = [
+        PixelFormat::Argb8888,
+        PixelFormat::Xrgb8888,
+        PixelFormat::Abgr8888,
+        PixelFormat::Xbgr8888,
+        PixelFormat::Rgb888,
+        PixelFormat::Bgr888,
+        PixelFormat::Rgb565,
+        PixelFormat::Argb2101010,
+        PixelFormat::Xrgb2101010,
+        PixelFormat::Abgr2101010,
+        PixelFormat::Xbgr2101010,
+    ];
+
+    fn encode(format: PixelFormat, color: u32) -> Vec<u8> {
+        let mut pixel = vec![0; format.bytes_per_pixel()];
+        format.encode(color, &mut pixel);
+        pixel
+    }
+
+    #[test]
+    fn layouts_match_the_protocol() {
+        assert_eq!(encode(PixelFormat::Argb8888, 0x8011_2233), [0x33, 0x22, 0x11, 0x80]);
+        assert_eq!(encode(PixelFormat::Abgr8888, 0x8011_2233), [0x11, 0x22, 0x33, 0x80]);
+        assert_eq!(encode(PixelFormat::Rgb888, 0xFF11_2233), [0x33, 0x22, 0x11]);
+        assert_eq!(encode(PixelFormat::Bgr888, 0xFF11_2233), [0x11, 0x22, 0x33]);
+        // Pure red, green and blue fill their own bits.
+        assert_eq!(encode(PixelFormat::Rgb565, 0xFFFF_0000), 0xF800u16.to_le_bytes());
+        assert_eq!(encode(PixelFormat::Rgb565, 0xFF00_FF00), 0x07E0u16.to_le_bytes());
+        assert_eq!(encode(PixelFormat::Rgb565, 0xFF00_00FF), 0x001Fu16.to_le_bytes());
+        assert_eq!(
+            encode(PixelFormat::Xrgb2101010, 0xFFFF_0000),
+            0xFFF0_0000u32.to_le_bytes()
+        );
+        assert_eq!(
+            encode(PixelFormat::Abgr2101010, 0xFFFF_0000),
+            0xC000_03FFu32.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn colors_survive_a_roundtrip_within_the_precision() {
+        for &format in &ALL {
+            // How far 5 or 6 bit colors and 2 bit alpha may be off.
+            let (color_error, alpha_error) = match format {
+                PixelFormat::Rgb565 => (4, 0),
+                PixelFormat::Argb2101010 | PixelFormat::Abgr2101010 => (0, 43),
+                _ => (0, 0),
+            };
+            for &color in &[0xFF00_0000u32, 0xFFFF_FFFF, 0xFF12_80ED, 0x8040_2010] {
+                let decoded = format.decode(&encode(format, color));
+                let [b, g, r, a] = color.to_le_bytes();
+                let a = if format.has_alpha() { a } else { 0xFF };
+                let expected = [
+                    (b, color_error),
+                    (g, color_error),
+                    (r, color_error),
+                    (a, alpha_error),
+                ];
+                for ((want, error), got) in expected.iter().zip(decoded.to_le_bytes()) {
+                    assert!(want.abs_diff(got) <= *error, "{:?} {:08x}", format, decoded);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shm_formats_map_both_ways() {
+        for &format in &ALL {
+            assert_eq!(PixelFormat::from_shm(format.to_shm()), Some(format));
+        }
+        assert_eq!(PixelFormat::from_shm(Format::Yuyv), None);
+    }
+}
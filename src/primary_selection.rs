@@ -0,0 +1,199 @@
+//! The primary selection: text selected is pasted with the middle button.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::rc::Rc;
+
+use wayland_client::{protocol::wl_seat::WlSeat, Main};
+use wayland_protocols::unstable::primary_selection::v1::client::{
+    zwp_primary_selection_device_manager_v1::ZwpPrimarySelectionDeviceManagerV1 as DeviceManager,
+    zwp_primary_selection_device_v1::{self as device, ZwpPrimarySelectionDeviceV1 as Device},
+    zwp_primary_selection_offer_v1::{self as offer, ZwpPrimarySelectionOfferV1},
+    zwp_primary_selection_source_v1::{self as source, ZwpPrimarySelectionSourceV1},
+};
+
+use crate::data_device::offer::{self as data_offer, Announcement, Offer, OfferProxy};
+use crate::data_device::selection::Selection;
+use crate::data_device::source::{self as data_source, SourceEvent, SourceMessage, SourceProxy};
+use crate::event_loop::LoopHandle;
+use crate::seat::InputSerials;
+use crate::trace;
+
+impl OfferProxy for ZwpPrimarySelectionOfferV1 {
+    fn announcement(event: offer::Event) -> Option<Announcement> {
+        match event {
+            offer::Event::Offer { mime_type } => Some(Announcement::MimeType(mime_type)),
+            _ => None,
+        }
+    }
+
+    fn receive(&self, mime_type: String, fd: RawFd) {
+        ZwpPrimarySelectionOfferV1::receive(self, mime_type, fd);
+    }
+
+    fn destroy(&self) {
+        ZwpPrimarySelectionOfferV1::destroy(self);
+    }
+}
+
+impl SourceProxy for ZwpPrimarySelectionSourceV1 {
+    fn message(event: source::Event) -> Option<SourceMessage> {
+        match event {
+            source::Event::Send { mime_type, fd } => Some(SourceMessage::Send { mime_type, fd }),
+            source::Event::Cancelled => Some(SourceMessage::Event(SourceEvent::Cancelled)),
+            _ => None,
+        }
+    }
+
+    fn offer(&self, mime_type: String) {
+        ZwpPrimarySelectionSourceV1::offer(self, mime_type);
+    }
+
+    fn destroy(&self) {
+        ZwpPrimarySelectionSourceV1::destroy(self);
+    }
+}
+
+/// The primary selection offered by another client.
+///
+/// The offer is destroyed when dropped.
+pub struct PrimaryOffer {
+    offer: Offer<ZwpPrimarySelectionOfferV1>,
+}
+
+impl PrimaryOffer {
+    /// The MIME types the selection is offered in.
+    pub fn mime_types(&self) -> Vec<String> {
+        self.offer.mime_types()
+    }
+
+    /// Whether the selection is offered as `mime_type`.
+    pub fn has_mime_type(&self, mime_type: &str) -> bool {
+        self.offer.has_mime_type(mime_type)
+    }
+
+    /// Asks the offering client for the data as `mime_type`, and calls
+    /// `callback` with all of it once the client closes the pipe.
+    pub fn receive<F>(&self, mime_type: &str, handle: &LoopHandle, callback: F) -> io::Result<()>
+    where
+        F: FnOnce(io::Result<Vec<u8>>) + 'static,
+    {
+        self.offer.receive(mime_type, handle, callback)
+    }
+}
+
+/// The primary selection, pasted with the middle mouse button.
+///
+/// Works like the clipboard of `DataDevice`, but is set whenever the
+/// user selects text rather than on an explicit copy.
+#[derive(Clone)]
+pub struct PrimarySelection {
+    manager: Main<DeviceManager>,
+    device: Main<Device>,
+    serials: InputSerials,
+    handle: LoopHandle,
+    selection: Rc<RefCell<Selection<PrimaryOffer>>>,
+}
+
+impl PrimarySelection {
+    /// Offers the primary selection as `mime_types`. `send` writes the data
+    /// as the requested type.
+    ///
+    /// Uses the serial of the pointer button held while selecting, or else
+    /// of the latest key press. Returns `false` if there is neither.
+    pub fn set_selection<F>(&self, mime_types: &[&str], send: F) -> bool
+    where
+        F: FnMut(&str, File) + 'static,
+    {
+        let serial = match self.serial() {
+            Some(serial) => serial,
+            None => return false,
+        };
+        let source = data_source::create(self.manager.create_source(), mime_types, send);
+        self.device.set_selection(Some(&source), serial);
+        true
+    }
+
+    /// Clears our primary selection. Returns `false` without a serial
+    /// of recent input to do it with.
+    pub fn clear_selection(&self) -> bool {
+        match self.serial() {
+            Some(serial) => {
+                self.device.set_selection(None, serial);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn serial(&self) -> Option<u32> {
+        self.serials.button.get().or_else(|| self.serials.keyboard.get())
+    }
+
+    /// The MIME types of the current primary selection, empty if there is none.
+    pub fn selection_mime_types(&self) -> Vec<String> {
+        self.selection
+            .borrow()
+            .offer()
+            .map(PrimaryOffer::mime_types)
+            .unwrap_or_default()
+    }
+
+    /// Receives the current primary selection as `mime_type` on the event loop.
+    pub fn receive_selection<F>(&self, mime_type: &str, callback: F) -> io::Result<()>
+    where
+        F: FnOnce(io::Result<Vec<u8>>) + 'static,
+    {
+        match self.selection.borrow().offer() {
+            Some(offer) => offer.receive(mime_type, &self.handle, callback),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no primary selection")),
+        }
+    }
+
+    /// Calls `callback` every time the primary selection changes, with
+    /// `None` when it was cleared.
+    pub fn on_selection<F>(&self, callback: F)
+    where
+        F: FnMut(Option<&PrimaryOffer>) + 'static,
+    {
+        self.selection.borrow_mut().on_change(callback);
+    }
+}
+
+/// Follows the primary selection of `seat`.
+pub fn setup(
+    manager: &Main<DeviceManager>,
+    seat: &Main<WlSeat>,
+    serials: &InputSerials,
+    handle: &LoopHandle,
+) -> PrimarySelection {
+    let device = manager.get_device(seat);
+    let selection = Rc::new(RefCell::new(Selection::default()));
+
+    device.quick_assign({
+        let selection = selection.clone();
+        move |device, event, _| {
+            trace::event(&device, &event);
+            match event {
+                device::Event::DataOffer { offer } => data_offer::handle(&offer),
+                device::Event::Selection { id } => {
+                    let offer = id.map(|offer| PrimaryOffer {
+                        offer: Offer::new(offer),
+                    });
+                    Selection::set(&selection, offer);
+                }
+                _ => (),
+            }
+        }
+    });
+
+    PrimarySelection {
+        manager: manager.clone(),
+        device,
+        serials: serials.clone(),
+        handle: handle.clone(),
+        selection,
+    }
+}
@@ -0,0 +1,244 @@
+use crate::canvas::{Canvas, Paint, Rect, Shader};
+use crate::damage::Damage;
+
+/// What a frame is rendered for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The time of the frame callback in milliseconds, or `None` for the
+    /// first frame, which is drawn before the compositor asks for one.
+    pub time: Option<u32>,
+    /// How many buffer pixels make one surface unit. The canvas is already
+    /// sized in buffer pixels.
+    pub scale: i32,
+    /// The parts of the canvas that must be rendered, the rest holds
+    /// the previous frame already.
+    pub damage: Vec<Rect>,
+}
+
+/// Renders the contents of a window, one frame at a time.
+///
+/// Closures taking a canvas and a frame are renderers too.
+pub trait Renderer {
+    /// Adds the parts of the canvas that change in the frame at `time` to
+    /// `damage`, before it is rendered. Nothing is rendered or sent to the
+    /// compositor without damage.
+    ///
+    /// By default everything changes in every frame.
+    fn damage(&mut self, time: Option<u32>, damage: &mut Damage) {
+        let _ = time;
+        damage.add_all();
+    }
+
+    /// Renders at least the damaged parts of `canvas`.
+    fn render(&mut self, canvas: &mut Canvas, frame: &Frame);
+}
+
+impl<F: FnMut(&mut Canvas, &Frame)> Renderer for F {
+    fn render(&mut self, canvas: &mut Canvas, frame: &Frame) {
+        self(canvas, frame)
+    }
+}
+
+/// A checkerboard scrolling sideways at 24 pixels per second.
+pub struct Checkerboard {
+    float_offset: f32,
+    last_frame: Option<u32>,
+    paints: [Paint; 2],
+}
+
+impl Default for Checkerboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer for Checkerboard {
+    fn damage(&mut self, time: Option<u32>, damage: &mut Damage) {
+        // It only moves a whole pixel at a time.
+        if self.last_frame.is_none() || Self::wrap(self.advanced(time)) != self.offset() {
+            damage.add_all();
+        }
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, frame: &Frame) {
+        self.float_offset = self.advanced(frame.time);
+        if frame.time.is_some() {
+            self.last_frame = frame.time;
+        }
+        // Squares are 8 surface units wide whatever the scale.
+        let scale = frame.scale.max(1) as usize;
+        let offset = self.offset() * scale;
+        let transform = canvas.transform();
+        let shaders = [
+            Shader::new(&self.paints[0], &transform),
+            Shader::new(&self.paints[1], &transform),
+        ];
+        for (y, row) in canvas.rows_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let square = if ((x + offset) / scale + (y + offset) / (8 * scale) * 8) % 16 < 8 {
+                    0
+                } else {
+                    1
+                };
+                *pixel = shaders[square].color(x, y);
+            }
+        }
+    }
+}
+
+impl Checkerboard {
+    const COLOR1: u32 = 0xFF666666;
+    const COLOR2: u32 = 0xFFEEEEEE;
+
+    /// A checkerboard at its starting position.
+    pub fn new() -> Checkerboard {
+        Self::with_paints(Self::COLOR1, Self::COLOR2)
+    }
+
+    /// A checkerboard of squares painted with `first` and `second` rather
+    /// than two grays. The paints stay put while the squares scroll.
+    pub fn with_paints<P: Into<Paint>, Q: Into<Paint>>(first: P, second: Q) -> Checkerboard {
+        Checkerboard {
+            float_offset: 0.0,
+            last_frame: None,
+            paints: [first.into(), second.into()],
+        }
+    }
+
+    fn offset(&self) -> usize {
+        Self::wrap(self.float_offset)
+    }
+
+    fn wrap(float_offset: f32) -> usize {
+        (float_offset as usize) % 8
+    }
+
+    /// The offset in the frame at `time`.
+    fn advanced(&self, time: Option<u32>) -> f32 {
+        match (time, self.last_frame) {
+            (Some(time), Some(last_frame)) => {
+                let elapsed = time.wrapping_sub(last_frame);
+                self.float_offset + (elapsed as f32) / 1000.0 * 24.0
+            }
+            _ => self.float_offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Gradient;
+    use crate::image::Image;
+    use crate::snapshot::{assert_snapshot, Tolerance};
+
+    /// The frame of a `width` by `height` window after frame callbacks at
+    /// each of `times`, rendered like a window does: first without a time.
+    fn render(width: usize, height: usize, times: &[u32]) -> Image {
+        let mut checkerboard = Checkerboard::new();
+        let mut pixels = vec![0; width * height];
+        let mut canvas = Canvas::new(&mut pixels, width, height);
+        let damage = vec![Rect::new(0, 0, width, height)];
+        for time in std::iter::once(None).chain(times.iter().copied().map(Some)) {
+            let frame = Frame {
+                time,
+                scale: 1,
+                damage: damage.clone(),
+            };
+            checkerboard.render(&mut canvas, &frame);
+        }
+        Image::new(width, height, pixels)
+    }
+
+    #[test]
+    fn checkerboard_first_frame() {
+        assert_snapshot("checkerboard_first_frame", &render(64, 48, &[]), Tolerance::EXACT);
+    }
+
+    #[test]
+    fn checkerboard_moves_with_time() {
+        // 250 ms at 24 pixels per second.
+        let image = render(64, 48, &[1000, 1250]);
+        assert_snapshot("checkerboard_after_250ms", &image, Tolerance::EXACT);
+    }
+
+    #[test]
+    fn checkerboard_odd_size() {
+        assert_snapshot("checkerboard_odd_size", &render(37, 23, &[]), Tolerance::EXACT);
+    }
+
+    #[test]
+    fn checkerboard_squares_keep_their_size_when_scaled() {
+        let mut pixels = vec![0; 128 * 96];
+        let frame = Frame {
+            time: None,
+            scale: 2,
+            damage: vec![Rect::new(0, 0, 128, 96)],
+        };
+        Checkerboard::new().render(&mut Canvas::new(&mut pixels, 128, 96), &frame);
+        let small = render(64, 48, &[]);
+        for (index, &pixel) in pixels.iter().enumerate() {
+            let (x, y) = (index % 128, index / 128);
+            assert_eq!(pixel, small.pixels()[y / 2 * 64 + x / 2], "at {}, {}", x, y);
+        }
+    }
+
+    #[test]
+    fn checkerboard_squares_take_any_paint() {
+        let fade = Gradient::linear(0.0, 0.0, 16.0, 0.0).stop(0.0, 0xFF00_0000).stop(1.0, 0xFF00_00FF);
+        let mut checkerboard = Checkerboard::with_paints(fade, 0x80FF_FFFF);
+        let mut pixels = vec![0; 16 * 8];
+        let frame = Frame {
+            time: None,
+            scale: 1,
+            damage: vec![Rect::new(0, 0, 16, 8)],
+        };
+        checkerboard.render(&mut Canvas::new(&mut pixels, 16, 8), &frame);
+        // The gradient runs on under the squares of the other paint.
+        assert_eq!(pixels[0], 0xFF00_0008);
+        assert_eq!(pixels[7], 0xFF00_0078);
+        assert_eq!(pixels[8], 0x8080_8080);
+    }
+
+    #[test]
+    fn checkerboard_is_damaged_when_it_moves_a_pixel() {
+        let mut checkerboard = Checkerboard::new();
+        let mut pixels = vec![0; 16 * 16];
+        let mut frame = |time| {
+            let mut damage = Damage::new(16, 16);
+            checkerboard.damage(time, &mut damage);
+            if !damage.is_empty() {
+                let frame = Frame {
+                    time,
+                    scale: 1,
+                    damage: damage.rects().to_vec(),
+                };
+                checkerboard.render(&mut Canvas::new(&mut pixels, 16, 16), &frame);
+            }
+            damage.rects().to_vec()
+        };
+        let everything = vec![Rect::new(0, 0, 16, 16)];
+        assert_eq!(frame(None), everything);
+        assert_eq!(frame(Some(1000)), everything);
+        // A pixel takes 1/24 s, which the frames add up to.
+        assert_eq!(frame(Some(1016)), vec![]);
+        assert_eq!(frame(Some(1032)), vec![]);
+        assert_eq!(frame(Some(1048)), everything);
+    }
+
+    #[test]
+    fn closures_are_renderers() {
+        let mut pixels = vec![0; 6];
+        let mut canvas = Canvas::new(&mut pixels, 3, 2);
+        let mut renderer = |canvas: &mut Canvas, frame: &Frame| {
+            canvas.clear(0xFF00_0000 | frame.scale as u32)
+        };
+        let frame = Frame {
+            time: Some(16),
+            scale: 7,
+            damage: Vec::new(),
+        };
+        renderer.render(&mut canvas, &frame);
+        assert_eq!(pixels, vec![0xFF00_0007; 6]);
+    }
+}
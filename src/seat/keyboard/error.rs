@@ -0,0 +1,42 @@
+use std::fmt;
+use std::io;
+
+/// Why a keymap could not be used.
+#[derive(Debug)]
+pub enum KeymapError {
+    /// The compositor sent a keymap format we don't know.
+    UnsupportedFormat(u32),
+    /// The compositor sent a keymap of size 0, or only a null terminator.
+    Empty,
+    /// Mapping the keymap file descriptor failed.
+    Map(io::Error),
+    /// xkbcommon could not compile the keymap.
+    Compile,
+    /// xkbcommon could not compile a keymap from the given RMLVO names.
+    Names(String),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeymapError::UnsupportedFormat(format) => {
+                write!(f, "Unsupported keymap format {}", format)
+            }
+            KeymapError::Empty => write!(f, "Keymap is empty"),
+            KeymapError::Map(error) => write!(f, "Failed to map keymap: {}", error),
+            KeymapError::Compile => write!(f, "Failed to compile keymap"),
+            KeymapError::Names(names) => {
+                write!(f, "Failed to compile keymap from names {}", names)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KeymapError::Map(error) => Some(error),
+            _ => None,
+        }
+    }
+}
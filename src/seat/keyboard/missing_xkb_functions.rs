@@ -1,31 +1,132 @@
-use xkbcommon_sys as ffi;
-#[derive(Debug)]
-pub struct KeyMapError;
+use std::ffi::CString;
+use std::fmt;
+use std::ptr;
 
-impl std::fmt::Display for KeyMapError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Error getting keymap")
-    }
-}
+use xkbcommon_sys as ffi;
 
-impl std::error::Error for KeyMapError {}
+use super::error::KeymapError;
 
 pub fn keymap_from_buffer(
     context: &xkb::Context,
     buffer: &[u8],
-) -> Result<xkb::Keymap, KeyMapError> {
+) -> Result<xkb::Keymap, KeymapError> {
+    // Treating the buffer as a slice of chars instead of a CString
+    // by stripping everything from the null terminator.
+    let length = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    if length == 0 {
+        return Err(KeymapError::Empty);
+    }
     unsafe {
         let ptr = ffi::xkb_keymap_new_from_buffer(
             context.as_ptr(),
             buffer.as_ptr().cast(),
-            buffer.len(),
+            length,
             ffi::XKB_KEYMAP_FORMAT_TEXT_v1,
             ffi::XKB_KEYMAP_COMPILE_NO_FLAGS,
         );
         if ptr.is_null() {
-            Err(KeyMapError)
+            Err(KeymapError::Compile)
+        } else {
+            Ok(xkb::Keymap::from_ptr(ptr))
+        }
+    }
+}
+
+/// Rules, model, layout, variant and options to build a keymap from.
+/// Missing names are taken from the `XKB_DEFAULT_*` environment variables,
+/// or else the system defaults.
+#[derive(Debug, Default, Clone)]
+pub struct RuleNames {
+    /// The rules file, usually `evdev`.
+    pub rules: Option<String>,
+    /// The keyboard model, like `pc105`.
+    pub model: Option<String>,
+    /// Comma separated layouts, like `us,de`.
+    pub layout: Option<String>,
+    /// Comma separated variants, one per layout, like `dvorak,`.
+    pub variant: Option<String>,
+    /// Comma separated options, like `ctrl:nocaps`.
+    pub options: Option<String>,
+}
+
+impl fmt::Display for RuleNames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = [
+            &self.rules,
+            &self.model,
+            &self.layout,
+            &self.variant,
+            &self.options,
+        ];
+        let names: Vec<&str> = names
+            .iter()
+            .map(|name| name.as_deref().unwrap_or("default"))
+            .collect();
+        write!(f, "({})", names.join(", "))
+    }
+}
+
+pub fn keymap_from_names(
+    context: &xkb::Context,
+    names: &RuleNames,
+) -> Result<xkb::Keymap, KeymapError> {
+    let invalid = || KeymapError::Names(names.to_string());
+    let to_c = |name: &Option<String>| match name {
+        Some(name) => CString::new(name.as_str()).map(Some).map_err(|_| invalid()),
+        None => Ok(None),
+    };
+    let rules = to_c(&names.rules)?;
+    let model = to_c(&names.model)?;
+    let layout = to_c(&names.layout)?;
+    let variant = to_c(&names.variant)?;
+    let options = to_c(&names.options)?;
+    let as_ptr = |name: &Option<CString>| name.as_ref().map_or(ptr::null(), |name| name.as_ptr());
+    let rule_names = ffi::xkb_rule_names {
+        rules: as_ptr(&rules),
+        model: as_ptr(&model),
+        layout: as_ptr(&layout),
+        variant: as_ptr(&variant),
+        options: as_ptr(&options),
+    };
+    unsafe {
+        let ptr = ffi::xkb_keymap_new_from_names(
+            context.as_ptr(),
+            &rule_names,
+            ffi::XKB_KEYMAP_COMPILE_NO_FLAGS,
+        );
+        if ptr.is_null() {
+            Err(invalid())
         } else {
             Ok(xkb::Keymap::from_ptr(ptr))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_keymap_is_an_error() {
+        let context = xkb::Context::default();
+        assert!(matches!(keymap_from_buffer(&context, b""), Err(KeymapError::Empty)));
+        assert!(matches!(keymap_from_buffer(&context, b"\0"), Err(KeymapError::Empty)));
+    }
+
+    #[test]
+    fn malformed_keymap_is_an_error() {
+        let context = xkb::Context::default();
+        let result = keymap_from_buffer(&context, b"xkb_keymap { garbage\0");
+        assert!(matches!(result, Err(KeymapError::Compile)));
+    }
+
+    #[test]
+    fn names_with_nul_are_an_error() {
+        let context = xkb::Context::default();
+        let names = RuleNames {
+            layout: Some("u\0s".to_owned()),
+            ..Default::default()
+        };
+        assert!(matches!(keymap_from_names(&context, &names), Err(KeymapError::Names(_))));
+    }
+}
@@ -1,120 +1,221 @@
+use bitflags::bitflags;
+use std::cell::RefCell;
 use std::fs::File;
 use std::os::unix::io::FromRawFd;
+use std::rc::Rc;
 
 use wayland_client::{
-    protocol::wl_keyboard::{Event::*, KeymapFormat, WlKeyboard},
+    protocol::{
+        wl_keyboard::{Event, Event::*, KeyState, KeymapFormat, WlKeyboard},
+        wl_surface::WlSurface,
+    },
     Main,
 };
 
+use super::Serial;
+
+mod error;
 mod missing_xkb_functions;
-use missing_xkb_functions::keymap_from_buffer;
+pub use error::KeymapError;
+use missing_xkb_functions::{keymap_from_buffer, keymap_from_names};
+pub use missing_xkb_functions::RuleNames;
+use crate::trace;
 
-pub fn handle(keyboard: &Main<WlKeyboard>) {
-    let context = xkb::Context::default();
-    keyboard.quick_assign(move |keyboard, event, _| match event {
-        Keymap { format, fd, size } => {
-            assert_eq!(format, KeymapFormat::XkbV1);
-            let mut file = unsafe { File::from_raw_fd(fd) };
-            let state = get_state(&context, &mut file, size as usize)
-                .expect("Failed to create first state");
-            handle_after_first_keymap_event(keyboard, state, context.clone());
-        }
-        _ => (),
-    });
+bitflags! {
+    /// The modifiers that take part in key bindings. Locks like Caps Lock
+    /// and Num Lock are left out.
+    #[derive(Default)]
+    pub struct Modifiers: u32 {
+        /// Control.
+        const CTRL = 1 << 0;
+        /// Shift.
+        const SHIFT = 1 << 1;
+        /// Alt, also known as Mod1.
+        const ALT = 1 << 2;
+        /// Super or the Windows key, also known as Mod4.
+        const LOGO = 1 << 3;
+    }
 }
 
-fn get_state(
-    xkb_context: &xkb::Context,
-    file: &mut File,
-    size: usize,
-) -> Result<xkb::State, Box<dyn std::error::Error>> {
-    let buffer = unsafe { memmap2::MmapOptions::new().len(size).map(&file) }?;
-    // Treating the buffer as a slice of chars instead of a CString
-    // by stripping the trailing null byte.
-    let keymap = keymap_from_buffer(&xkb_context, &buffer[..size-1])?;
-    let state = keymap.state();
-    Ok(state)
+impl Modifiers {
+    fn from_state(state: &xkb::State) -> Self {
+        let names = [
+            (Modifiers::CTRL, xkb::name::mods::CTRL),
+            (Modifiers::SHIFT, xkb::name::mods::SHIFT),
+            (Modifiers::ALT, xkb::name::mods::ALT),
+            (Modifiers::LOGO, xkb::name::mods::LOGO),
+        ];
+        let mods = state.mods();
+        names
+            .iter()
+            .filter(|(_, name)| mods.active(*name, xkb::state::component::MODS_EFFECTIVE))
+            .fold(Modifiers::empty(), |active, (modifier, _)| active | *modifier)
+    }
 }
 
-fn handle_after_first_keymap_event(
-    keyboard: Main<WlKeyboard>,
-    state: xkb::State,
-    context: xkb::Context,
-) {
-    let mut state = state;
+/// A key press or release, decoded with the current keymap.
+#[derive(Clone)]
+pub struct KeyEvent {
+    /// The surface with keyboard focus.
+    pub surface: Option<WlSurface>,
+    /// Timestamp in milliseconds.
+    pub time: u32,
+    /// The evdev keycode.
+    pub keycode: u32,
+    /// Whether the key went down rather than up.
+    pub pressed: bool,
+    /// The keysym produced with the active modifiers, like `T` for Shift+t.
+    pub keysym: Option<xkb::Keysym>,
+    /// The keysym on the key's first level, the same whatever modifiers
+    /// are held, like `t` for Shift+t.
+    pub base_keysym: Option<xkb::Keysym>,
+    /// The text the key types, if any.
+    pub utf8: Option<String>,
+    /// The modifiers held, as far as bindings care.
+    pub modifiers: Modifiers,
+}
 
-    keyboard.quick_assign(move |_, event, _| match event {
-        Keymap { format, fd, size } => {
-            assert_eq!(format, KeymapFormat::XkbV1);
-            let mut file = unsafe { File::from_raw_fd(fd) };
-            match get_state(&context, &mut file, size as usize) {
-                Ok(new_state) => {
-                    state = new_state;
-                }
-                Err(error) => {
-                    eprintln!("Failed to set new state after keymap event: {}", error);
-                }
-            }
+impl KeyEvent {
+    fn new(
+        state: Option<&xkb::State>,
+        surface: Option<WlSurface>,
+        time: u32,
+        keycode: u32,
+        pressed: bool,
+    ) -> Self {
+        let mut event = KeyEvent {
+            surface,
+            time,
+            keycode,
+            pressed,
+            keysym: None,
+            base_keysym: None,
+            utf8: None,
+            modifiers: Modifiers::empty(),
+        };
+        if let Some(state) = state {
+            let key = state.key(keycode + 8);
+            event.keysym = key.sym();
+            event.utf8 = key.utf8();
+            event.base_keysym = key.layout().and_then(|layout| {
+                let keymap = state.keymap();
+                let key = keymap.key(xkb::Keycode(keycode + 8));
+                let syms = key.syms(xkb::LayoutIndex(layout as u32), xkb::LevelIndex(0));
+                syms.first().copied()
+            });
+            event.modifiers = Modifiers::from_state(state);
         }
-        Enter {
-            serial: _,
-            surface: _,
-            keys,
-        } => {
-            eprintln!("keyboard enter keys pressed are: ");
+        event
+    }
+}
 
-            // Assume keys are already aligned
-            let keys = unsafe { keys.as_slice().align_to::<u32>() }.1
-                .iter()
-                .map(|key| state.key(*key + 8));
-            for key in keys {
-                if let Some(sym) = key.sym() {
-                    eprint!("sym: {} ({}), ", sym.to_string(), sym.0);
-                } else {
-                    eprint!("sym: Unknown ");
-                }
+pub fn handle<F>(
+    keyboard: &Main<WlKeyboard>,
+    serial: Serial,
+    fallback: Rc<RefCell<RuleNames>>,
+    mut on_key: F,
+) where
+    F: FnMut(&KeyEvent) + 'static,
+{
+    let context = xkb::Context::default();
+    // Without a keymap, keys are reported as raw keycodes.
+    let mut state = None as Option<xkb::State>;
+    let mut focus = None as Option<WlSurface>;
 
-                if let Some(utf8_string) = key.utf8() {
-                    eprint!("utf8: {}", utf8_string);
+    keyboard.quick_assign(move |keyboard, event, _| {
+        trace::event(&keyboard, &event);
+        match event {
+            Keymap { format, fd, size } => {
+                let file = unsafe { File::from_raw_fd(fd) };
+                state = match get_state(&context, format, &file, size as usize) {
+                    Ok(new_state) => new_state,
+                    Err(error) => {
+                        eprintln!("warning: {}, falling back to the default keymap", error);
+                        fallback_state(&context, &fallback.borrow())
+                    }
+                };
+            }
+            Enter {
+                serial: enter_serial,
+                surface,
+                ..
+            } => {
+                serial.set(Some(enter_serial));
+                focus = Some(surface);
+            }
+            Key {
+                serial: key_serial,
+                time,
+                key,
+                state: key_state,
+            } => {
+                serial.set(Some(key_serial));
+                let pressed = key_state == KeyState::Pressed;
+                on_key(&KeyEvent::new(state.as_ref(), focus.clone(), time, key, pressed));
+            }
+            Leave { .. } => {
+                serial.set(None);
+                focus = None;
+            }
+            Event::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+                ..
+            } => {
+                if let Some(state) = state.as_mut() {
+                    state
+                        .update()
+                        .mask(mods_depressed, mods_latched, mods_locked, 0, 0, group);
                 }
-
-                eprintln!();
             }
+            RepeatInfo { .. } => (),
+            _ => (),
         }
-        Key {
-            key,
-            state: key_state,
-            ..
-        } => {
-            eprint!("key {:?}: ", key_state);
-
-            let key = state.key(key + 8);
+    });
+}
 
-            if let Some(sym) = key.sym() {
-                eprint!("sym: {} ", sym.to_string());
-            }
+/// Compiles the keymap sent by the compositor. `Ok(None)` means the
+/// compositor has no keymap, and keys should be taken as raw keycodes.
+fn get_state(
+    xkb_context: &xkb::Context,
+    format: KeymapFormat,
+    file: &File,
+    size: usize,
+) -> Result<Option<xkb::State>, KeymapError> {
+    Ok(read_keymap(xkb_context, format, file, size)?.map(|keymap| keymap.state()))
+}
 
-            if let Some(utf8_string) = key.utf8() {
-                eprint!("utf8: {}", utf8_string);
-            }
+/// Compiles the keymap of a `wl_keyboard.keymap` event, `size` bytes of
+/// `file` in `format`. `Ok(None)` means the compositor has no keymap.
+pub fn read_keymap(
+    xkb_context: &xkb::Context,
+    format: KeymapFormat,
+    file: &File,
+    size: usize,
+) -> Result<Option<xkb::Keymap>, KeymapError> {
+    match format {
+        KeymapFormat::NoKeymap => return Ok(None),
+        KeymapFormat::XkbV1 => (),
+        format => return Err(KeymapError::UnsupportedFormat(format.to_raw())),
+    }
+    if size == 0 {
+        return Err(KeymapError::Empty);
+    }
+    let buffer = unsafe { memmap2::MmapOptions::new().len(size).map(file) }
+        .map_err(KeymapError::Map)?;
+    keymap_from_buffer(xkb_context, &buffer).map(Some)
+}
 
-            eprintln!();
-        }
-        Leave { .. } => {
-            eprintln!("keyboard leave");
+/// A keymap from `names`, completed from the `XKB_DEFAULT_*` environment
+/// variables or the system defaults.
+fn fallback_state(xkb_context: &xkb::Context, names: &RuleNames) -> Option<xkb::State> {
+    match keymap_from_names(xkb_context, names) {
+        Ok(keymap) => Some(keymap.state()),
+        Err(error) => {
+            eprintln!("warning: {}, using raw keycodes", error);
+            None
         }
-        Modifiers {
-            mods_depressed,
-            mods_latched,
-            mods_locked,
-            group,
-            ..
-        } => {
-            state
-                .update()
-                .mask(mods_depressed, mods_latched, mods_locked, 0, 0, group);
-        }
-        RepeatInfo { .. } => (),
-        _ => (),
-    });
+    }
 }
@@ -1,60 +1,450 @@
+//! Input from seats: pointer, keyboard, touch and text input.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
 use wayland_client::{
     protocol::{
-        wl_keyboard::WlKeyboard,
-        wl_pointer::WlPointer,
-        wl_touch::WlTouch,
+        wl_keyboard::{self, WlKeyboard},
+        wl_pointer::{self, WlPointer},
+        wl_touch::{self, WlTouch},
         wl_seat::{
+            self,
             Capability,
-            Event::{Capabilities, Name},
+            Event::Capabilities,
             WlSeat,
         },
     },
     Main,
 };
 
+use crate::globals::{GlobalChange, Globals};
+
 mod keyboard;
 mod pointer;
+mod shortcuts_inhibit;
+mod text_input;
 mod touch;
 
-pub fn handle(seat: &Main<WlSeat>) {
-    let mut pointer = None as Option<WlPointer>;
-    let mut keyboard = None as Option<WlKeyboard>;
-    let mut touch = None as Option<WlTouch>;
+pub use keyboard::{read_keymap, KeyEvent, KeymapError, Modifiers, RuleNames};
+pub use pointer::PointerEvent;
+pub use shortcuts_inhibit::ShortcutsInhibitEvent;
+pub use text_input::TextInputEvent;
+pub use touch::{TouchEvent, TouchPoint};
+use crate::trace;
+
+/// The serial of the latest input event of some kind.
+///
+/// Requests like setting the selection or starting a drag must carry the
+/// serial of the user input that caused them.
+#[derive(Clone, Default)]
+pub struct Serial(Rc<Cell<Option<u32>>>);
+
+impl Serial {
+    /// The serial, or `None` if there is no such input right now.
+    pub fn get(&self) -> Option<u32> {
+        self.0.get()
+    }
+
+    fn set(&self, serial: Option<u32>) {
+        self.0.set(serial);
+    }
+}
+
+/// Serials of the input events that requests on behalf of the user need.
+#[derive(Clone, Default)]
+pub struct InputSerials {
+    /// The latest keyboard `Enter` or `Key` event, cleared on `Leave`.
+    pub keyboard: Serial,
+    /// The pointer button press that is still held down, if any.
+    pub button: Serial,
+}
+
+type Listener<E> = Box<dyn FnMut(&E)>;
+
+#[derive(Default)]
+struct Listeners {
+    pointer_frame: Vec<Listener<PointerEvent>>,
+    key: Vec<Listener<KeyEvent>>,
+    touch_frame: Vec<Listener<TouchEvent>>,
+    text_input: Vec<Listener<TextInputEvent>>,
+    shortcuts_inhibit: Vec<Listener<ShortcutsInhibitEvent>>,
+}
+
+/// Calls the listeners picked by `select` without keeping them borrowed,
+/// so they are free to add more listeners.
+fn notify<E>(
+    listeners: &RefCell<Listeners>,
+    select: fn(&mut Listeners) -> &mut Vec<Listener<E>>,
+    event: &E,
+) {
+    let mut current = std::mem::take(select(&mut listeners.borrow_mut()));
+    for listener in current.iter_mut() {
+        listener(event);
+    }
+    let mut listeners = listeners.borrow_mut();
+    let added = select(&mut listeners);
+    current.append(added);
+    *added = current;
+}
+
+/// The input devices of a seat, as its capabilities allow.
+#[derive(Default)]
+struct Devices {
+    pointer: Option<WlPointer>,
+    keyboard: Option<WlKeyboard>,
+    touch: Option<WlTouch>,
+}
+
+impl Devices {
+    fn release_pointer(&mut self) {
+        if let Some(pointer) = self.pointer.take() {
+            if pointer.as_ref().version() >= wl_pointer::REQ_RELEASE_SINCE {
+                pointer.release();
+            }
+        }
+    }
+
+    fn release_keyboard(&mut self) {
+        if let Some(keyboard) = self.keyboard.take() {
+            if keyboard.as_ref().version() >= wl_keyboard::REQ_RELEASE_SINCE {
+                keyboard.release();
+            }
+        }
+    }
+
+    fn release_touch(&mut self) {
+        if let Some(touch) = self.touch.take() {
+            if touch.as_ref().version() >= wl_touch::REQ_RELEASE_SINCE {
+                touch.release();
+            }
+        }
+    }
+}
+
+/// Lets the application follow the input of a seat.
+#[derive(Clone)]
+pub struct Seat {
+    seat: Main<WlSeat>,
+    serials: InputSerials,
+    devices: Rc<RefCell<Devices>>,
+    listeners: Rc<RefCell<Listeners>>,
+    fallback_keymap: Rc<RefCell<RuleNames>>,
+}
+
+impl Seat {
+    /// The seat itself, for what this crate doesn't wrap.
+    pub fn wl_seat(&self) -> &Main<WlSeat> {
+        &self.seat
+    }
+
+    /// Serials of the latest input on this seat.
+    pub fn serials(&self) -> &InputSerials {
+        &self.serials
+    }
+
+    /// Sets the layout to fall back to when the compositor's keymap
+    /// can't be used. Names left out come from the `XKB_DEFAULT_*`
+    /// environment variables, or else the system defaults.
+    pub fn set_fallback_keymap(&self, names: RuleNames) {
+        *self.fallback_keymap.borrow_mut() = names;
+    }
+
+    /// Stops listening to the seat, once it's gone or no longer needed.
+    pub fn release(&self) {
+        let mut devices = self.devices.borrow_mut();
+        devices.release_pointer();
+        devices.release_keyboard();
+        devices.release_touch();
+        self.serials.keyboard.set(None);
+        self.serials.button.set(None);
+        if self.seat.as_ref().version() >= wl_seat::REQ_RELEASE_SINCE {
+            self.seat.release();
+        }
+    }
+
+    /// Calls `listener` with every pointer frame, after all of its events.
+    pub fn on_pointer_frame<F>(&self, listener: F)
+    where
+        F: FnMut(&PointerEvent) + 'static,
+    {
+        self.listeners
+            .borrow_mut()
+            .pointer_frame
+            .push(Box::new(listener));
+    }
+
+    /// Calls `listener` with every touch frame, after all of its events.
+    pub fn on_touch_frame<F>(&self, listener: F)
+    where
+        F: FnMut(&TouchEvent) + 'static,
+    {
+        self.listeners
+            .borrow_mut()
+            .touch_frame
+            .push(Box::new(listener));
+    }
+
+    /// Calls `listener` with every key press and release.
+    pub fn on_key<F>(&self, listener: F)
+    where
+        F: FnMut(&KeyEvent) + 'static,
+    {
+        self.listeners.borrow_mut().key.push(Box::new(listener));
+    }
+}
+
+/// A seat coming or going, as passed to the callback of `watch`.
+pub enum SeatChange {
+    /// A seat was added, or was there already.
+    Added(u32, Seat),
+    /// A seat was removed, and has been released.
+    Removed(u32, Seat),
+}
+
+/// Follows the seats of the compositor as they come and go, and calls
+/// `callback` with each change.
+pub fn watch<F>(globals: &Globals, mut callback: F)
+where
+    F: FnMut(&SeatChange) + 'static,
+{
+    let mut seats = Vec::<(u32, Seat)>::new();
+    globals.on_global::<WlSeat, _>(1..=5, move |change| match change {
+        GlobalChange::Added { name, global } => {
+            let seat = handle(&global, &InputSerials::default());
+            seats.push((name, seat.clone()));
+            callback(&SeatChange::Added(name, seat));
+        }
+        GlobalChange::Removed { name } => {
+            if let Some(index) = seats.iter().position(|(seat_name, _)| *seat_name == name) {
+                let (_, seat) = seats.swap_remove(index);
+                seat.release();
+                callback(&SeatChange::Removed(name, seat));
+            }
+        }
+    });
+}
+
+fn handle(seat: &Main<WlSeat>, serials: &InputSerials) -> Seat {
+    let serials = serials.clone();
+    let listeners = Rc::new(RefCell::new(Listeners::default()));
+    let devices = Rc::new(RefCell::new(Devices::default()));
+    let fallback_keymap = Rc::new(RefCell::new(RuleNames::default()));
+    let seat_handle = Seat {
+        seat: seat.clone(),
+        serials: serials.clone(),
+        devices: devices.clone(),
+        listeners: listeners.clone(),
+        fallback_keymap: fallback_keymap.clone(),
+    };
     seat.quick_assign(move |seat, event, _| {
+        trace::event(&seat, &event);
+        let mut devices = devices.borrow_mut();
         match event {
             Capabilities { capabilities: cap } => {
                 let seat_has_pointer = cap.contains(Capability::Pointer);
-                let pointer_created = pointer.is_some();
+                let pointer_created = devices.pointer.is_some();
                 if seat_has_pointer && !pointer_created {
                     let new_pointer = seat.get_pointer();
-                    pointer::handle(&new_pointer);
-                    pointer.replace(new_pointer.detach());
+                    pointer::handle(&new_pointer, serials.button.clone(), {
+                        let listeners = listeners.clone();
+                        move |event| notify(&listeners, |l| &mut l.pointer_frame, event)
+                    });
+                    devices.pointer.replace(new_pointer.detach());
                 } else if !seat_has_pointer && pointer_created {
-                    pointer.take();
+                    devices.release_pointer();
+                    serials.button.set(None);
                 }
 
                 let seat_has_keyboard = cap.contains(Capability::Keyboard);
-                let keyboard_created = keyboard.is_some();
+                let keyboard_created = devices.keyboard.is_some();
                 if seat_has_keyboard && !keyboard_created {
                     let new_keyboard = seat.get_keyboard();
-                    keyboard::handle(&new_keyboard);
-                    keyboard.replace(new_keyboard.detach());
+                    let fallback = fallback_keymap.clone();
+                    keyboard::handle(&new_keyboard, serials.keyboard.clone(), fallback, {
+                        let listeners = listeners.clone();
+                        move |event| notify(&listeners, |l| &mut l.key, event)
+                    });
+                    devices.keyboard.replace(new_keyboard.detach());
                 } else if !seat_has_keyboard && keyboard_created {
-                    keyboard.take();
+                    devices.release_keyboard();
+                    serials.keyboard.set(None);
                 }
 
                 let seat_has_touch = cap.contains(Capability::Touch);
-                let touch_created = touch.is_some();
+                let touch_created = devices.touch.is_some();
                 if seat_has_touch && !touch_created {
                     let new_touch = seat.get_touch();
-                    touch::handle(&new_touch);
-                    touch.replace(new_touch.detach());
+                    touch::handle(&new_touch, {
+                        let listeners = listeners.clone();
+                        move |event| notify(&listeners, |l| &mut l.touch_frame, event)
+                    });
+                    devices.touch.replace(new_touch.detach());
                 } else if !seat_has_touch && touch_created {
-                    touch.take();
+                    devices.release_touch();
                 }
             }
-            Name { name } => eprintln!("seat name: {}", name),
             _ => (),
         };
     });
+    seat_handle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_compositor::{Capability as ServerCapability, TestClient, TestCompositor};
+    use wayland_client::protocol::wl_compositor::WlCompositor;
+
+    /// A seat with all devices, and a surface for them to focus.
+    fn seat() -> (TestCompositor, TestClient, Seat) {
+        let (compositor, mut client) = TestCompositor::start();
+        let seats = Rc::new(RefCell::new(Vec::new()));
+        watch(client.globals(), {
+            let seats = seats.clone();
+            move |change| {
+                if let SeatChange::Added(_, seat) = change {
+                    seats.borrow_mut().push(seat.clone());
+                }
+            }
+        });
+        let wl_compositor = client.globals().bind::<WlCompositor>(1..=4).unwrap();
+        wl_compositor.create_surface();
+        client.roundtrip();
+        let seat = seats.borrow_mut().pop().expect("no seat was announced");
+        (compositor, client, seat)
+    }
+
+    #[test]
+    fn button_serial_is_kept_while_pressed() {
+        let (compositor, mut client, seat) = seat();
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        seat.on_pointer_frame({
+            let frames = frames.clone();
+            move |event| frames.borrow_mut().push(event.button())
+        });
+        compositor.pointer_enter(10.0, 10.0);
+        compositor.pointer_motion(12.0, 10.0);
+        let serial = compositor.pointer_button(0x110, true);
+        client.roundtrip();
+        assert_eq!(seat.serials().button.get(), Some(serial));
+        compositor.pointer_button(0x110, false);
+        compositor.pointer_leave();
+        client.roundtrip();
+        assert_eq!(seat.serials().button.get(), None);
+        let frames = frames.borrow();
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[1], None);
+        assert_eq!(frames[3], Some((0x110, wl_pointer::ButtonState::Released)));
+    }
+
+    #[test]
+    fn pointer_frames_tell_where_and_how_far() {
+        let (compositor, mut client, seat) = seat();
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        seat.on_pointer_frame({
+            let frames = frames.clone();
+            move |event: &PointerEvent| {
+                frames.borrow_mut().push((
+                    event.position(),
+                    event.entered().is_some(),
+                    event.left().is_some(),
+                    event.axis(wl_pointer::Axis::VerticalScroll),
+                    event.axis_source(),
+                    event.serial(),
+                ))
+            }
+        });
+        let enter = compositor.pointer_enter(1.0, 2.0);
+        compositor.pointer_scroll(15.0, 1);
+        let leave = compositor.pointer_leave();
+        client.roundtrip();
+        assert_eq!(
+            frames.borrow().as_slice(),
+            &[
+                (Some((1.0, 2.0)), true, false, None, None, enter),
+                (None, false, false, Some((15.0, 1)), Some(wl_pointer::AxisSource::Wheel), 0),
+                (None, false, true, None, None, leave),
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_reach_listeners_with_the_focused_surface() {
+        let (compositor, mut client, seat) = seat();
+        let keys = Rc::new(RefCell::new(Vec::new()));
+        seat.on_key({
+            let keys = keys.clone();
+            move |event| keys.borrow_mut().push((event.keycode, event.pressed, event.surface.is_some()))
+        });
+        compositor.keyboard_enter();
+        let serial = compositor.key(30, true);
+        compositor.key(30, false);
+        client.roundtrip();
+        assert_eq!(keys.borrow().as_slice(), &[(30, true, true), (30, false, true)]);
+        assert!(seat.serials().keyboard.get() > Some(serial));
+        compositor.keyboard_leave();
+        client.roundtrip();
+        assert_eq!(seat.serials().keyboard.get(), None);
+    }
+
+    #[test]
+    fn touch_frames_reach_listeners() {
+        let (compositor, mut client, seat) = seat();
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        seat.on_touch_frame({
+            let frames = frames.clone();
+            move |event: &TouchEvent| {
+                let points = event.points().map(|(id, point)| (id, point.is_down(), point.is_up(), point.position()));
+                frames.borrow_mut().extend(points);
+            }
+        });
+        compositor.touch_down(3, 1.0, 2.0);
+        compositor.touch_motion(3, 4.0, 5.0);
+        compositor.touch_up(3);
+        client.roundtrip();
+        assert_eq!(
+            frames.borrow().as_slice(),
+            &[
+                (3, true, false, Some((1.0, 2.0))),
+                (3, false, false, Some((4.0, 5.0))),
+                (3, false, true, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_keymaps_fall_back_to_the_chosen_layout() {
+        let (compositor, mut client, seat) = seat();
+        let keysyms = Rc::new(RefCell::new(Vec::new()));
+        seat.on_key({
+            let keysyms = keysyms.clone();
+            move |event| keysyms.borrow_mut().push(event.keysym)
+        });
+        seat.set_fallback_keymap(RuleNames {
+            layout: Some("de".to_owned()),
+            ..Default::default()
+        });
+        compositor.broken_keymap();
+        compositor.keyboard_enter();
+        // The key right of T, which is Z rather than Y in German.
+        compositor.key(21, true);
+        client.roundtrip();
+        assert_eq!(keysyms.borrow().as_slice(), &[Some("z".parse().unwrap())]);
+    }
+
+    #[test]
+    fn lost_capabilities_release_their_devices() {
+        let (compositor, mut client, _seat) = seat();
+        compositor.touch_down(0, 1.0, 1.0);
+        compositor.touch_motion(0, 2.0, 2.0);
+        compositor.touch_up(0);
+        client.roundtrip();
+        compositor.set_capabilities(ServerCapability::Keyboard);
+        client.roundtrip();
+        let log = compositor.log();
+        assert_eq!(log.count("wl_pointer.release"), 1);
+        assert_eq!(log.count("wl_touch.release"), 1);
+        assert_eq!(log.count("wl_keyboard.release"), 0);
+    }
 }
@@ -1,75 +1,96 @@
 use bitflags::bitflags;
 use std::fmt;
+use super::Serial;
 use wayland_client::{
-    protocol::wl_pointer::{ButtonState, Event::*, WlPointer},
+    protocol::{
+        wl_pointer::{self, ButtonState, Event::*, WlPointer},
+        wl_surface::WlSurface,
+    },
     Main,
 };
+use crate::trace;
 
-pub fn handle(pointer: &Main<WlPointer>) {
+pub fn handle<F>(pointer: &Main<WlPointer>, button_serial: Serial, mut on_frame: F)
+where
+    F: FnMut(&PointerEvent) + 'static,
+{
     let mut pointer_event = PointerEvent::default();
-    pointer.quick_assign(move |_pointer, event, _data| match event {
-        Enter {
-            serial,
-            surface_x,
-            surface_y,
-            ..
-        } => {
-            pointer_event.event_mask |= EventMask::ENTER;
-            pointer_event.serial = serial;
-            pointer_event.surface_x = surface_x;
-            pointer_event.surface_y = surface_y;
-        }
-        Leave { serial, .. } => {
-            pointer_event.event_mask |= EventMask::LEAVE;
-            pointer_event.serial = serial;
-        }
-        Motion {
-            time,
-            surface_x,
-            surface_y,
-        } => {
-            pointer_event.event_mask |= EventMask::MOTION;
-            pointer_event.time = time;
-            pointer_event.surface_x = surface_x;
-            pointer_event.surface_y = surface_y;
-        }
-        Button {
-            serial,
-            time,
-            button,
-            state,
-        } => {
-            pointer_event.event_mask |= EventMask::BUTTON;
-            pointer_event.time = time;
-            pointer_event.serial = serial;
-            pointer_event.button = button;
-            pointer_event.state = state.to_raw();
-        }
-        Axis { time, axis, value } => {
-            pointer_event.event_mask |= EventMask::AXIS;
-            pointer_event.time = time;
-            pointer_event.axes[axis.to_raw() as usize].valid = true;
-            pointer_event.axes[axis.to_raw() as usize].value = value;
-        }
-        AxisSource { axis_source } => {
-            pointer_event.event_mask |= EventMask::AXIS_SOURCE;
-            pointer_event.axis_source = axis_source.to_raw();
-        }
-        AxisStop { time, axis } => {
-            pointer_event.event_mask |= EventMask::AXIS_STOP;
-            pointer_event.time = time;
-            pointer_event.axes[axis.to_raw() as usize].valid = true;
-        }
-        AxisDiscrete { axis, discrete } => {
-            pointer_event.event_mask |= EventMask::AXIS_DISCRETE;
-            pointer_event.axes[axis.to_raw() as usize].valid = true;
-            pointer_event.axes[axis.to_raw() as usize].discrete = discrete;
+    // Before wl_seat 5 there are no frames, so every event stands alone.
+    let has_frames = pointer.as_ref().version() >= wl_pointer::EVT_FRAME_SINCE;
+    pointer.quick_assign(move |pointer, event, _data| {
+        trace::event(&pointer, &event);
+        let ends_frame = !has_frames || matches!(event, Frame);
+        match event {
+            Enter {
+                serial,
+                surface,
+                surface_x,
+                surface_y,
+            } => {
+                pointer_event.event_mask |= EventMask::ENTER;
+                pointer_event.serial = serial;
+                pointer_event.entered = Some(surface);
+                pointer_event.surface_x = surface_x;
+                pointer_event.surface_y = surface_y;
+            }
+            Leave { serial, surface } => {
+                pointer_event.event_mask |= EventMask::LEAVE;
+                pointer_event.serial = serial;
+                pointer_event.left = Some(surface);
+            }
+            Motion {
+                time,
+                surface_x,
+                surface_y,
+            } => {
+                pointer_event.event_mask |= EventMask::MOTION;
+                pointer_event.time = time;
+                pointer_event.surface_x = surface_x;
+                pointer_event.surface_y = surface_y;
+            }
+            Button {
+                serial,
+                time,
+                button,
+                state,
+            } => {
+                pointer_event.event_mask |= EventMask::BUTTON;
+                pointer_event.time = time;
+                pointer_event.serial = serial;
+                pointer_event.button = button;
+                pointer_event.state = state.to_raw();
+                match state {
+                    ButtonState::Pressed => button_serial.set(Some(serial)),
+                    _ => button_serial.set(None),
+                }
+            }
+            Axis { time, axis, value } => {
+                pointer_event.event_mask |= EventMask::AXIS;
+                pointer_event.time = time;
+                pointer_event.axes[axis.to_raw() as usize].valid = true;
+                pointer_event.axes[axis.to_raw() as usize].value = value;
+            }
+            AxisSource { axis_source } => {
+                pointer_event.event_mask |= EventMask::AXIS_SOURCE;
+                pointer_event.axis_source = axis_source.to_raw();
+            }
+            AxisStop { time, axis } => {
+                pointer_event.event_mask |= EventMask::AXIS_STOP;
+                pointer_event.time = time;
+                pointer_event.axes[axis.to_raw() as usize].valid = true;
+                pointer_event.axes[axis.to_raw() as usize].stopped = true;
+            }
+            AxisDiscrete { axis, discrete } => {
+                pointer_event.event_mask |= EventMask::AXIS_DISCRETE;
+                pointer_event.axes[axis.to_raw() as usize].valid = true;
+                pointer_event.axes[axis.to_raw() as usize].discrete = discrete;
+            }
+            _ => (),
         }
-        Frame => {
-            eprintln!("{}", pointer_event);
+        if ends_frame {
+            on_frame(&pointer_event);
             pointer_event = Default::default();
         }
-        _ => (),
     });
 }
 
@@ -93,11 +114,15 @@ struct Axes {
     valid: bool,
     value: f64,
     discrete: i32,
+    stopped: bool,
 }
 
-#[derive(Default, Debug)]
-struct PointerEvent {
+/// All pointer events up to a `frame`, which belong together.
+#[derive(Default)]
+pub struct PointerEvent {
     event_mask: EventMask,
+    entered: Option<WlSurface>,
+    left: Option<WlSurface>,
     surface_x: f64,
     surface_y: f64,
     button: u32,
@@ -109,8 +134,73 @@ struct PointerEvent {
 }
 
 impl PointerEvent {
+    /// Where the pointer entered or moved to, in surface coordinates.
+    pub fn position(&self) -> Option<(f64, f64)> {
+        if self.event_mask.intersects(EventMask::ENTER | EventMask::MOTION) {
+            Some((self.surface_x, self.surface_y))
+        } else {
+            None
+        }
+    }
+
+    /// The surface the pointer moved onto, which later events are on.
+    pub fn entered(&self) -> Option<&WlSurface> {
+        self.entered.as_ref()
+    }
+
+    /// The surface the pointer moved off. A frame may both leave one
+    /// surface and enter another.
+    pub fn left(&self) -> Option<&WlSurface> {
+        self.left.as_ref()
+    }
+
+    /// The button that changed state in this frame, and its new state.
+    pub fn button(&self) -> Option<(u32, ButtonState)> {
+        if self.event_mask.contains(EventMask::BUTTON) {
+            ButtonState::from_raw(self.state).map(|state| (self.button, state))
+        } else {
+            None
+        }
+    }
+
+    /// How far `axis` scrolled, and by how many wheel clicks if it was a
+    /// wheel. Both are 0 when the axis only stopped.
+    pub fn axis(&self, axis: wl_pointer::Axis) -> Option<(f64, i32)> {
+        match &self.axes[axis.to_raw() as usize] {
+            axes if axes.valid => Some((axes.value, axes.discrete)),
+            _ => None,
+        }
+    }
+
+    /// Whether scrolling on `axis` came to a stop, as when fingers lift
+    /// off a touchpad.
+    pub fn axis_stopped(&self, axis: wl_pointer::Axis) -> bool {
+        self.axes[axis.to_raw() as usize].stopped
+    }
+
+    /// What the scrolling in this frame came from.
+    pub fn axis_source(&self) -> Option<wl_pointer::AxisSource> {
+        if self.event_mask.contains(EventMask::AXIS_SOURCE) {
+            wl_pointer::AxisSource::from_raw(self.axis_source)
+        } else {
+            None
+        }
+    }
+
+    /// Timestamp in milliseconds of the latest motion, button or axis
+    /// event, or 0 if there was none.
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    /// Serial of the latest enter, leave or button event, or 0 if there
+    /// was none. Requests answering the input, like a move, need it.
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
     fn axis_name(i: usize) -> &'static str {
-        use wayland_client::protocol::wl_pointer::Axis;
+        use wl_pointer::Axis;
         if let Option::Some(Axis::VerticalScroll) = Axis::from_raw(i as u32) {
             "vertical"
         } else {
@@ -119,12 +209,12 @@ impl PointerEvent {
     }
 
     fn axis_source_type(x: u32) -> &'static str {
-        use wayland_client::protocol::wl_pointer::AxisSource;
+        use wl_pointer::AxisSource;
         if let Some(source) = AxisSource::from_raw(x) {
             match source {
                 AxisSource::Wheel => "wheel",
                 AxisSource::Finger => "finger",
-                AxisSource::Continuous => "continous",
+                AxisSource::Continuous => "continuous",
                 AxisSource::WheelTilt => "wheel tilt",
                 _ => "unknown source",
             }
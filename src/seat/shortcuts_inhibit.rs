@@ -0,0 +1,89 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use wayland_client::{protocol::wl_surface::WlSurface, Main};
+use wayland_protocols::unstable::keyboard_shortcuts_inhibit::v1::client::{
+    zwp_keyboard_shortcuts_inhibit_manager_v1::ZwpKeyboardShortcutsInhibitManagerV1,
+    zwp_keyboard_shortcuts_inhibitor_v1::{Event, ZwpKeyboardShortcutsInhibitorV1},
+};
+
+use super::{notify, Listener, Listeners, Seat};
+use crate::trace;
+
+/// Whether a `ShortcutsInhibitor` is honored.
+#[derive(Clone)]
+pub enum ShortcutsInhibitEvent {
+    /// The compositor stopped handling its shortcuts while `surface` has
+    /// keyboard focus, so all keys reach the application.
+    Active(WlSurface),
+    /// The compositor handles its shortcuts again, for example after the
+    /// user pressed its escape combination.
+    Inactive(WlSurface),
+}
+
+/// A request to the compositor to pass all keys to a surface while it has
+/// keyboard focus, even those it would use for its own shortcuts.
+///
+/// The compositor decides whether and when to honor it, and the user may
+/// override it. Shortcuts are restored when this is dropped.
+pub struct ShortcutsInhibitor {
+    inhibitor: Main<ZwpKeyboardShortcutsInhibitorV1>,
+    active: Rc<Cell<bool>>,
+}
+
+impl ShortcutsInhibitor {
+    pub fn is_active(&self) -> bool {
+        self.active.get()
+    }
+}
+
+impl Drop for ShortcutsInhibitor {
+    fn drop(&mut self) {
+        self.inhibitor.destroy();
+    }
+}
+
+impl Seat {
+    /// Asks the compositor to stop handling its shortcuts while `surface`
+    /// has the keyboard focus of this seat. Only one inhibitor may exist
+    /// per surface and seat.
+    pub fn inhibit_shortcuts(
+        &self,
+        manager: &Main<ZwpKeyboardShortcutsInhibitManagerV1>,
+        surface: &WlSurface,
+    ) -> ShortcutsInhibitor {
+        fn select(listeners: &mut Listeners) -> &mut Vec<Listener<ShortcutsInhibitEvent>> {
+            &mut listeners.shortcuts_inhibit
+        }
+        let inhibitor = manager.inhibit_shortcuts(surface, &self.seat);
+        let active = Rc::new(Cell::new(false));
+        inhibitor.quick_assign({
+            let listeners = self.listeners.clone();
+            let active = active.clone();
+            let surface = surface.clone();
+            move |inhibitor, event, _| {
+                trace::event(&inhibitor, &event);
+                let event = match event {
+                    Event::Active => ShortcutsInhibitEvent::Active(surface.clone()),
+                    Event::Inactive => ShortcutsInhibitEvent::Inactive(surface.clone()),
+                    _ => return,
+                };
+                active.set(matches!(event, ShortcutsInhibitEvent::Active(_)));
+                notify(&listeners, select, &event);
+            }
+        });
+        ShortcutsInhibitor { inhibitor, active }
+    }
+
+    /// Calls `listener` when shortcuts inhibitors of this seat become
+    /// active or inactive.
+    pub fn on_shortcuts_inhibit<F>(&self, listener: F)
+    where
+        F: FnMut(&ShortcutsInhibitEvent) + 'static,
+    {
+        self.listeners
+            .borrow_mut()
+            .shortcuts_inhibit
+            .push(Box::new(listener));
+    }
+}
@@ -0,0 +1,302 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wayland_client::{protocol::wl_surface::WlSurface, Main};
+use wayland_protocols::unstable::text_input::v3::client::{
+    zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+    zwp_text_input_v3::{ContentHint, ContentPurpose, Event, ZwpTextInputV3},
+};
+
+use super::{notify, Listener, Listeners, Seat};
+use crate::trace;
+
+/// Text being composed by the input method, shown at the cursor until
+/// it is committed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preedit {
+    pub text: String,
+    /// Byte range within `text` to show as the cursor, `None` to hide it.
+    pub cursor: Option<(usize, usize)>,
+}
+
+/// Changes from the input method to apply together, in field order:
+/// delete around the cursor, insert the committed text, then show the
+/// new preedit in place of the old one.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TextInputChange {
+    /// Bytes to delete before and after the cursor.
+    pub delete_surrounding: Option<(usize, usize)>,
+    pub commit: Option<String>,
+    pub preedit: Option<Preedit>,
+    /// Whether the input method had seen our latest state when making the
+    /// change. Surrounding text should be sent again if not.
+    pub up_to_date: bool,
+}
+
+impl TextInputChange {
+    /// Applies the deletion and the committed text to `text` at the byte
+    /// offset `cursor`, and moves the cursor after the inserted text.
+    pub fn apply(&self, text: &mut String, cursor: &mut usize) {
+        let mut position = floor_char_boundary(text, (*cursor).min(text.len()));
+        if let Some((before, after)) = self.delete_surrounding {
+            let start = floor_char_boundary(text, position.saturating_sub(before));
+            let end = ceil_char_boundary(text, (position + after).min(text.len()));
+            text.replace_range(start..end, "");
+            position = start;
+        }
+        if let Some(commit) = &self.commit {
+            text.insert_str(position, commit);
+            position += commit.len();
+        }
+        *cursor = position;
+    }
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+/// What the input method of a seat does.
+#[derive(Clone)]
+pub enum TextInputEvent {
+    /// The input method focus entered a surface.
+    Enter(WlSurface),
+    /// The input method focus left a surface.
+    Leave(WlSurface),
+    /// The input method changed the text, to be applied at once.
+    Change(TextInputChange),
+}
+
+struct EnabledSurface {
+    surface: WlSurface,
+    hint: ContentHint,
+    purpose: ContentPurpose,
+}
+
+#[derive(Default)]
+struct State {
+    enabled_surfaces: Vec<EnabledSurface>,
+    focus: Option<WlSurface>,
+    commit_count: u32,
+    pending: TextInputChange,
+}
+
+/// Input method support through `zwp_text_input_v3`, for text that can't be
+/// typed with the keyboard layout alone.
+///
+/// Input methods are enabled while a surface marked with `enable_for` has
+/// text input focus. Their changes are delivered by `Seat::on_text_input`.
+#[derive(Clone)]
+pub struct TextInput {
+    text_input: Main<ZwpTextInputV3>,
+    state: Rc<RefCell<State>>,
+}
+
+impl TextInput {
+    /// Makes `surface` take text from input methods, described by a
+    /// content type.
+    pub fn enable_for(&self, surface: &WlSurface, hint: ContentHint, purpose: ContentPurpose) {
+        let mut state = self.state.borrow_mut();
+        state.enabled_surfaces.retain(|enabled| enabled.surface != *surface);
+        state.enabled_surfaces.push(EnabledSurface {
+            surface: surface.clone(),
+            hint,
+            purpose,
+        });
+        if state.focus.as_ref() == Some(surface) {
+            drop(state);
+            self.enable(hint, purpose);
+        }
+    }
+
+    pub fn disable_for(&self, surface: &WlSurface) {
+        let mut state = self.state.borrow_mut();
+        state.enabled_surfaces.retain(|enabled| enabled.surface != *surface);
+        if state.focus.as_ref() == Some(surface) {
+            drop(state);
+            self.text_input.disable();
+            self.commit();
+        }
+    }
+
+    fn enable(&self, hint: ContentHint, purpose: ContentPurpose) {
+        self.text_input.enable();
+        self.text_input.set_content_type(hint, purpose);
+        self.commit();
+    }
+
+    /// Tells the input method about the text around the cursor, with the
+    /// cursor and selection anchor as byte offsets into `text`.
+    /// The text should stay below 4000 bytes. Takes effect on `commit`.
+    pub fn set_surrounding_text(&self, text: &str, cursor: usize, anchor: usize) {
+        self.text_input
+            .set_surrounding_text(text.to_owned(), cursor as i32, anchor as i32);
+    }
+
+    /// Tells the input method where the cursor is in surface coordinates,
+    /// so candidate windows can be placed next to it. Takes effect on `commit`.
+    pub fn set_cursor_rectangle(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.text_input.set_cursor_rectangle(x, y, width, height);
+    }
+
+    pub fn set_content_type(&self, hint: ContentHint, purpose: ContentPurpose) {
+        self.text_input.set_content_type(hint, purpose);
+    }
+
+    pub fn commit(&self) {
+        self.state.borrow_mut().commit_count += 1;
+        self.text_input.commit();
+    }
+}
+
+impl Seat {
+    /// Creates the text input of this seat.
+    pub fn text_input(&self, manager: &Main<ZwpTextInputManagerV3>) -> TextInput {
+        let text_input = TextInput {
+            text_input: manager.get_text_input(&self.seat),
+            state: Rc::new(RefCell::new(State::default())),
+        };
+        text_input.text_input.quick_assign({
+            let text_input = text_input.clone();
+            let listeners = self.listeners.clone();
+            move |proxy, event, _| {
+                trace::event(&proxy, &event);
+                handle_event(&text_input, &listeners, event)
+            }
+        });
+        text_input
+    }
+
+    /// Calls `listener` with the changes made by the input method, and as
+    /// its focus moves between surfaces.
+    pub fn on_text_input<F>(&self, listener: F)
+    where
+        F: FnMut(&TextInputEvent) + 'static,
+    {
+        self.listeners
+            .borrow_mut()
+            .text_input
+            .push(Box::new(listener));
+    }
+}
+
+fn handle_event(text_input: &TextInput, listeners: &RefCell<Listeners>, event: Event) {
+    fn select(listeners: &mut Listeners) -> &mut Vec<Listener<TextInputEvent>> {
+        &mut listeners.text_input
+    }
+    match event {
+        Event::Enter { surface } => {
+            let enabled = {
+                let mut state = text_input.state.borrow_mut();
+                state.focus = Some(surface.clone());
+                state
+                    .enabled_surfaces
+                    .iter()
+                    .find(|enabled| enabled.surface == surface)
+                    .map(|enabled| (enabled.hint, enabled.purpose))
+            };
+            if let Some((hint, purpose)) = enabled {
+                text_input.enable(hint, purpose);
+            }
+            notify(listeners, select, &TextInputEvent::Enter(surface));
+        }
+        Event::Leave { surface } => {
+            let was_enabled = {
+                let mut state = text_input.state.borrow_mut();
+                state.focus = None;
+                state.pending = TextInputChange::default();
+                state
+                    .enabled_surfaces
+                    .iter()
+                    .any(|enabled| enabled.surface == surface)
+            };
+            if was_enabled {
+                text_input.text_input.disable();
+                text_input.commit();
+            }
+            notify(listeners, select, &TextInputEvent::Leave(surface));
+        }
+        Event::PreeditString {
+            text,
+            cursor_begin,
+            cursor_end,
+        } => {
+            let cursor = if cursor_begin < 0 || cursor_end < 0 {
+                None
+            } else {
+                Some((cursor_begin as usize, cursor_end as usize))
+            };
+            text_input.state.borrow_mut().pending.preedit = text.map(|text| Preedit { text, cursor });
+        }
+        Event::CommitString { text } => {
+            text_input.state.borrow_mut().pending.commit = text;
+        }
+        Event::DeleteSurroundingText {
+            before_length,
+            after_length,
+        } => {
+            text_input.state.borrow_mut().pending.delete_surrounding =
+                Some((before_length as usize, after_length as usize));
+        }
+        Event::Done { serial } => {
+            let change = {
+                let mut state = text_input.state.borrow_mut();
+                let mut change = std::mem::take(&mut state.pending);
+                change.up_to_date = serial == state.commit_count;
+                change
+            };
+            notify(listeners, select, &TextInputEvent::Change(change));
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(change: TextInputChange, text: &str, cursor: usize) -> (String, usize) {
+        let mut text = text.to_owned();
+        let mut cursor = cursor;
+        change.apply(&mut text, &mut cursor);
+        (text, cursor)
+    }
+
+    #[test]
+    fn commit_is_inserted_at_cursor() {
+        let change = TextInputChange {
+            commit: Some("日本".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(apply(change, "ab", 1), ("a日本b".to_owned(), 7));
+    }
+
+    #[test]
+    fn delete_surrounding_happens_before_commit() {
+        let change = TextInputChange {
+            delete_surrounding: Some((2, 1)),
+            commit: Some("X".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(apply(change, "abcde", 3), ("aXe".to_owned(), 2));
+    }
+
+    #[test]
+    fn delete_surrounding_does_not_split_characters() {
+        let change = TextInputChange {
+            delete_surrounding: Some((1, 0)),
+            ..Default::default()
+        };
+        assert_eq!(apply(change, "aé", 3), ("a".to_owned(), 1));
+    }
+}
@@ -1,52 +1,64 @@
 use bitflags::bitflags;
 use std::collections::HashMap;
+use std::fmt;
 
 use wayland_client::{
     protocol::wl_touch::{WlTouch, Event::*},
     Main,
 };
+use crate::trace;
 
-pub fn handle(touch: &Main<WlTouch>) {
+pub fn handle<F>(touch: &Main<WlTouch>, mut on_frame: F)
+where
+    F: FnMut(&TouchEvent) + 'static,
+{
     let mut touch_event = TouchEvent::default();
-    touch.quick_assign(move |_, event, _| match event {
-        Down { id, x, y, time, serial, .. } => {
-            let point = touch_event.get_point(id);
-            point.event_mask |= EventMask::DOWN;
-            point.surface_x = x;
-            point.surface_y = y;
-            touch_event.time = time;
-            touch_event.serial = serial;
-        }
-        Up { id, .. } => {
-            let point = touch_event.get_point(id);
-            point.event_mask |= EventMask::UP;
-        }
-        Motion { id, x, y, time, .. } => {
-            let point = touch_event.get_point(id);
-            point.event_mask |= EventMask::MOTION;
-            point.surface_x = x;
-            point.surface_y = y;
-            touch_event.time = time;
-        }
-        Cancel { .. } => {
-            touch_event.event_mask |= EventMask::CANCEL;
-        }
-        Shape { id, major, minor, .. } => {
-            let point = touch_event.get_point(id);
-            point.event_mask |= EventMask::SHAPE;
-            point.major = major;
-            point.minor = minor;
-        }
-        Orientation { id, orientation, .. } => {
-            let point = touch_event.get_point(id);
-            point.event_mask |= EventMask::ORIENTATION;
-            point.orientation = orientation;
+    touch.quick_assign(move |touch, event, _| {
+        trace::event(&touch, &event);
+        // A cancel comes alone, without a frame after it.
+        let ends_frame = matches!(event, Frame | Cancel);
+        match event {
+            Down { id, x, y, time, serial, .. } => {
+                let point = touch_event.get_point(id);
+                point.event_mask |= EventMask::DOWN;
+                point.surface_x = x;
+                point.surface_y = y;
+                touch_event.time = time;
+                touch_event.serial = serial;
+            }
+            Up { id, time, serial } => {
+                let point = touch_event.get_point(id);
+                point.event_mask |= EventMask::UP;
+                touch_event.time = time;
+                touch_event.serial = serial;
+            }
+            Motion { id, x, y, time, .. } => {
+                let point = touch_event.get_point(id);
+                point.event_mask |= EventMask::MOTION;
+                point.surface_x = x;
+                point.surface_y = y;
+                touch_event.time = time;
+            }
+            Cancel => {
+                touch_event.event_mask |= EventMask::CANCEL;
+            }
+            Shape { id, major, minor, .. } => {
+                let point = touch_event.get_point(id);
+                point.event_mask |= EventMask::SHAPE;
+                point.major = major;
+                point.minor = minor;
+            }
+            Orientation { id, orientation, .. } => {
+                let point = touch_event.get_point(id);
+                point.event_mask |= EventMask::ORIENTATION;
+                point.orientation = orientation;
+            }
+            _ => (),
         }
-        Frame { .. } => {
-            eprintln!("{}", touch_event);
+        if ends_frame {
+            on_frame(&touch_event);
             touch_event = Default::default();
         }
-        _ => (),
     });
 }
 
@@ -62,10 +74,9 @@ bitflags! {
     }
 }
 
-#[derive(Default, Debug)]
-struct TouchPoint {
-    valid: bool,
-    id: i32,
+/// What happened to one touch point within a frame.
+#[derive(Default, Debug, Clone)]
+pub struct TouchPoint {
     event_mask: EventMask,
     surface_x: f64,
     surface_y: f64,
@@ -74,8 +85,30 @@ struct TouchPoint {
     orientation: f64,
 }
 
-#[derive(Default, Debug)]
-struct TouchEvent {
+impl TouchPoint {
+    /// Whether the point started touching the surface.
+    pub fn is_down(&self) -> bool {
+        self.event_mask.contains(EventMask::DOWN)
+    }
+
+    /// Whether the point stopped touching the surface.
+    pub fn is_up(&self) -> bool {
+        self.event_mask.contains(EventMask::UP)
+    }
+
+    /// Where the point went down or moved to, in surface coordinates.
+    pub fn position(&self) -> Option<(f64, f64)> {
+        if self.event_mask.intersects(EventMask::DOWN | EventMask::MOTION) {
+            Some((self.surface_x, self.surface_y))
+        } else {
+            None
+        }
+    }
+}
+
+/// All touch events up to a `frame`, which belong together.
+#[derive(Default, Debug, Clone)]
+pub struct TouchEvent {
     event_mask: EventMask,
     time: u32,
     serial: u32,
@@ -88,32 +121,53 @@ impl TouchEvent {
             .entry(id)
             .or_default()
     }
+
+    /// The points that changed in this frame, by id, in no particular
+    /// order. Ids are reused once a point is up.
+    pub fn points(&self) -> impl Iterator<Item = (i32, &TouchPoint)> {
+        self.points.iter().map(|(&id, point)| (id, point))
+    }
+
+    /// Whether the compositor took the touches over, say for a gesture.
+    /// None of the points that were down are any longer.
+    pub fn is_cancelled(&self) -> bool {
+        self.event_mask.contains(EventMask::CANCEL)
+    }
+
+    /// Timestamp in milliseconds.
+    pub fn time(&self) -> u32 {
+        self.time
+    }
 }
 
-impl std::fmt::Display for TouchEvent {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl fmt::Display for TouchEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "touch event @ {}:", self.time)?;
 
+        if self.is_cancelled() {
+            writeln!(f, "cancelled")?;
+        }
+
         for (id, point) in self.points.iter() {
             write!(f, "point {}: ", id)?;
 
-            if self.event_mask.contains(EventMask::DOWN) {
+            if point.event_mask.contains(EventMask::DOWN) {
                 write!(f, "down {},{} ", point.surface_x, point.surface_y)?;
             }
 
-            if self.event_mask.contains(EventMask::UP) {
+            if point.event_mask.contains(EventMask::UP) {
                 write!(f, "up ")?;
             }
 
-            if self.event_mask.contains(EventMask::MOTION) {
+            if point.event_mask.contains(EventMask::MOTION) {
                 write!(f, "motion {},{} ", point.surface_x, point.surface_y)?;
             }
 
-            if self.event_mask.contains(EventMask::SHAPE) {
+            if point.event_mask.contains(EventMask::SHAPE) {
                 write!(f, "shape {}x{}", point.major, point.minor)?;
             }
 
-            if self.event_mask.contains(EventMask::ORIENTATION) {
+            if point.event_mask.contains(EventMask::ORIENTATION) {
                 write!(f, "orientation {} ", point.orientation)?;
             }
 
@@ -1,15 +1,14 @@
-use memmap2;
 use nix::sys::memfd;
-use std::ffi::CString;
+use std::ffi::CStr;
 use std::fs::File;
 use std::ops::{Deref, DerefMut};
 use std::os::unix::io::FromRawFd;
 
-pub fn create_anonymous_file() -> Result<File, Box<dyn std::error::Error>> {
-    let fd = memfd::memfd_create(
-        CString::new("Fafa")?.as_c_str(),
-        memfd::MemFdCreateFlag::empty(),
-    )?;
+use crate::error::Error;
+
+pub fn create_anonymous_file() -> Result<File, Error> {
+    let name = CStr::from_bytes_with_nul(b"Fafa\0").unwrap();
+    let fd = memfd::memfd_create(name, memfd::MemFdCreateFlag::empty()).map_err(Error::Memfd)?;
     Ok(unsafe { File::from_raw_fd(fd) })
 }
 
@@ -20,13 +19,14 @@ pub struct MemMap {
 }
 
 impl<'a> MemMap {
-    pub fn anon_file(size: usize) -> Result<MemMap, Box<dyn std::error::Error>> {
+    pub fn anon_file(size: usize) -> Result<MemMap, Error> {
         let file = create_anonymous_file()?;
-        file.set_len(size as u64)?;
+        file.set_len(size as u64).map_err(Error::Mmap)?;
         let buffer = unsafe {
             memmap2::MmapOptions::new()
-                .len(size as usize)
-                .map_mut(&file)?
+                .len(size)
+                .map_mut(&file)
+                .map_err(Error::Mmap)?
         };
         Ok(MemMap { buffer, file })
     }
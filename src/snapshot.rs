@@ -0,0 +1,166 @@
+//! Golden-image tests: pixels rendered in memory are compared with
+//! reference images in `tests/snapshots`, stored as binary PPM.
+//!
+//! On a mismatch the rendered image and a diff are written next to each
+//! other in `target/snapshots`. Run the tests with `UPDATE_SNAPSHOTS=1` to
+//! write the references from what is rendered instead.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::image::{ppm, Image};
+
+fn channels(pixel: u32) -> [u8; 3] {
+    [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]
+}
+
+/// How far a rendering may be off its reference and still match.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// The largest difference allowed in any color channel of a pixel.
+    pub channel: u8,
+    /// How many pixels may differ by more than `channel`.
+    pub pixels: usize,
+}
+
+impl Tolerance {
+    pub const EXACT: Tolerance = Tolerance {
+        channel: 0,
+        pixels: 0,
+    };
+}
+
+/// Checks that `actual` is within `tolerance` of `expected`. The diff of a
+/// mismatch is red where pixels differ too much, and a dimmed copy of
+/// `expected` elsewhere.
+pub fn compare(expected: &Image, actual: &Image, tolerance: Tolerance) -> Result<(), Mismatch> {
+    let size = |image: &Image| (image.width(), image.height());
+    if size(expected) != size(actual) {
+        return Err(Mismatch {
+            reason: format!(
+                "size is {}x{}, expected {}x{}",
+                actual.width(),
+                actual.height(),
+                expected.width(),
+                expected.height()
+            ),
+            diff: None,
+        });
+    }
+    let mut diff = vec![0; expected.pixels().len()];
+    let mut differing = 0;
+    for ((&want, &got), out) in expected.pixels().iter().zip(actual.pixels()).zip(&mut diff) {
+        let want_channels = channels(want);
+        let got_channels = channels(got);
+        let off = want_channels
+            .iter()
+            .zip(&got_channels)
+            .any(|(a, b)| a.abs_diff(*b) > tolerance.channel);
+        *out = if off {
+            differing += 1;
+            0xFFFF_0000
+        } else {
+            let [r, g, b] = want_channels.map(|c| c / 4);
+            0xFF00_0000 | u32::from(r) << 16 | u32::from(g) << 8 | u32::from(b)
+        };
+    }
+    if differing > tolerance.pixels {
+        Err(Mismatch {
+            reason: format!(
+                "{} pixels differ by more than {}, {} allowed",
+                differing, tolerance.channel, tolerance.pixels
+            ),
+            diff: Some(Image::new(expected.width(), expected.height(), diff)),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Mismatch {
+    pub reason: String,
+    pub diff: Option<Image>,
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(name)
+        .with_extension("ppm")
+}
+
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target/snapshots")
+}
+
+fn write(path: &Path, image: &Image) -> io::Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, ppm::encode(image))
+}
+
+/// Compares `actual` with the reference image `name`, and panics with the
+/// paths of the rendered and diff images if they don't match.
+pub fn assert_snapshot(name: &str, actual: &Image, tolerance: Tolerance) {
+    let reference = reference_path(name);
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        write(&reference, actual).expect("failed to write the reference image");
+        return;
+    }
+    let expected = match fs::read(&reference) {
+        Ok(data) => Image::decode(&data)
+            .unwrap_or_else(|error| panic!("{}: {}", reference.display(), error)),
+        Err(error) => panic!(
+            "{}: {}, run with UPDATE_SNAPSHOTS=1 to create it",
+            reference.display(),
+            error
+        ),
+    };
+    if let Err(mismatch) = compare(&expected, actual, tolerance) {
+        let actual_path = output_dir().join(format!("{}.actual.ppm", name));
+        write(&actual_path, actual).expect("failed to write the rendered image");
+        let mut message = format!(
+            "snapshot {} does not match: {}\n  rendered: {}",
+            name,
+            mismatch.reason,
+            actual_path.display()
+        );
+        if let Some(diff) = mismatch.diff {
+            let diff_path = output_dir().join(format!("{}.diff.ppm", name));
+            write(&diff_path, &diff).expect("failed to write the diff image");
+            message += &format!("\n  diff: {}", diff_path.display());
+        }
+        panic!("{}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> Vec<u32> {
+        (0..6).map(|i| 0xFF00_0000 | (i * 40) << 8).collect()
+    }
+
+    #[test]
+    fn tolerance_allows_small_and_few_differences() {
+        let expected = Image::new(3, 2, gradient());
+        let mut pixels = gradient();
+        pixels[0] += 2;
+        pixels[5] = 0xFFFF_FFFF;
+        let actual = Image::new(3, 2, pixels);
+        assert!(compare(&expected, &actual, Tolerance::EXACT).is_err());
+        let tolerance = Tolerance {
+            channel: 2,
+            pixels: 1,
+        };
+        assert!(compare(&expected, &actual, tolerance).is_ok());
+        let mismatch = compare(&expected, &actual, Tolerance { channel: 1, ..tolerance }).unwrap_err();
+        let diff = mismatch.diff.unwrap();
+        assert_eq!(diff.pixels()[0], 0xFFFF_0000);
+        assert_eq!(diff.pixels()[5], 0xFFFF_0000);
+        assert_ne!(diff.pixels()[1], 0xFFFF_0000);
+    }
+}
@@ -0,0 +1,1018 @@
+//! A headless compositor for tests, running in a thread of the test and
+//! talking to the client over a socketpair.
+//!
+//! It advertises `wl_compositor`, `wl_shm`, `xdg_wm_base` and `wl_seat`,
+//! records every request of the client and a copy of every committed shm
+//! buffer, and sends input and window events when the test asks for them.
+//! Each of those calls returns once the events are sent, so a roundtrip of
+//! the client afterwards is enough to see them.
+
+use std::fs::File;
+use std::os::unix::io::{AsFd, AsRawFd, IntoRawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use nix::poll::{poll, PollFd, PollFlags};
+use wayland_client::Display;
+use wayland_protocols_server::xdg::shell::server::{
+    xdg_popup::XdgPopup, xdg_positioner::XdgPositioner, xdg_surface, xdg_surface::XdgSurface,
+    xdg_toplevel, xdg_toplevel::XdgToplevel, xdg_wm_base, xdg_wm_base::XdgWmBase,
+};
+use wayland_server::{
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_callback::{self, WlCallback},
+        wl_compositor::{self, WlCompositor},
+        wl_keyboard::{self, KeyState, KeymapFormat, WlKeyboard},
+        wl_pointer::{self, ButtonState, WlPointer},
+        wl_region::{self, WlRegion},
+        wl_seat::{self, WlSeat},
+        wl_shm::{self, WlShm},
+        wl_shm_pool::{self, WlShmPool},
+        wl_surface::{self, WlSurface},
+        wl_touch::{self, WlTouch},
+    },
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+};
+
+use crate::connection::Connection;
+use crate::globals::Globals;
+use crate::pixel_format::PixelFormat;
+
+pub use wayland_server::protocol::wl_seat::Capability;
+
+/// A rectangle as sent with `damage` and `damage_buffer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// A copy of the contents of a committed shm buffer.
+#[derive(Debug, Clone)]
+pub struct ShmBuffer {
+    pub width: i32,
+    pub height: i32,
+    pub stride: i32,
+    /// The raw `wl_shm` format code.
+    pub format: u32,
+    pub data: Vec<u8>,
+}
+
+impl ShmBuffer {
+    /// The 32 bit pixel at `x`, `y`, in native byte order.
+    pub fn pixel(&self, x: i32, y: i32) -> u32 {
+        let offset = (y * self.stride + x * 4) as usize;
+        let bytes = [
+            self.data[offset],
+            self.data[offset + 1],
+            self.data[offset + 2],
+            self.data[offset + 3],
+        ];
+        u32::from_ne_bytes(bytes)
+    }
+
+    /// The pixel at `x`, `y` as premultiplied ARGB8888, whatever the
+    /// format of the buffer.
+    pub fn argb(&self, x: i32, y: i32) -> u32 {
+        let format = wayland_client::protocol::wl_shm::Format::from_raw(self.format)
+            .and_then(PixelFormat::from_shm)
+            .expect("not a format we draw in");
+        let size = format.bytes_per_pixel();
+        let offset = y as usize * self.stride as usize + x as usize * size;
+        format.decode(&self.data[offset..offset + size])
+    }
+}
+
+/// What a `wl_surface.commit` applied.
+#[derive(Debug, Clone, Default)]
+pub struct Commit {
+    /// The buffer attached since the previous commit, if any was.
+    pub buffer: Option<ShmBuffer>,
+    pub damage: Vec<Rect>,
+    pub buffer_damage: Vec<Rect>,
+    /// The opaque region, if it was set since the previous commit. Empty
+    /// when it was unset.
+    pub opaque_region: Option<Vec<Rect>>,
+}
+
+/// What the client did so far.
+#[derive(Debug, Clone, Default)]
+pub struct Log {
+    /// Every request, as `interface.request`, in the order received.
+    pub requests: Vec<String>,
+    pub commits: Vec<Commit>,
+    pub acked_configures: Vec<u32>,
+    pub title: Option<String>,
+}
+
+impl Log {
+    pub fn count(&self, request: &str) -> usize {
+        self.requests.iter().filter(|name| *name == request).count()
+    }
+}
+
+enum Command {
+    Configure { width: i32, height: i32 },
+    Close,
+    FrameDone { time: u32 },
+    HoldBuffers(bool),
+    SetCapabilities(Capability),
+    PointerEnter { x: f64, y: f64 },
+    PointerMotion { x: f64, y: f64 },
+    PointerButton { button: u32, pressed: bool },
+    PointerScroll { value: f64, clicks: i32 },
+    PointerLeave,
+    BrokenKeymap,
+    KeyboardEnter,
+    Key { key: u32, pressed: bool },
+    KeyboardLeave,
+    TouchDown { id: i32, x: f64, y: f64 },
+    TouchMotion { id: i32, x: f64, y: f64 },
+    TouchUp { id: i32 },
+}
+
+/// The compositor side of a test. Stops its thread when dropped.
+pub struct TestCompositor {
+    commands: Option<Sender<(Command, SyncSender<u32>)>>,
+    log: Arc<Mutex<Log>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The client side of a test, connected to a `TestCompositor`.
+pub struct TestClient {
+    pub connection: Connection,
+}
+
+impl TestClient {
+    /// Dispatches the events of the compositor, then waits until it has
+    /// handled the requests made in response, like acks and commits.
+    pub fn roundtrip(&mut self) {
+        for _ in 0..2 {
+            self.connection.roundtrip().expect("the test compositor hung up");
+        }
+    }
+
+    pub fn globals(&self) -> &Globals {
+        self.connection.globals()
+    }
+}
+
+impl TestCompositor {
+    /// Starts a compositor and connects a client to it, with the globals
+    /// already bound.
+    pub fn start() -> (TestCompositor, TestClient) {
+        let (server, client) = UnixStream::pair().expect("failed to create a socketpair");
+        let log = Arc::new(Mutex::new(Log::default()));
+        let (commands, receiver) = mpsc::channel();
+        let thread = thread::spawn({
+            let log = log.clone();
+            move || run(server, log, receiver)
+        });
+        let compositor = TestCompositor {
+            commands: Some(commands),
+            log,
+            thread: Some(thread),
+        };
+
+        let display = unsafe { Display::from_fd(client.into_raw_fd()) }
+            .expect("failed to connect to the test compositor");
+        let connection = Connection::from_display(display).expect("failed to set up the connection");
+        (compositor, TestClient { connection })
+    }
+
+    /// Runs `command` in the compositor thread and waits until its events
+    /// are sent. Returns the serial of the events, if they have one.
+    fn send(&self, command: Command) -> u32 {
+        let (done, wait) = mpsc::sync_channel(1);
+        self.commands
+            .as_ref()
+            .unwrap()
+            .send((command, done))
+            .expect("the test compositor stopped");
+        wait.recv().expect("the test compositor stopped")
+    }
+
+    /// A copy of what the client did so far.
+    pub fn log(&self) -> Log {
+        self.log.lock().unwrap().clone()
+    }
+
+    /// The latest committed buffer.
+    pub fn last_buffer(&self) -> Option<ShmBuffer> {
+        let log = self.log.lock().unwrap();
+        log.commits.iter().rev().find_map(|commit| commit.buffer.clone())
+    }
+
+    /// Configures every toplevel, and returns the serial to be acked.
+    pub fn configure(&self, width: i32, height: i32) -> u32 {
+        self.send(Command::Configure { width, height })
+    }
+
+    pub fn close(&self) {
+        self.send(Command::Close);
+    }
+
+    /// Calls the frame callbacks of the surfaces that have committed since
+    /// the previous call.
+    pub fn frame_done(&self, time: u32) {
+        self.send(Command::FrameDone { time });
+    }
+
+    /// Keeps attached buffers instead of releasing them right away, until
+    /// called again with `false`.
+    pub fn hold_buffers(&self, hold: bool) {
+        self.send(Command::HoldBuffers(hold));
+    }
+
+    pub fn set_capabilities(&self, capabilities: Capability) {
+        self.send(Command::SetCapabilities(capabilities));
+    }
+
+    /// Moves the pointer onto the latest surface.
+    pub fn pointer_enter(&self, x: f64, y: f64) -> u32 {
+        self.send(Command::PointerEnter { x, y })
+    }
+
+    pub fn pointer_motion(&self, x: f64, y: f64) {
+        self.send(Command::PointerMotion { x, y });
+    }
+
+    pub fn pointer_button(&self, button: u32, pressed: bool) -> u32 {
+        self.send(Command::PointerButton { button, pressed })
+    }
+
+    /// Turns the wheel by `clicks`, scrolling vertically by `value`.
+    pub fn pointer_scroll(&self, value: f64, clicks: i32) {
+        self.send(Command::PointerScroll { value, clicks });
+    }
+
+    pub fn pointer_leave(&self) -> u32 {
+        self.send(Command::PointerLeave)
+    }
+
+    /// Sends an empty XKB keymap, which the client can't compile.
+    pub fn broken_keymap(&self) {
+        self.send(Command::BrokenKeymap);
+    }
+
+    /// Gives the keyboard focus to the latest surface.
+    pub fn keyboard_enter(&self) -> u32 {
+        self.send(Command::KeyboardEnter)
+    }
+
+    /// Presses or releases the key with evdev keycode `key`.
+    pub fn key(&self, key: u32, pressed: bool) -> u32 {
+        self.send(Command::Key { key, pressed })
+    }
+
+    pub fn keyboard_leave(&self) -> u32 {
+        self.send(Command::KeyboardLeave)
+    }
+
+    /// Touches the latest surface.
+    pub fn touch_down(&self, id: i32, x: f64, y: f64) -> u32 {
+        self.send(Command::TouchDown { id, x, y })
+    }
+
+    pub fn touch_motion(&self, id: i32, x: f64, y: f64) {
+        self.send(Command::TouchMotion { id, x, y });
+    }
+
+    pub fn touch_up(&self, id: i32) -> u32 {
+        self.send(Command::TouchUp { id })
+    }
+}
+
+impl Drop for TestCompositor {
+    fn drop(&mut self) {
+        // Hanging up the channel stops the thread.
+        self.commands.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct State {
+    log: Arc<Mutex<Log>>,
+    serial: u32,
+    start: Instant,
+    capabilities: Capability,
+    surfaces: Vec<WlSurface>,
+    toplevels: Vec<(XdgSurface, XdgToplevel)>,
+    seats: Vec<WlSeat>,
+    pointers: Vec<WlPointer>,
+    keyboards: Vec<WlKeyboard>,
+    touches: Vec<WlTouch>,
+    /// Frame callbacks of committed surfaces, for `frame_done` to call.
+    frame_callbacks: Vec<WlCallback>,
+    /// Attached buffers to release later, while holding them.
+    held_buffers: Option<Vec<WlBuffer>>,
+}
+
+impl State {
+    fn next_serial(&mut self) -> u32 {
+        self.serial += 1;
+        self.serial
+    }
+
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    fn log_request<I: Resource>(&self, opcode: u16) {
+        let name = I::interface().requests[opcode as usize].name;
+        let request = format!("{}.{}", I::interface().name, name);
+        self.log.lock().unwrap().requests.push(request);
+    }
+
+    fn focus(&self) -> Option<WlSurface> {
+        self.surfaces.iter().rev().find(|surface| surface.is_alive()).cloned()
+    }
+
+    fn pointer_frame(&self) {
+        for pointer in alive(&self.pointers) {
+            if pointer.version() >= wl_pointer::EVT_FRAME_SINCE {
+                pointer.frame();
+            }
+        }
+    }
+
+    fn touch_frame(&self) {
+        for touch in alive(&self.touches) {
+            touch.frame();
+        }
+    }
+
+    fn run(&mut self, command: Command) -> u32 {
+        let time = self.time();
+        match command {
+            Command::Configure { width, height } => {
+                let serial = self.next_serial();
+                for (xdg_surface, toplevel) in &self.toplevels {
+                    if toplevel.is_alive() {
+                        toplevel.configure(width, height, Vec::new());
+                        xdg_surface.configure(serial);
+                    }
+                }
+                serial
+            }
+            Command::Close => {
+                for (_, toplevel) in &self.toplevels {
+                    if toplevel.is_alive() {
+                        toplevel.close();
+                    }
+                }
+                0
+            }
+            Command::FrameDone { time } => {
+                for callback in self.frame_callbacks.drain(..) {
+                    callback.done(time);
+                }
+                0
+            }
+            Command::HoldBuffers(hold) => {
+                let held = std::mem::replace(&mut self.held_buffers, hold.then(Vec::new));
+                for buffer in held.into_iter().flatten() {
+                    if buffer.is_alive() {
+                        buffer.release();
+                    }
+                }
+                0
+            }
+            Command::SetCapabilities(capabilities) => {
+                self.capabilities = capabilities;
+                for seat in alive(&self.seats) {
+                    seat.capabilities(capabilities);
+                }
+                0
+            }
+            Command::PointerEnter { x, y } => {
+                let serial = self.next_serial();
+                if let Some(surface) = self.focus() {
+                    for pointer in alive(&self.pointers) {
+                        pointer.enter(serial, &surface, x, y);
+                    }
+                }
+                self.pointer_frame();
+                serial
+            }
+            Command::PointerMotion { x, y } => {
+                for pointer in alive(&self.pointers) {
+                    pointer.motion(time, x, y);
+                }
+                self.pointer_frame();
+                0
+            }
+            Command::PointerButton { button, pressed } => {
+                let serial = self.next_serial();
+                let state = if pressed {
+                    ButtonState::Pressed
+                } else {
+                    ButtonState::Released
+                };
+                for pointer in alive(&self.pointers) {
+                    pointer.button(serial, time, button, state);
+                }
+                self.pointer_frame();
+                serial
+            }
+            Command::PointerScroll { value, clicks } => {
+                for pointer in alive(&self.pointers) {
+                    pointer.axis_source(wl_pointer::AxisSource::Wheel);
+                    pointer.axis_discrete(wl_pointer::Axis::VerticalScroll, clicks);
+                    pointer.axis(time, wl_pointer::Axis::VerticalScroll, value);
+                }
+                self.pointer_frame();
+                0
+            }
+            Command::PointerLeave => {
+                let serial = self.next_serial();
+                if let Some(surface) = self.focus() {
+                    for pointer in alive(&self.pointers) {
+                        pointer.leave(serial, &surface);
+                    }
+                }
+                self.pointer_frame();
+                serial
+            }
+            Command::BrokenKeymap => {
+                let empty = File::open("/dev/null").expect("failed to open /dev/null");
+                for keyboard in alive(&self.keyboards) {
+                    keyboard.keymap(KeymapFormat::XkbV1, empty.as_fd(), 0);
+                }
+                0
+            }
+            Command::KeyboardEnter => {
+                let serial = self.next_serial();
+                if let Some(surface) = self.focus() {
+                    for keyboard in alive(&self.keyboards) {
+                        keyboard.enter(serial, &surface, Vec::new());
+                    }
+                }
+                serial
+            }
+            Command::Key { key, pressed } => {
+                let serial = self.next_serial();
+                let state = if pressed {
+                    KeyState::Pressed
+                } else {
+                    KeyState::Released
+                };
+                for keyboard in alive(&self.keyboards) {
+                    keyboard.key(serial, time, key, state);
+                }
+                serial
+            }
+            Command::KeyboardLeave => {
+                let serial = self.next_serial();
+                if let Some(surface) = self.focus() {
+                    for keyboard in alive(&self.keyboards) {
+                        keyboard.leave(serial, &surface);
+                    }
+                }
+                serial
+            }
+            Command::TouchDown { id, x, y } => {
+                let serial = self.next_serial();
+                if let Some(surface) = self.focus() {
+                    for touch in alive(&self.touches) {
+                        touch.down(serial, time, &surface, id, x, y);
+                    }
+                }
+                self.touch_frame();
+                serial
+            }
+            Command::TouchMotion { id, x, y } => {
+                for touch in alive(&self.touches) {
+                    touch.motion(time, id, x, y);
+                }
+                self.touch_frame();
+                0
+            }
+            Command::TouchUp { id } => {
+                let serial = self.next_serial();
+                for touch in alive(&self.touches) {
+                    touch.up(serial, time, id);
+                }
+                self.touch_frame();
+                serial
+            }
+        }
+    }
+}
+
+fn alive<R: Resource>(resources: &[R]) -> impl Iterator<Item = &R> {
+    resources.iter().filter(|resource| resource.is_alive())
+}
+
+fn run(stream: UnixStream, log: Arc<Mutex<Log>>, commands: Receiver<(Command, SyncSender<u32>)>) {
+    let mut display = wayland_server::Display::<State>::new().expect("failed to create a display");
+    let handle = display.handle();
+    handle.create_global::<State, WlCompositor, _>(4, ());
+    handle.create_global::<State, WlShm, _>(1, ());
+    handle.create_global::<State, XdgWmBase, _>(2, ());
+    handle.create_global::<State, WlSeat, _>(5, ());
+    display
+        .handle()
+        .insert_client(stream, Arc::new(()))
+        .expect("failed to add the client");
+
+    let mut state = State {
+        log,
+        serial: 0,
+        start: Instant::now(),
+        capabilities: Capability::Pointer | Capability::Keyboard | Capability::Touch,
+        surfaces: Vec::new(),
+        toplevels: Vec::new(),
+        seats: Vec::new(),
+        pointers: Vec::new(),
+        keyboards: Vec::new(),
+        touches: Vec::new(),
+        frame_callbacks: Vec::new(),
+        held_buffers: None,
+    };
+    loop {
+        let fd = display.backend().poll_fd().as_raw_fd();
+        // Wakes up now and then to look for commands.
+        let _ = poll(&mut [PollFd::new(fd, PollFlags::POLLIN)], 5);
+        // The client hanging up ends the test, one way or another.
+        if display.dispatch_clients(&mut state).is_err() {
+            return;
+        }
+        loop {
+            match commands.try_recv() {
+                Ok((command, done)) => {
+                    let serial = state.run(command);
+                    let _ = display.flush_clients();
+                    let _ = done.send(serial);
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => return,
+            }
+        }
+        let _ = display.flush_clients();
+    }
+}
+
+impl GlobalDispatch<WlCompositor, ()> for State {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<WlCompositor>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<WlCompositor, ()> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<WlCompositor>(request.opcode());
+        match request {
+            wl_compositor::Request::CreateSurface { id } => {
+                let surface = data_init.init(id, Mutex::new(Commit::default()));
+                state.surfaces.push(surface);
+            }
+            wl_compositor::Request::CreateRegion { id } => {
+                data_init.init(id, Region::default());
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The rectangles added to a region. Subtracting is not supported.
+type Region = Mutex<Vec<Rect>>;
+
+impl Dispatch<WlRegion, Region> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        region: &WlRegion,
+        request: wl_region::Request,
+        rects: &Region,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<WlRegion>(request.opcode());
+        match request {
+            wl_region::Request::Add {
+                x,
+                y,
+                width,
+                height,
+            } => rects.lock().unwrap().push(Rect {
+                x,
+                y,
+                width,
+                height,
+            }),
+            wl_region::Request::Subtract { .. } => {
+                region.post_error(0u32, "subtracting is not supported by the test compositor")
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The surface's pending state, applied on commit.
+type PendingCommit = Mutex<Commit>;
+
+impl Dispatch<WlSurface, PendingCommit> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &WlSurface,
+        request: wl_surface::Request,
+        pending: &PendingCommit,
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<WlSurface>(request.opcode());
+        let mut pending = pending.lock().unwrap();
+        match request {
+            wl_surface::Request::Attach { buffer, .. } => {
+                pending.buffer = buffer.as_ref().and_then(read_buffer);
+                if let Some(buffer) = buffer {
+                    match &mut state.held_buffers {
+                        Some(held) => held.push(buffer),
+                        // The contents are copied already.
+                        None => buffer.release(),
+                    }
+                }
+            }
+            wl_surface::Request::Damage {
+                x,
+                y,
+                width,
+                height,
+            } => pending.damage.push(Rect {
+                x,
+                y,
+                width,
+                height,
+            }),
+            wl_surface::Request::DamageBuffer {
+                x,
+                y,
+                width,
+                height,
+            } => pending.buffer_damage.push(Rect {
+                x,
+                y,
+                width,
+                height,
+            }),
+            wl_surface::Request::SetOpaqueRegion { region } => {
+                let rects = region.and_then(|region| {
+                    region.data::<Region>().map(|rects| rects.lock().unwrap().clone())
+                });
+                pending.opaque_region = Some(rects.unwrap_or_default());
+            }
+            wl_surface::Request::Frame { callback } => {
+                let callback = data_init.init(callback, ());
+                state.frame_callbacks.push(callback);
+            }
+            wl_surface::Request::Commit => {
+                let commit = std::mem::take(&mut *pending);
+                state.log.lock().unwrap().commits.push(commit);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<WlCallback, ()> for State {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &WlCallback,
+        _: wl_callback::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<WlShm, ()> for State {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<WlShm>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let shm = data_init.init(resource, ());
+        shm.format(wl_shm::Format::Argb8888);
+        shm.format(wl_shm::Format::Xrgb8888);
+        // Some of the optional ones, but not all.
+        shm.format(wl_shm::Format::Rgb565);
+        shm.format(wl_shm::Format::Xbgr8888);
+        shm.format(wl_shm::Format::Abgr8888);
+        shm.format(wl_shm::Format::Xrgb2101010);
+    }
+}
+
+impl Dispatch<WlShm, ()> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &WlShm,
+        request: wl_shm::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<WlShm>(request.opcode());
+        if let wl_shm::Request::CreatePool { id, fd, size } = request {
+            let file = File::from(fd);
+            let pool = Pool::map(file, size as usize);
+            data_init.init(id, Arc::new(Mutex::new(pool)));
+        }
+    }
+}
+
+/// The memory of a pool, as the client may resize it.
+struct Pool {
+    file: File,
+    map: Option<memmap2::Mmap>,
+}
+
+impl Pool {
+    fn map(file: File, size: usize) -> Self {
+        let map = unsafe { memmap2::MmapOptions::new().len(size).map(&file) }.ok();
+        Pool { file, map }
+    }
+}
+
+type SharedPool = Arc<Mutex<Pool>>;
+
+impl Dispatch<WlShmPool, SharedPool> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &WlShmPool,
+        request: wl_shm_pool::Request,
+        pool: &SharedPool,
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<WlShmPool>(request.opcode());
+        match request {
+            wl_shm_pool::Request::CreateBuffer {
+                id,
+                offset,
+                width,
+                height,
+                stride,
+                format,
+            } => {
+                let data = BufferData {
+                    pool: pool.clone(),
+                    offset,
+                    width,
+                    height,
+                    stride,
+                    format: u32::from(format),
+                };
+                data_init.init(id, data);
+            }
+            wl_shm_pool::Request::Resize { size } => {
+                let mut pool = pool.lock().unwrap();
+                let file = pool.file.try_clone().expect("failed to duplicate the pool fd");
+                *pool = Pool::map(file, size as usize);
+            }
+            _ => (),
+        }
+    }
+}
+
+struct BufferData {
+    pool: SharedPool,
+    offset: i32,
+    width: i32,
+    height: i32,
+    stride: i32,
+    format: u32,
+}
+
+fn read_buffer(buffer: &WlBuffer) -> Option<ShmBuffer> {
+    let data = buffer.data::<BufferData>()?;
+    let pool = data.pool.lock().unwrap();
+    let map = pool.map.as_ref()?;
+    let start = data.offset as usize;
+    let end = start + (data.stride * data.height) as usize;
+    Some(ShmBuffer {
+        width: data.width,
+        height: data.height,
+        stride: data.stride,
+        format: data.format,
+        data: map.get(start..end)?.to_vec(),
+    })
+}
+
+impl Dispatch<WlBuffer, BufferData> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &WlBuffer,
+        request: wl_buffer::Request,
+        _: &BufferData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<WlBuffer>(request.opcode());
+    }
+}
+
+impl GlobalDispatch<XdgWmBase, ()> for State {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<XdgWmBase>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<XdgWmBase, ()> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &XdgWmBase,
+        request: xdg_wm_base::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<XdgWmBase>(request.opcode());
+        match request {
+            xdg_wm_base::Request::GetXdgSurface { id, .. } => {
+                data_init.init(id, ());
+            }
+            xdg_wm_base::Request::CreatePositioner { id } => {
+                data_init.post_error::<XdgPositioner>(id, 0u32, "not supported by the test compositor");
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<XdgSurface, ()> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        xdg_surface: &XdgSurface,
+        request: xdg_surface::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<XdgSurface>(request.opcode());
+        match request {
+            xdg_surface::Request::GetToplevel { id } => {
+                let toplevel = data_init.init(id, ());
+                state.toplevels.push((xdg_surface.clone(), toplevel));
+            }
+            xdg_surface::Request::GetPopup { id, .. } => {
+                data_init.post_error::<XdgPopup>(id, 0u32, "not supported by the test compositor");
+            }
+            xdg_surface::Request::AckConfigure { serial } => {
+                state.log.lock().unwrap().acked_configures.push(serial);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<XdgToplevel, ()> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &XdgToplevel,
+        request: xdg_toplevel::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<XdgToplevel>(request.opcode());
+        if let xdg_toplevel::Request::SetTitle { title } = request {
+            state.log.lock().unwrap().title = Some(title);
+        }
+    }
+}
+
+impl GlobalDispatch<WlSeat, ()> for State {
+    fn bind(
+        state: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<WlSeat>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let seat = data_init.init(resource, ());
+        seat.capabilities(state.capabilities);
+        if seat.version() >= wl_seat::EVT_NAME_SINCE {
+            seat.name("seat0".to_owned());
+        }
+        state.seats.push(seat);
+    }
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &WlSeat,
+        request: wl_seat::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<WlSeat>(request.opcode());
+        match request {
+            wl_seat::Request::GetPointer { id } => {
+                let pointer = data_init.init(id, ());
+                state.pointers.push(pointer);
+            }
+            wl_seat::Request::GetKeyboard { id } => {
+                let keyboard = data_init.init(id, ());
+                // Without a keymap the client reports raw keycodes, whatever
+                // the keyboard layouts of the machine running the tests.
+                let empty = File::open("/dev/null").expect("failed to open /dev/null");
+                keyboard.keymap(KeymapFormat::NoKeymap, empty.as_fd(), 0);
+                state.keyboards.push(keyboard);
+            }
+            wl_seat::Request::GetTouch { id } => {
+                let touch = data_init.init(id, ());
+                state.touches.push(touch);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<WlPointer, ()> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &WlPointer,
+        request: wl_pointer::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<WlPointer>(request.opcode());
+    }
+}
+
+impl Dispatch<WlKeyboard, ()> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &WlKeyboard,
+        request: wl_keyboard::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<WlKeyboard>(request.opcode());
+    }
+}
+
+impl Dispatch<WlTouch, ()> for State {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &WlTouch,
+        request: wl_touch::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        state.log_request::<WlTouch>(request.opcode());
+    }
+}
@@ -0,0 +1,220 @@
+//! The built-in bitmap font, which needs no font files.
+
+use std::rc::Rc;
+
+use super::{Font, Glyph};
+
+/// The printable ASCII characters, from space on, 5 columns of 7 pixels
+/// each. The lowest bit is the top row.
+const GLYPHS: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x14, 0x08, 0x3E, 0x08, 0x14], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+/// A 5 by 7 pixel font for printable ASCII, scaled up by a whole factor.
+/// Other characters are drawn as `?`.
+///
+/// Glyphs are 6 pixels apart and lines 9, times the scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitmapFont {
+    scale: usize,
+}
+
+impl BitmapFont {
+    /// The font with each of its pixels `scale` pixels wide and high.
+    pub fn new(scale: usize) -> Self {
+        BitmapFont {
+            scale: scale.max(1),
+        }
+    }
+
+    /// How many pixels wide and high each pixel of the font is drawn.
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+
+    fn columns(c: char) -> [u8; 5] {
+        let index = (c as usize).wrapping_sub(' ' as usize);
+        GLYPHS.get(index).copied().unwrap_or(GLYPHS[('?' as usize) - (' ' as usize)])
+    }
+}
+
+impl Default for BitmapFont {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl Font for BitmapFont {
+    fn ascent(&self) -> f32 {
+        (7 * self.scale) as f32
+    }
+
+    fn descent(&self) -> f32 {
+        self.scale as f32
+    }
+
+    fn line_height(&self) -> f32 {
+        (9 * self.scale) as f32
+    }
+
+    fn advance(&self, _: char) -> f32 {
+        (6 * self.scale) as f32
+    }
+
+    fn glyph(&self, c: char) -> Option<Rc<Glyph>> {
+        if c.is_whitespace() {
+            return None;
+        }
+        let columns = Self::columns(c);
+        let scale = self.scale;
+        let (width, height) = (5 * scale, 7 * scale);
+        let mut coverage = vec![0; width * height];
+        for (y, row) in coverage.chunks_exact_mut(width).enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                if columns[x / scale] >> (y / scale) & 1 == 1 {
+                    *pixel = 0xFF;
+                }
+            }
+        }
+        Some(Rc::new(Glyph {
+            width,
+            height,
+            left: 0,
+            top: height as i32,
+            coverage,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ascii_art(glyph: &Glyph) -> Vec<String> {
+        glyph
+            .coverage
+            .chunks_exact(glyph.width)
+            .map(|row| row.iter().map(|&c| if c > 0 { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn glyphs_are_drawn_from_the_table() {
+        let glyph = BitmapFont::new(1).glyph('A').unwrap();
+        assert_eq!((glyph.width, glyph.height, glyph.top), (5, 7, 7));
+        let a = [".###.", "#...#", "#...#", "#...#", "#####", "#...#", "#...#"];
+        assert_eq!(ascii_art(&glyph), a);
+        assert!(BitmapFont::new(1).glyph(' ').is_none());
+    }
+
+    #[test]
+    fn scaling_doubles_every_pixel() {
+        let font = BitmapFont::new(2);
+        let glyph = font.glyph('!').unwrap();
+        assert_eq!((glyph.width, glyph.height, font.advance('!')), (10, 14, 12.0));
+        let art = ascii_art(&glyph);
+        assert_eq!(art[0], "....##....");
+        assert_eq!(art[11], "..........");
+        assert_eq!(art[12], "....##....");
+    }
+
+    #[test]
+    fn unknown_characters_are_question_marks() {
+        let font = BitmapFont::default();
+        assert_eq!(font.glyph('é'), font.glyph('?'));
+    }
+}
@@ -0,0 +1,229 @@
+//! Finding installed fonts by family and style, like fontconfig does for
+//! a pattern, but by reading the font directories ourselves.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ttf_parser::{name_id, Face, Width};
+
+/// Common fonts for the generic families, in order of preference.
+const SANS_SERIF: &[&str] = &["DejaVu Sans", "Noto Sans", "Liberation Sans", "Cantarell", "Roboto"];
+const SERIF: &[&str] = &["DejaVu Serif", "Noto Serif", "Liberation Serif", "Times New Roman"];
+const MONOSPACE: &[&str] = &[
+    "DejaVu Sans Mono",
+    "Noto Sans Mono",
+    "Liberation Mono",
+    "Source Code Pro",
+    "Hack",
+];
+
+/// How deep to look into font directories, in case of symlink loops.
+const MAX_DEPTH: usize = 8;
+
+/// A font in a file, which may hold a collection of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontFile {
+    /// The file.
+    pub path: PathBuf,
+    /// Which font of the collection, 0 for files of a single font.
+    pub index: u32,
+}
+
+/// The directories fonts are installed in, most personal first:
+/// `$XDG_DATA_HOME/fonts`, `~/.fonts`, then `fonts` in each of
+/// `$XDG_DATA_DIRS`.
+pub fn font_dirs() -> Vec<PathBuf> {
+    dirs_from(
+        env::var_os("HOME"),
+        env::var_os("XDG_DATA_HOME"),
+        env::var_os("XDG_DATA_DIRS"),
+    )
+}
+
+fn dirs_from(
+    home: Option<OsString>,
+    data_home: Option<OsString>,
+    data_dirs: Option<OsString>,
+) -> Vec<PathBuf> {
+    let home = home.map(PathBuf::from);
+    let data_home = data_home
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+    let data_dirs = data_dirs
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+    data_home
+        .into_iter()
+        .map(|dir| dir.join("fonts"))
+        .chain(home.map(|home| home.join(".fonts")))
+        .chain(env::split_paths(&data_dirs).map(|dir| dir.join("fonts")))
+        .collect()
+}
+
+/// What a pattern asks for.
+#[derive(Debug, PartialEq)]
+struct Query {
+    /// Candidate families, lowercase, the preferred first.
+    families: Vec<String>,
+    bold: bool,
+    italic: bool,
+}
+
+impl Query {
+    /// Parses a fontconfig style pattern: a family, then styles separated
+    /// by colons, like `DejaVu Sans:bold:italic` or `serif:style=Italic`.
+    fn parse(pattern: &str) -> Self {
+        let mut parts = pattern.split(':');
+        let family = parts.next().unwrap_or("").trim().to_lowercase();
+        let styles = parts.collect::<Vec<_>>().join(":").to_lowercase();
+        let generic = match family.as_str() {
+            "sans-serif" | "sans" => SANS_SERIF,
+            "serif" => SERIF,
+            "monospace" | "mono" => MONOSPACE,
+            _ => &[],
+        };
+        let families = if generic.is_empty() {
+            vec![family]
+        } else {
+            generic.iter().map(|family| family.to_lowercase()).collect()
+        };
+        Query {
+            families,
+            bold: styles.contains("bold"),
+            italic: styles.contains("italic") || styles.contains("oblique"),
+        }
+    }
+
+    /// How far `face` is from what is asked for, lower is better, or `None`
+    /// if it isn't of a family asked for.
+    fn score(&self, face: &Face) -> Option<(usize, u8, u8, bool)> {
+        let names = |id| {
+            face.names()
+                .into_iter()
+                .filter(move |name| name.name_id == id)
+                .filter_map(|name| name.to_string())
+                .map(|name| name.to_lowercase())
+        };
+        let rank = |id| {
+            let mut names = names(id);
+            self.families.iter().position(|family| names.any(|name| name == *family))
+        };
+        // The typographic family groups widths and weights that have
+        // families of their own, like DejaVu Sans Condensed.
+        let (rank, exact) = match rank(name_id::FAMILY) {
+            Some(rank) => (rank, 0),
+            None => (rank(name_id::TYPOGRAPHIC_FAMILY)?, 1),
+        };
+        let style = u8::from(face.is_bold() != self.bold)
+            + u8::from((face.is_italic() || face.is_oblique()) != self.italic);
+        Some((rank, style, exact, face.width() != Width::Normal))
+    }
+}
+
+/// The installed font best matching the fontconfig style `pattern`, a
+/// family and styles like `DejaVu Sans:bold`. The generic families
+/// `sans-serif`, `serif` and `monospace` pick a common font of the kind.
+///
+/// Every font file in `font_dirs` is read, so keep the result rather than
+/// looking up the same font again.
+pub fn find_font(pattern: &str) -> Option<FontFile> {
+    find_font_in(&font_dirs(), pattern)
+}
+
+fn find_font_in(dirs: &[PathBuf], pattern: &str) -> Option<FontFile> {
+    let query = Query::parse(pattern);
+    let mut files = Vec::new();
+    for dir in dirs {
+        font_files(dir, 0, &mut files);
+    }
+    let mut best = None;
+    for path in files {
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        for index in 0..count {
+            let score = match Face::parse(&data, index).ok().and_then(|face| query.score(&face)) {
+                Some(score) => score,
+                None => continue,
+            };
+            if best.as_ref().is_none_or(|(best, _)| score < *best) {
+                let path = path.clone();
+                best = Some((score, FontFile { path, index }));
+            }
+        }
+    }
+    best.map(|(_, file)| file)
+}
+
+/// Collects the font files in `dir` and below, sorted for the same fonts
+/// to win ties every time.
+fn font_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) if depth <= MAX_DEPTH => entries,
+        _ => return,
+    };
+    let mut paths: Vec<_> = entries.filter_map(|entry| Some(entry.ok()?.path())).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            font_files(&path, depth + 1, files);
+        } else {
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            let extension = extension.map(str::to_lowercase);
+            if matches!(extension.as_deref(), Some("ttf" | "otf" | "ttc" | "otc")) {
+                files.push(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_are_a_family_and_styles() {
+        let query = Query::parse("DejaVu Sans:Bold:style=Oblique");
+        assert_eq!(query.families, ["dejavu sans"]);
+        assert!(query.bold && query.italic);
+        let query = Query::parse("monospace");
+        assert_eq!(query.families[0], "dejavu sans mono");
+        assert!(!query.bold && !query.italic);
+    }
+
+    #[test]
+    fn dirs_follow_the_xdg_base_directories() {
+        let dirs = dirs_from(Some("/home/me".into()), None, None);
+        let expected = [
+            "/home/me/.local/share/fonts",
+            "/home/me/.fonts",
+            "/usr/local/share/fonts",
+            "/usr/share/fonts",
+        ];
+        assert_eq!(dirs, expected.iter().map(PathBuf::from).collect::<Vec<_>>());
+        let dirs = dirs_from(None, Some("/data".into()), Some("/opt/share".into()));
+        assert_eq!(dirs, [PathBuf::from("/data/fonts"), PathBuf::from("/opt/share/fonts")]);
+    }
+
+    #[test]
+    fn styles_pick_among_the_fonts_of_a_family() {
+        let dirs = font_dirs();
+        let file = match find_font_in(&dirs, "DejaVu Sans:bold") {
+            Some(file) => file,
+            None => {
+                eprintln!("DejaVu Sans isn't installed, skipping");
+                return;
+            }
+        };
+        let name = file.path.file_name().unwrap().to_str().unwrap();
+        assert_eq!(name, "DejaVuSans-Bold.ttf");
+        let file = find_font_in(&dirs, "sans-serif").unwrap();
+        assert_eq!(file.path.file_name().unwrap(), "DejaVuSans.ttf");
+        assert_eq!(find_font_in(&dirs, "No Such Font"), None);
+    }
+}
@@ -0,0 +1,194 @@
+//! Fonts, and laying text out in lines to draw on a
+//! [`Canvas`](crate::Canvas).
+//!
+//! [`BitmapFont`] is built in. With the `truetype` feature, on by default,
+//! TrueType and OpenType fonts can be loaded with [`TrueTypeFont`] and looked
+//! up among the installed ones with [`find_font`].
+
+use std::rc::Rc;
+
+mod bitmap;
+#[cfg(feature = "truetype")]
+mod lookup;
+#[cfg(feature = "truetype")]
+mod truetype;
+
+pub use bitmap::BitmapFont;
+#[cfg(feature = "truetype")]
+pub use lookup::{find_font, font_dirs, FontFile};
+#[cfg(feature = "truetype")]
+pub use truetype::TrueTypeFont;
+
+/// A character rendered as coverage, from 0 to 255, row after row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glyph {
+    /// Width in pixels.
+    pub width: usize,
+    /// Height in pixels.
+    pub height: usize,
+    /// How far right of the pen the glyph starts.
+    pub left: i32,
+    /// How far above the baseline the glyph starts.
+    pub top: i32,
+    /// How much of each pixel the glyph covers.
+    pub coverage: Vec<u8>,
+}
+
+/// A font at a given size, in pixels.
+pub trait Font {
+    /// How far glyphs reach above the baseline.
+    fn ascent(&self) -> f32;
+
+    /// How far glyphs reach below the baseline.
+    fn descent(&self) -> f32;
+
+    /// The distance between the baselines of two lines.
+    fn line_height(&self) -> f32;
+
+    /// How far the pen moves on after drawing `c`.
+    fn advance(&self, c: char) -> f32;
+
+    /// How much closer `right` goes after `left` than their advance says,
+    /// as a negative distance.
+    fn kerning(&self, left: char, right: char) -> f32 {
+        let _ = (left, right);
+        0.0
+    }
+
+    /// The rendered glyph of `c`, or `None` if it draws nothing, like a
+    /// space.
+    fn glyph(&self, c: char) -> Option<Rc<Glyph>>;
+}
+
+/// The characters of a line with where the pen is when drawing them,
+/// relative to the start of the line.
+pub fn positions<'t>(font: &'t dyn Font, line: &'t str) -> impl Iterator<Item = (char, f32)> + 't {
+    let mut pen = 0.0;
+    let mut previous = None;
+    line.chars().map(move |c| {
+        if let Some(previous) = previous {
+            pen += font.kerning(previous, c);
+        }
+        let position = pen;
+        pen += font.advance(c);
+        previous = Some(c);
+        (c, position)
+    })
+}
+
+/// How wide `line` is when drawn in `font`.
+pub fn measure(font: &dyn Font, line: &str) -> f32 {
+    positions(font, line)
+        .last()
+        .map_or(0.0, |(c, position)| position + font.advance(c))
+}
+
+/// Breaks `text` into lines at most `max_width` wide, at its newlines and
+/// between words. Words too wide for a line of their own are broken
+/// between characters. The spaces lines are broken at are dropped.
+pub fn break_lines<'t>(font: &dyn Font, text: &'t str, max_width: f32) -> Vec<&'t str> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let first_line = lines.len();
+        let mut start = 0;
+        let mut end = 0;
+        for (word_start, word_end) in words(paragraph) {
+            if start < end && measure(font, &paragraph[start..word_end]) > max_width {
+                lines.push(&paragraph[start..end]);
+                start = word_start;
+            }
+            while measure(font, &paragraph[start..word_end]) > max_width {
+                let fits = longest_fitting(font, &paragraph[start..word_end], max_width);
+                lines.push(&paragraph[start..start + fits]);
+                start += fits;
+            }
+            end = word_end;
+        }
+        if start < end || lines.len() == first_line {
+            lines.push(&paragraph[start..end.max(start)]);
+        }
+    }
+    lines
+}
+
+/// Where the words of `paragraph` start and end.
+fn words(paragraph: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    paragraph
+        .split(' ')
+        .scan(0, |offset, word| {
+            let start = *offset;
+            *offset += word.len() + 1;
+            Some((start, start + word.len()))
+        })
+        .filter(|(start, end)| start < end)
+}
+
+/// The length in bytes of the longest start of `word` within `max_width`,
+/// but at least one character.
+fn longest_fitting(font: &dyn Font, word: &str, max_width: f32) -> usize {
+    let first = word.chars().next().map_or(0, char::len_utf8);
+    let fitting = word
+        .char_indices()
+        .skip(1)
+        .map(|(index, _)| index)
+        .chain(std::iter::once(word.len()))
+        .take_while(|&end| measure(font, &word[..end]) <= max_width)
+        .last();
+    fitting.unwrap_or(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_break_between_words() {
+        // 6 pixels per character, so 10 fit in 60.
+        let font = BitmapFont::new(1);
+        let lines = break_lines(&font, "the quick brown fox jumps", 60.0);
+        assert_eq!(lines, ["the quick", "brown fox", "jumps"]);
+        assert_eq!(break_lines(&font, "one\n\ntwo  three", 60.0), ["one", "", "two  three"]);
+    }
+
+    #[test]
+    fn long_words_break_anywhere() {
+        let font = BitmapFont::new(1);
+        let lines = break_lines(&font, "a wayland_compositor", 30.0);
+        assert_eq!(lines, ["a", "wayla", "nd_co", "mposi", "tor"]);
+        // Even narrower than a character, something goes on each line.
+        assert_eq!(break_lines(&font, "ab", 1.0), ["a", "b"]);
+    }
+
+    #[test]
+    fn text_is_measured_with_kerning() {
+        struct Kerned;
+        impl Font for Kerned {
+            fn ascent(&self) -> f32 {
+                10.0
+            }
+            fn descent(&self) -> f32 {
+                2.0
+            }
+            fn line_height(&self) -> f32 {
+                14.0
+            }
+            fn advance(&self, _: char) -> f32 {
+                10.0
+            }
+            fn kerning(&self, left: char, right: char) -> f32 {
+                if (left, right) == ('A', 'V') {
+                    -3.0
+                } else {
+                    0.0
+                }
+            }
+            fn glyph(&self, _: char) -> Option<Rc<Glyph>> {
+                None
+            }
+        }
+        assert_eq!(measure(&Kerned, "AVA"), 27.0);
+        let positions: Vec<_> = positions(&Kerned, "AVA").collect();
+        assert_eq!(positions, [('A', 0.0), ('V', 7.0), ('A', 17.0)]);
+        assert_eq!(measure(&Kerned, ""), 0.0);
+    }
+}
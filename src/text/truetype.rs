@@ -0,0 +1,316 @@
+//! TrueType and OpenType fonts, with outlines filled by the canvas.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+
+use ttf_parser::gpos::{PairAdjustment, PositioningSubtable};
+use ttf_parser::{Face, GlyphId, OutlineBuilder, Tag};
+
+use super::{find_font, Font, Glyph};
+use crate::canvas::{Canvas, Path, Transform};
+use crate::error::Error;
+
+/// The advance of a character, and its glyph if it draws anything.
+type Cached = (f32, Option<Rc<Glyph>>);
+
+/// Font data together with its face, parsed once.
+struct OwnedFace {
+    // Declared before the data it borrows, to be dropped first.
+    face: Face<'static>,
+    _data: Rc<[u8]>,
+}
+
+impl OwnedFace {
+    fn parse(data: Vec<u8>, index: u32) -> Result<Self, Error> {
+        let data: Rc<[u8]> = data.into();
+        // The bytes stay where they are, unchanged, for as long as `data`
+        // lives, and `face()` lends the face out for no longer than that.
+        let bytes: &'static [u8] = unsafe { &*(&*data as *const [u8]) };
+        let face = Face::parse(bytes, index).map_err(|error| Error::InvalidFont(error.to_string()))?;
+        Ok(OwnedFace { face, _data: data })
+    }
+
+    fn face(&self) -> &Face<'_> {
+        &self.face
+    }
+}
+
+/// A TrueType or OpenType font at a size in pixels.
+///
+/// Glyphs are rendered once and cached. Pairs are kerned by the `kern`
+/// feature of the `GPOS` table, or else the older `kern` table. Other
+/// OpenType features like ligatures aren't applied.
+#[derive(Clone)]
+pub struct TrueTypeFont {
+    face: Rc<OwnedFace>,
+    size: f32,
+    /// Pixels per font unit.
+    scale: f32,
+    glyphs: RefCell<HashMap<char, Cached>>,
+    kerning: RefCell<HashMap<(char, char), f32>>,
+}
+
+impl TrueTypeFont {
+    /// The font number `index` in `data`, which is 0 unless `data` is a
+    /// collection, with its em square `size` pixels high.
+    pub fn from_bytes(data: Vec<u8>, index: u32, size: f32) -> Result<Self, Error> {
+        let face = OwnedFace::parse(data, index)?;
+        let scale = size / f32::from(face.face().units_per_em());
+        Ok(TrueTypeFont {
+            face: Rc::new(face),
+            size,
+            scale,
+            glyphs: RefCell::new(HashMap::new()),
+            kerning: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Loads font number `index` of the file at `path`, see `from_bytes`.
+    pub fn open<P: AsRef<std::path::Path>>(path: P, index: u32, size: f32) -> Result<Self, Error> {
+        Self::from_bytes(fs::read(path).map_err(Error::FontFile)?, index, size)
+    }
+
+    /// Loads the installed font best matching `pattern`, see `find_font`.
+    pub fn find(pattern: &str, size: f32) -> Result<Self, Error> {
+        let file = find_font(pattern).ok_or_else(|| Error::FontNotFound(pattern.to_owned()))?;
+        Self::open(&file.path, file.index, size)
+    }
+
+    /// The same font at another size. The font data is shared.
+    pub fn with_size(&self, size: f32) -> Self {
+        TrueTypeFont {
+            face: self.face.clone(),
+            size,
+            scale: self.scale / self.size * size,
+            glyphs: RefCell::new(HashMap::new()),
+            kerning: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// The height of the em square, in pixels.
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    fn face(&self) -> &Face<'_> {
+        self.face.face()
+    }
+
+    /// The advance and glyph of `c`, rendered on first use.
+    fn cached(&self, c: char) -> Cached {
+        if let Some(cached) = self.glyphs.borrow().get(&c) {
+            return cached.clone();
+        }
+        let face = self.face();
+        // Missing characters are drawn as glyph 0, usually a box.
+        let id = face.glyph_index(c).unwrap_or(GlyphId(0));
+        let advance = f32::from(face.glyph_hor_advance(id).unwrap_or(0)) * self.scale;
+        let glyph = if c.is_whitespace() {
+            None
+        } else {
+            self.render(face, id).map(Rc::new)
+        };
+        self.glyphs.borrow_mut().insert(c, (advance, glyph.clone()));
+        (advance, glyph)
+    }
+
+    fn render(&self, face: &Face, id: GlyphId) -> Option<Glyph> {
+        let mut outline = Outline(Path::new());
+        let bounds = face.outline_glyph(id, &mut outline)?;
+        let scale = self.scale;
+        let left = (f32::from(bounds.x_min) * scale).floor();
+        let right = (f32::from(bounds.x_max) * scale).ceil();
+        let top = (f32::from(bounds.y_max) * scale).ceil();
+        let bottom = (f32::from(bounds.y_min) * scale).floor();
+        let (width, height) = ((right - left) as usize, (top - bottom) as usize);
+        if width == 0 || height == 0 {
+            return None;
+        }
+        // Fill the outline in white on transparent pixels, whose alpha is
+        // the coverage then.
+        let mut pixels = vec![0; width * height];
+        let mut canvas = Canvas::new(&mut pixels, width, height);
+        // Font units grow upwards.
+        canvas.set_transform(Transform::scale(scale, -scale).then(&Transform::translate(-left, top)));
+        canvas.fill(&outline.0, 0xFFFF_FFFF);
+        Some(Glyph {
+            width,
+            height,
+            left: left as i32,
+            top: top as i32,
+            coverage: pixels.iter().map(|pixel| (pixel >> 24) as u8).collect(),
+        })
+    }
+}
+
+impl Font for TrueTypeFont {
+    fn ascent(&self) -> f32 {
+        f32::from(self.face().ascender()) * self.scale
+    }
+
+    fn descent(&self) -> f32 {
+        -f32::from(self.face().descender()) * self.scale
+    }
+
+    fn line_height(&self) -> f32 {
+        let face = self.face();
+        let units = i32::from(face.ascender()) - i32::from(face.descender()) + i32::from(face.line_gap());
+        units as f32 * self.scale
+    }
+
+    fn advance(&self, c: char) -> f32 {
+        self.cached(c).0
+    }
+
+    fn kerning(&self, left: char, right: char) -> f32 {
+        if let Some(&kerning) = self.kerning.borrow().get(&(left, right)) {
+            return kerning;
+        }
+        let face = self.face();
+        let id = |c| face.glyph_index(c).unwrap_or(GlyphId(0));
+        let (left_id, right_id) = (id(left), id(right));
+        let units = gpos_kerning(face, left_id, right_id).unwrap_or_else(|| {
+            face.tables()
+                .kern
+                .into_iter()
+                .flat_map(|kern| kern.subtables)
+                .filter(|subtable| subtable.horizontal && !subtable.variable && !subtable.has_cross_stream)
+                .find_map(|subtable| subtable.glyphs_kerning(left_id, right_id))
+                .unwrap_or(0)
+        });
+        let kerning = f32::from(units) * self.scale;
+        self.kerning.borrow_mut().insert((left, right), kerning);
+        kerning
+    }
+
+    fn glyph(&self, c: char) -> Option<Rc<Glyph>> {
+        self.cached(c).1
+    }
+}
+
+/// The kerning of a pair by the pair adjustments of the `kern` feature in
+/// the `GPOS` table, in font units, or `None` if the font has no such
+/// feature. Each lookup adds the first adjustment it has for the pair,
+/// whatever the script and language.
+fn gpos_kerning(face: &Face, left: GlyphId, right: GlyphId) -> Option<i16> {
+    let gpos = face.tables().gpos?;
+    let kern = Tag::from_bytes(b"kern");
+    let mut lookups: Vec<u16> = gpos
+        .features
+        .into_iter()
+        .filter(|feature| feature.tag == kern)
+        .flat_map(|feature| feature.lookup_indices)
+        .collect();
+    if lookups.is_empty() {
+        return None;
+    }
+    lookups.sort_unstable();
+    lookups.dedup();
+    let adjustment = |subtable: PositioningSubtable| {
+        let pair = match subtable {
+            PositioningSubtable::Pair(pair) => pair,
+            _ => return None,
+        };
+        let index = pair.coverage().get(left)?;
+        let (first, _) = match pair {
+            PairAdjustment::Format1 { sets, .. } => sets.get(index)?.get(right)?,
+            PairAdjustment::Format2 { classes, matrix, .. } => {
+                matrix.get((classes.0.get(left), classes.1.get(right)))?
+            }
+        };
+        Some(first.x_advance)
+    };
+    let total = lookups
+        .into_iter()
+        .filter_map(|index| gpos.lookups.get(index))
+        .filter_map(|lookup| lookup.subtables.into_iter::<PositioningSubtable>().find_map(adjustment))
+        .fold(0i16, |total, units| total.saturating_add(units));
+    Some(total)
+}
+
+/// Collects the outline of a glyph into a path, in font units.
+struct Outline(Path);
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0.quad_to(x1, y1, x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.cubic_to(x1, y1, x2, y2, x, y);
+    }
+
+    fn close(&mut self) {
+        self.0.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A subset of DejaVu Sans, with the glyphs of " AHVlm" and the GPOS
+    /// kerning between them.
+    fn dejavu(size: f32) -> TrueTypeFont {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fonts/DejaVuSans-subset.ttf");
+        TrueTypeFont::open(path, 0, size).unwrap()
+    }
+
+    #[test]
+    fn glyphs_are_rendered_and_cached() {
+        let font = dejavu(32.0);
+        let glyph = font.glyph('H').unwrap();
+        // Two stems and a bar, standing on the baseline.
+        assert!(glyph.height >= 22 && glyph.height <= 25, "{}", glyph.height);
+        assert!(glyph.top >= 22 && glyph.top <= 25);
+        let middle = &glyph.coverage[glyph.height / 2 * glyph.width..][..glyph.width];
+        assert!(middle.iter().all(|&coverage| coverage > 0));
+        let full = glyph.coverage.iter().filter(|&&coverage| coverage == 255).count();
+        assert!(full > glyph.width * 4);
+        assert!(Rc::ptr_eq(&glyph, &font.glyph('H').unwrap()));
+        assert!(font.glyph(' ').is_none());
+        assert!(font.advance(' ') > 0.0);
+    }
+
+    #[test]
+    fn metrics_follow_the_size() {
+        let font = dejavu(20.0);
+        let larger = font.with_size(40.0);
+        assert_eq!(larger.advance('m'), font.advance('m') * 2.0);
+        assert_eq!(larger.line_height(), font.line_height() * 2.0);
+        assert!(font.ascent() > font.descent() && font.descent() > 0.0);
+        assert!(font.line_height() >= font.ascent() + font.descent());
+    }
+
+    #[test]
+    fn pairs_are_kerned() {
+        let font = dejavu(40.0);
+        assert!(font.kerning('A', 'V') < 0.0);
+        assert_eq!(font.kerning('l', 'l'), 0.0);
+        assert!(font.kerning('A', 'A') > 0.0);
+        // The subset keeps its kerning in GPOS only, like most OpenType fonts.
+        let face = font.face();
+        assert!(face.tables().kern.is_none());
+        let id = |c| face.glyph_index(c).unwrap();
+        assert_eq!(gpos_kerning(face, id('A'), id('V')), Some(-131));
+        assert_eq!(gpos_kerning(face, id('l'), id('l')), Some(0));
+    }
+
+    #[test]
+    fn garbage_is_not_a_font() {
+        assert!(matches!(
+            TrueTypeFont::from_bytes(b"not a font".to_vec(), 0, 12.0),
+            Err(Error::InvalidFont(_))
+        ));
+    }
+}
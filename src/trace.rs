@@ -0,0 +1,644 @@
+//! Logs of the events the compositor sends, with their arguments.
+//!
+//! Tracing is off unless `WAYLAND_TRACE` holds a filter, a comma separated
+//! list of `[target=]level` directives where the target is an interface
+//! like `wl_pointer`, or one of its events like `wl_pointer.motion`. The
+//! most specific directive applies, and a bare level applies to the rest.
+//! For example `WAYLAND_TRACE=wl_pointer.motion=off,wl_pointer=trace,info`.
+//!
+//! Logs go to stderr, or as JSON lines to the file in `WAYLAND_TRACE_FILE`.
+
+use std::cell::RefCell;
+use std::env;
+use std::fmt::{self, Write as _};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write as _};
+use std::str::FromStr;
+use std::time::Instant;
+
+use wayland_client::{
+    protocol::{
+        wl_buffer, wl_callback, wl_data_device, wl_data_offer, wl_data_source, wl_keyboard,
+        wl_output, wl_pointer, wl_seat, wl_shm, wl_surface::WlSurface, wl_touch,
+    },
+    AnonymousObject, Argument, Interface, Main, MessageGroup, Proxy, RawEvent,
+};
+use wayland_protocols::unstable::keyboard_shortcuts_inhibit::v1::client::zwp_keyboard_shortcuts_inhibitor_v1;
+use wayland_protocols::unstable::primary_selection::v1::client::{
+    zwp_primary_selection_device_v1, zwp_primary_selection_offer_v1,
+    zwp_primary_selection_offer_v1::ZwpPrimarySelectionOfferV1, zwp_primary_selection_source_v1,
+};
+use wayland_protocols::unstable::text_input::v3::client::zwp_text_input_v3;
+use wayland_protocols::xdg_shell::client::{xdg_surface, xdg_toplevel, xdg_wm_base};
+
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Off => "off",
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Level::Off),
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Which logs to keep, from the `WAYLAND_TRACE` syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Filter {
+    default: Level,
+    /// Interfaces or `interface.event` targets, with their levels.
+    targets: Vec<(String, Level)>,
+}
+
+impl Filter {
+    /// Parses directives, skipping invalid ones with a warning, the way a
+    /// typo in an environment variable shouldn't keep the client from running.
+    pub fn parse(directives: &str) -> Self {
+        let mut filter = Filter {
+            default: Level::Off,
+            targets: Vec::new(),
+        };
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            let (target, level) = match directive.rfind('=') {
+                Some(index) => (Some(&directive[..index]), &directive[index + 1..]),
+                None => (None, directive),
+            };
+            let level = match level.parse() {
+                Ok(level) => level,
+                Err(()) => {
+                    eprintln!("warning: unknown trace level in {:?}", directive);
+                    continue;
+                }
+            };
+            match target {
+                Some(target) => filter.targets.push((target.to_owned(), level)),
+                None => filter.default = level,
+            }
+        }
+        filter
+    }
+
+    pub fn level(&self, interface: &str, event: &str) -> Level {
+        let find = |target: &str| {
+            self.targets
+                .iter()
+                .rev()
+                .find(|(name, _)| name == target)
+                .map(|(_, level)| *level)
+        };
+        find(&format!("{}.{}", interface, event))
+            .or_else(|| find(interface))
+            .unwrap_or(self.default)
+    }
+
+    pub fn enabled(&self, level: Level, interface: &str, event: &str) -> bool {
+        level != Level::Off && level <= self.level(interface, event)
+    }
+}
+
+/// An argument of a traced message.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    /// A protocol enum or bitfield, by name.
+    Name(String),
+    Object { interface: &'static str, id: u32 },
+    Array(Vec<u8>),
+    Null,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Str(value) => write!(f, "{:?}", value),
+            Value::Name(name) => write!(f, "{}", name),
+            Value::Object { interface, id } => write!(f, "{}@{}", interface, id),
+            Value::Array(bytes) => write!(f, "array[{}]", bytes.len()),
+            Value::Null => write!(f, "nil"),
+        }
+    }
+}
+
+impl Value {
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Value::Int(value) => write!(out, "{}", value).unwrap(),
+            Value::Float(value) if value.is_finite() => write!(out, "{}", value).unwrap(),
+            Value::Float(_) | Value::Null => out.push_str("null"),
+            Value::Str(value) | Value::Name(value) => write_json_string(out, value),
+            Value::Object { interface, id } => {
+                out.push_str("{\"interface\":");
+                write_json_string(out, interface);
+                write!(out, ",\"id\":{}}}", id).unwrap();
+            }
+            Value::Array(bytes) => {
+                out.push('[');
+                for (index, byte) in bytes.iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    write!(out, "{}", byte).unwrap();
+                }
+                out.push(']');
+            }
+        }
+    }
+}
+
+fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+trait ToValue {
+    fn to_value(&self) -> Value;
+}
+
+impl ToValue for u32 {
+    fn to_value(&self) -> Value {
+        Value::Int(i64::from(*self))
+    }
+}
+
+/// Also file descriptors.
+impl ToValue for i32 {
+    fn to_value(&self) -> Value {
+        Value::Int(i64::from(*self))
+    }
+}
+
+impl ToValue for f64 {
+    fn to_value(&self) -> Value {
+        Value::Float(*self)
+    }
+}
+
+impl ToValue for String {
+    fn to_value(&self) -> Value {
+        Value::Str(self.clone())
+    }
+}
+
+impl ToValue for Vec<u8> {
+    fn to_value(&self) -> Value {
+        Value::Array(self.clone())
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(&self) -> Value {
+        self.as_ref().map_or(Value::Null, ToValue::to_value)
+    }
+}
+
+fn object<I: Interface + AsRef<Proxy<I>> + From<Proxy<I>>>(object: &I) -> Value {
+    Value::Object {
+        interface: I::NAME,
+        id: object.as_ref().id(),
+    }
+}
+
+macro_rules! object_values {
+    ($($object:ty),*) => {
+        $(
+            impl ToValue for $object {
+                fn to_value(&self) -> Value {
+                    object(self)
+                }
+            }
+
+            impl ToValue for Main<$object> {
+                fn to_value(&self) -> Value {
+                    object(&***self)
+                }
+            }
+        )*
+    };
+}
+
+object_values!(WlSurface, wl_data_offer::WlDataOffer, ZwpPrimarySelectionOfferV1);
+
+macro_rules! named_values {
+    ($($name:ty),*) => {
+        $(
+            impl ToValue for $name {
+                fn to_value(&self) -> Value {
+                    Value::Name(format!("{:?}", self))
+                }
+            }
+        )*
+    };
+}
+
+named_values!(
+    wl_seat::Capability,
+    wl_pointer::ButtonState,
+    wl_pointer::Axis,
+    wl_pointer::AxisSource,
+    wl_keyboard::KeymapFormat,
+    wl_keyboard::KeyState,
+    wl_output::Subpixel,
+    wl_output::Transform,
+    wl_output::Mode,
+    wl_shm::Format
+);
+
+/// Events that can list their arguments by name.
+pub(crate) trait TracedEvent: MessageGroup {
+    fn args(&self) -> Vec<(&'static str, Value)>;
+}
+
+macro_rules! traced_events {
+    ($($event:ty { $($variant:ident $({ $($arg:ident),* })?),* $(,)? })*) => {
+        $(
+            impl TracedEvent for $event {
+                fn args(&self) -> Vec<(&'static str, Value)> {
+                    #[allow(unreachable_patterns)]
+                    match self {
+                        $(Self::$variant $({ $($arg),* })? => {
+                            vec![$($((stringify!($arg), $arg.to_value())),*)?]
+                        })*
+                        _ => Vec::new(),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+traced_events! {
+    wl_seat::Event {
+        Capabilities { capabilities },
+        Name { name },
+    }
+    wl_pointer::Event {
+        Enter { serial, surface, surface_x, surface_y },
+        Leave { serial, surface },
+        Motion { time, surface_x, surface_y },
+        Button { serial, time, button, state },
+        Axis { time, axis, value },
+        Frame,
+        AxisSource { axis_source },
+        AxisStop { time, axis },
+        AxisDiscrete { axis, discrete },
+    }
+    wl_keyboard::Event {
+        Keymap { format, fd, size },
+        Enter { serial, surface, keys },
+        Leave { serial, surface },
+        Key { serial, time, key, state },
+        Modifiers { serial, mods_depressed, mods_latched, mods_locked, group },
+        RepeatInfo { rate, delay },
+    }
+    wl_touch::Event {
+        Down { serial, time, surface, id, x, y },
+        Up { serial, time, id },
+        Motion { time, id, x, y },
+        Frame,
+        Cancel,
+        Shape { id, major, minor },
+        Orientation { id, orientation },
+    }
+    wl_output::Event {
+        Geometry { x, y, physical_width, physical_height, subpixel, make, model, transform },
+        Mode { flags, width, height, refresh },
+        Done,
+        Scale { factor },
+    }
+    wl_shm::Event {
+        Format { format },
+    }
+    wl_buffer::Event {
+        Release,
+    }
+    wl_callback::Event {
+        Done { callback_data },
+    }
+    wl_data_device::Event {
+        DataOffer { id },
+        Enter { serial, surface, x, y, id },
+        Leave,
+        Motion { time, x, y },
+        Drop,
+        Selection { id },
+    }
+    wl_data_offer::Event {
+        Offer { mime_type },
+        SourceActions { source_actions },
+        Action { dnd_action },
+    }
+    wl_data_source::Event {
+        Target { mime_type },
+        Send { mime_type, fd },
+        Cancelled,
+        DndDropPerformed,
+        DndFinished,
+        Action { dnd_action },
+    }
+    xdg_wm_base::Event {
+        Ping { serial },
+    }
+    xdg_surface::Event {
+        Configure { serial },
+    }
+    xdg_toplevel::Event {
+        Configure { width, height, states },
+        Close,
+    }
+    zwp_text_input_v3::Event {
+        Enter { surface },
+        Leave { surface },
+        PreeditString { text, cursor_begin, cursor_end },
+        CommitString { text },
+        DeleteSurroundingText { before_length, after_length },
+        Done { serial },
+    }
+    zwp_primary_selection_device_v1::Event {
+        DataOffer { offer },
+        Selection { id },
+    }
+    zwp_primary_selection_offer_v1::Event {
+        Offer { mime_type },
+    }
+    zwp_primary_selection_source_v1::Event {
+        Send { mime_type, fd },
+        Cancelled,
+    }
+    zwp_keyboard_shortcuts_inhibitor_v1::Event {
+        Active,
+        Inactive,
+    }
+}
+
+/// A log line, about a message on `interface@id`.
+pub(crate) struct Record<'a> {
+    pub level: Level,
+    pub interface: &'a str,
+    pub id: u32,
+    pub message: &'a str,
+    pub args: &'a [(&'a str, Value)],
+}
+
+impl Record<'_> {
+    fn to_text(&self, seconds: f64) -> String {
+        let args: Vec<String> = self
+            .args
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect();
+        format!(
+            "[{:10.4}] {:5} {}@{}.{}({})",
+            seconds,
+            self.level.name().to_ascii_uppercase(),
+            self.interface,
+            self.id,
+            self.message,
+            args.join(", ")
+        )
+    }
+
+    fn to_json(&self, seconds: f64) -> String {
+        let mut out = String::new();
+        write!(out, "{{\"time\":{:.6},\"level\":", seconds).unwrap();
+        write_json_string(&mut out, self.level.name());
+        out.push_str(",\"interface\":");
+        write_json_string(&mut out, self.interface);
+        write!(out, ",\"id\":{},\"message\":", self.id).unwrap();
+        write_json_string(&mut out, self.message);
+        out.push_str(",\"args\":{");
+        for (index, (name, value)) in self.args.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            write_json_string(&mut out, name);
+            out.push(':');
+            value.write_json(&mut out);
+        }
+        out.push_str("}}");
+        out
+    }
+}
+
+enum Output {
+    Stderr,
+    JsonLines(File),
+}
+
+struct Tracer {
+    filter: Filter,
+    output: Output,
+    start: Instant,
+}
+
+thread_local! {
+    static TRACER: RefCell<Option<Tracer>> = const { RefCell::new(None) };
+}
+
+/// Turns tracing on as `WAYLAND_TRACE` and `WAYLAND_TRACE_FILE` say.
+pub fn init_from_env() -> Result<(), Error> {
+    let filter = match env::var("WAYLAND_TRACE") {
+        Ok(directives) => Filter::parse(&directives),
+        Err(_) => return Ok(()),
+    };
+    let output = match env::var_os("WAYLAND_TRACE_FILE") {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(Error::TraceFile)?;
+            Output::JsonLines(file)
+        }
+        None => Output::Stderr,
+    };
+    TRACER.with(|tracer| {
+        tracer.replace(Some(Tracer {
+            filter,
+            output,
+            start: Instant::now(),
+        }))
+    });
+    Ok(())
+}
+
+fn enabled(level: Level, interface: &str, message: &str) -> bool {
+    TRACER.with(|tracer| match &*tracer.borrow() {
+        Some(tracer) => tracer.filter.enabled(level, interface, message),
+        None => false,
+    })
+}
+
+/// Writes `record`, if the filter lets it through.
+pub(crate) fn log(record: &Record) {
+    TRACER.with(|tracer| {
+        let mut tracer = tracer.borrow_mut();
+        let tracer = match &mut *tracer {
+            Some(tracer) => tracer,
+            None => return,
+        };
+        if !tracer.filter.enabled(record.level, record.interface, record.message) {
+            return;
+        }
+        let seconds = tracer.start.elapsed().as_secs_f64();
+        let result = match &mut tracer.output {
+            Output::Stderr => writeln!(io::stderr(), "{}", record.to_text(seconds)),
+            Output::JsonLines(file) => writeln!(file, "{}", record.to_json(seconds)),
+        };
+        if let Err(error) = result {
+            eprintln!("warning: failed to write trace, stopping: {}", error);
+            tracer.filter.default = Level::Off;
+            tracer.filter.targets.clear();
+        }
+    });
+}
+
+/// Traces an event about to be handled for `proxy`.
+pub(crate) fn event<I>(proxy: &Main<I>, event: &I::Event)
+where
+    I: Interface + AsRef<Proxy<I>> + From<Proxy<I>>,
+    I::Event: TracedEvent,
+{
+    let name = I::Event::MESSAGES[event.opcode() as usize].name;
+    if !enabled(Level::Debug, I::NAME, name) {
+        return;
+    }
+    log(&Record {
+        level: Level::Debug,
+        interface: I::NAME,
+        id: proxy.as_ref().id(),
+        message: name,
+        args: &event.args(),
+    });
+}
+
+/// Traces an event for an object without a handler of its own.
+pub(crate) fn raw_event(event: &RawEvent, object: &Main<AnonymousObject>) {
+    const NAMES: [&str; 8] = ["0", "1", "2", "3", "4", "5", "6", "7"];
+    if !enabled(Level::Info, event.interface, event.name) {
+        return;
+    }
+    let raw_object = |proxy: &Proxy<AnonymousObject>| Value::Object {
+        interface: "wl_object",
+        id: proxy.id(),
+    };
+    let args: Vec<(&str, Value)> = event
+        .args
+        .iter()
+        .enumerate()
+        .map(|(index, arg)| {
+            let value = match arg {
+                Argument::Int(value) | Argument::Fd(value) => value.to_value(),
+                Argument::Uint(value) => value.to_value(),
+                Argument::Float(value) => Value::Float(f64::from(*value)),
+                Argument::Str(value) => value.to_value(),
+                Argument::Object(proxy) => proxy.as_ref().map_or(Value::Null, raw_object),
+                Argument::NewId(proxy) => proxy.as_ref().map_or(Value::Null, |p| raw_object(p.as_ref())),
+                Argument::Array(bytes) => bytes.to_value(),
+            };
+            (NAMES.get(index).copied().unwrap_or("…"), value)
+        })
+        .collect();
+    log(&Record {
+        level: Level::Info,
+        interface: event.interface,
+        id: object.as_ref().id(),
+        message: event.name,
+        args: &args,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_specific_directive_applies() {
+        let filter = Filter::parse("wl_pointer.motion=off, wl_pointer=trace,info");
+        assert_eq!(filter.level("wl_pointer", "motion"), Level::Off);
+        assert_eq!(filter.level("wl_pointer", "button"), Level::Trace);
+        assert_eq!(filter.level("wl_keyboard", "key"), Level::Info);
+        assert!(!filter.enabled(Level::Debug, "wl_keyboard", "key"));
+        assert!(filter.enabled(Level::Debug, "wl_pointer", "button"));
+    }
+
+    #[test]
+    fn invalid_directives_are_skipped() {
+        let filter = Filter::parse("wl_seat=loud,debug");
+        assert_eq!(filter.level("wl_seat", "name"), Level::Debug);
+        assert_eq!(Filter::parse("").level("wl_seat", "name"), Level::Off);
+    }
+
+    #[test]
+    fn json_lines_escape_strings() {
+        let args = [
+            ("name", Value::Str("seat \"0\"\n".to_owned())),
+            ("surface", Value::Object { interface: "wl_surface", id: 3 }),
+            ("id", Value::Null),
+        ];
+        let record = Record {
+            level: Level::Debug,
+            interface: "wl_seat",
+            id: 7,
+            message: "name",
+            args: &args,
+        };
+        assert_eq!(
+            record.to_json(1.5),
+            "{\"time\":1.500000,\"level\":\"debug\",\"interface\":\"wl_seat\",\"id\":7,\
+             \"message\":\"name\",\"args\":{\"name\":\"seat \\\"0\\\"\\n\",\
+             \"surface\":{\"interface\":\"wl_surface\",\"id\":3},\"id\":null}}"
+        );
+        assert_eq!(
+            record.to_text(1.5),
+            "[    1.5000] DEBUG wl_seat@7.name(name: \"seat \\\"0\\\"\\n\", surface: wl_surface@3, id: nil)"
+        );
+    }
+}
@@ -0,0 +1,888 @@
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+
+use wayland_client::{
+    protocol::{wl_compositor::WlCompositor, wl_shm, wl_shm::WlShm, wl_surface, wl_surface::WlSurface, wl_callback},
+    Main,
+};
+
+use crate::canvas::Rect;
+use crate::capture::Recorder;
+use crate::renderer::{Frame, Renderer};
+use crate::buffer::Buffer;
+use crate::connection::Connection;
+use crate::damage::{Damage, DamageHistory};
+use crate::error::Error;
+use crate::image::Image;
+use crate::pixel_format::PixelFormat;
+use wayland_protocols::xdg_shell::client::{xdg_surface, xdg_wm_base};
+use wayland_protocols::xdg_shell::client::xdg_toplevel::Event as ToplevelEvent;
+use crate::trace;
+
+const WIDTH: usize = 600;
+const HEIGHT: usize = 400;
+
+struct State {
+    surface: Main<WlSurface>,
+    compositor: Main<WlCompositor>,
+    /// In surface units.
+    width: usize,
+    height: usize,
+    scale: i32,
+    /// The scale the surface was last committed with.
+    committed_scale: i32,
+    format: PixelFormat,
+    /// The formats the compositor accepts.
+    shm_formats: Rc<RefCell<Vec<wl_shm::Format>>>,
+    /// Where formats the canvas can't draw in directly are drawn first.
+    scratch: Vec<u32>,
+    /// Buffers to draw into when the compositor released them.
+    buffers: Vec<Slot>,
+    history: DamageHistory,
+    /// The opaque region to set with the next commit, in surface units.
+    opaque_region: Option<Vec<Rect>>,
+    asked_to_close: bool,
+    has_drawn: bool,
+    /// Whether to draw a frame for every frame callback, or only once
+    /// `needs_redraw`.
+    continuous: bool,
+    needs_redraw: bool,
+    /// Whether a frame callback was requested and not called yet.
+    frame_pending: bool,
+    /// The state itself, for frame callbacks to get back to.
+    this: Weak<RefCell<State>>,
+    shm: Main<WlShm>,
+    renderer: Box<dyn Renderer>,
+    /// Where submitted frames are recorded, if anywhere.
+    recorder: Option<Recorder>,
+    /// The first error in an event callback, for the event loop to return.
+    error: Option<Error>,
+}
+
+
+impl State {
+
+    fn new(
+        surface: &Main<WlSurface>,
+        compositor: &Main<WlCompositor>,
+        shm: &Main<WlShm>,
+        shm_formats: Rc<RefCell<Vec<wl_shm::Format>>>,
+        renderer: Box<dyn Renderer>,
+    ) -> Self {
+        Self {
+            surface: surface.clone(),
+            compositor: compositor.clone(),
+            width: WIDTH,
+            height: HEIGHT,
+            scale: 1,
+            committed_scale: 1,
+            format: PixelFormat::Xrgb8888,
+            shm_formats,
+            scratch: Vec::new(),
+            buffers: Vec::new(),
+            history: DamageHistory::new(),
+            opaque_region: None,
+            asked_to_close: false,
+            has_drawn: false,
+            continuous: false,
+            needs_redraw: false,
+            frame_pending: false,
+            this: Weak::new(),
+            shm: shm.clone(),
+            renderer,
+            recorder: None,
+            error: None,
+        }
+    }
+
+    fn set_geometry(&mut self, width: usize, height: usize) {
+        if width == 0 || height == 0 {
+            self.width = WIDTH;
+            self.height = HEIGHT;
+        } else {
+            self.width = width;
+            self.height = height;
+        };
+    }
+
+    fn draw(&mut self, time: Option<u32>) {
+        self.needs_redraw = false;
+        if self.continuous {
+            // Sent along with the commit below.
+            self.request_frame();
+        }
+        let scale = self.scale as usize;
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut damage = Damage::new(width, height);
+        let fits = |buffer: &Buffer| {
+            (buffer.width(), buffer.height(), buffer.format()) == (width, height, self.format)
+        };
+        if !self.buffers.iter().all(|slot| fits(&slot.buffer)) {
+            // What older buffers hold is of no use at another size.
+            self.buffers.clear();
+            self.history.clear();
+        }
+        if self.buffers.is_empty() {
+            damage.add_all();
+        }
+        self.renderer.damage(time, &mut damage);
+        if let Some(rects) = self.opaque_region.take() {
+            self.set_opaque_region(&rects);
+        }
+        if damage.is_empty() {
+            // Commit the rest, like the frame callback.
+            self.surface.commit();
+            // The last frame shows for longer.
+            self.record_frame();
+            return;
+        }
+
+        let index = match self.buffers.iter().position(|slot| !slot.buffer.is_busy()) {
+            Some(index) => index,
+            None => match Buffer::with_format(&self.shm, width, height, self.format) {
+                Ok(buffer) => {
+                    self.buffers.push(Slot { buffer, age: 0 });
+                    self.buffers.len() - 1
+                }
+                Err(error) => {
+                    self.error.get_or_insert(error);
+                    return;
+                }
+            },
+        };
+        let repaint = self.history.repaint(self.buffers[index].age, &damage);
+        let frame = Frame {
+            time,
+            scale: self.scale,
+            damage: repaint.rects().to_vec(),
+        };
+        let renderer = &mut self.renderer;
+        let buffer = &mut self.buffers[index].buffer;
+        buffer.draw(&mut self.scratch, repaint.rects(), |canvas| {
+            renderer.render(canvas, &frame)
+        });
+        for slot in &mut self.buffers {
+            if slot.age > 0 {
+                slot.age += 1;
+            }
+        }
+        self.buffers[index].age = 1;
+
+        if self.scale != self.committed_scale {
+            self.surface.set_buffer_scale(self.scale);
+            self.committed_scale = self.scale;
+        }
+        self.buffers[index].buffer.attach_to(&self.surface);
+        damage_rects(&self.surface, damage.rects(), self.scale);
+        self.surface.commit();
+        self.history.push(damage);
+        self.record_frame();
+    }
+
+    /// Whether the buffer last sent to the compositor, if any, has another
+    /// size than the window now.
+    fn resized(&self) -> bool {
+        let scale = self.scale as usize;
+        let size = (self.width * scale, self.height * scale);
+        self.submitted().is_none_or(|buffer| (buffer.width(), buffer.height()) != size)
+    }
+
+    /// The buffer last sent to the compositor, if one was sent.
+    fn submitted(&self) -> Option<&Buffer> {
+        self.buffers.iter().find(|slot| slot.age == 1).map(|slot| &slot.buffer)
+    }
+
+    fn record_frame(&mut self) {
+        let mut recorder = match self.recorder.take() {
+            Some(recorder) => recorder,
+            None => return,
+        };
+        let result = match self.submitted() {
+            Some(buffer) => recorder.frame(|| read_back(buffer)),
+            None => Ok(()),
+        };
+        match result {
+            Ok(()) => self.recorder = Some(recorder),
+            // A recording missing frames is of little use.
+            Err(error) => {
+                self.error.get_or_insert(error);
+            }
+        }
+    }
+
+    fn set_opaque_region(&self, rects: &[Rect]) {
+        if rects.is_empty() {
+            self.surface.set_opaque_region(None);
+            return;
+        }
+        let region = self.compositor.create_region();
+        for rect in rects {
+            region.add(rect.x as i32, rect.y as i32, rect.width as i32, rect.height as i32);
+        }
+        self.surface.set_opaque_region(Some(&region));
+        region.destroy();
+    }
+
+    fn handle_toplevel(&mut self, event: ToplevelEvent) {
+        use ToplevelEvent::*;
+        match event {
+            Configure { width, height, .. } => {
+                self.set_geometry(width as usize, height as usize);
+            }
+            Close => {
+                self.asked_to_close = true;
+            }
+            _ => ()
+        }
+    }
+
+    fn handle_xdg_surface(
+        &mut self,
+        xdg_surface: xdg_surface::XdgSurface,
+        event: xdg_surface::Event
+    ) {
+        match event {
+            xdg_surface::Event::Configure { serial } => {
+                xdg_surface.ack_configure(serial);
+                // The commit after acking a new size must bring a buffer
+                // of that size, so it can't wait for a frame callback.
+                if !self.has_drawn || self.resized() {
+                    self.draw(None);
+                    self.has_drawn = true;
+                } else {
+                    self.redraw();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_frame_callback(&mut self, time: u32) {
+        self.frame_pending = false;
+        if self.continuous || self.needs_redraw {
+            self.draw(Some(time));
+        }
+    }
+
+    /// Draws a frame once the compositor is ready for one. Until the first
+    /// configure, the first frame is drawn then.
+    fn redraw(&mut self) {
+        self.needs_redraw = true;
+        if self.has_drawn && !self.frame_pending {
+            self.request_frame();
+            self.surface.commit();
+        }
+    }
+
+    /// Asks for a frame callback, which the next commit sends along.
+    fn request_frame(&mut self) {
+        if self.frame_pending {
+            return;
+        }
+        self.frame_pending = true;
+        let state = self.this.clone();
+        self.surface.frame().quick_assign(move |callback, event, _| {
+            trace::event(&callback, &event);
+            match event {
+                wl_callback::Event::Done { callback_data: time } => {
+                    if let Some(state) = state.upgrade() {
+                        state.borrow_mut().handle_frame_callback(time);
+                    }
+                }
+                _ => (),
+            }
+        });
+    }
+}
+
+
+/// What `buffer` shows, converted from its pixel format.
+fn read_back(buffer: &Buffer) -> Image {
+    Image::new(buffer.width(), buffer.height(), buffer.read_argb())
+}
+
+/// A buffer of a window, and how many frames ago it was drawn.
+struct Slot {
+    buffer: Buffer,
+    /// 0 until it is drawn the first time.
+    age: usize,
+}
+
+/// Damages the whole surface, in buffer coordinates when the compositor
+/// supports it. Before `wl_compositor` 4 only surface coordinates could be
+/// used, which is the same while we don't scale buffers.
+pub(crate) fn damage_all(surface: &WlSurface) {
+    if surface.as_ref().version() >= wl_surface::REQ_DAMAGE_BUFFER_SINCE {
+        surface.damage_buffer(0, 0, i32::MAX, i32::MAX);
+    } else {
+        surface.damage(0, 0, i32::MAX, i32::MAX);
+    }
+}
+
+/// Damages `rects` of a buffer drawn at `scale`. Before `wl_compositor` 4
+/// they are rounded out to surface units.
+fn damage_rects(surface: &WlSurface, rects: &[Rect], scale: i32) {
+    let buffer_damage = surface.as_ref().version() >= wl_surface::REQ_DAMAGE_BUFFER_SINCE;
+    for rect in rects {
+        let (x, y) = (rect.x as i32, rect.y as i32);
+        let (right, bottom) = (x + rect.width as i32, y + rect.height as i32);
+        if buffer_damage {
+            surface.damage_buffer(x, y, right - x, bottom - y);
+        } else {
+            let (x, y) = (x / scale, y / scale);
+            let (right, bottom) = ((right + scale - 1) / scale, (bottom + scale - 1) / scale);
+            surface.damage(x, y, right - x, bottom - y);
+        }
+    }
+}
+
+/// A toplevel window, drawn by a `Renderer` when asked to redraw, or for
+/// every frame the compositor asks for when continuous.
+///
+/// The window is 600 by 400 pixels until the compositor picks a size.
+pub struct Window {
+    state: Rc<RefCell<State>>,
+}
+
+impl Window {
+    /// Opens a window titled `title`. It is shown once the compositor has
+    /// configured it and `renderer` has drawn the first frame.
+    pub fn new<R: Renderer + 'static>(connection: &Connection, title: &str, renderer: R) -> Window {
+        let state = setup(
+            connection.compositor(),
+            connection.xdg_wm_base(),
+            connection.shm(),
+            connection.shm_formats_handle(),
+            title,
+            Box::new(renderer),
+        );
+        Window { state }
+    }
+
+    /// The surface of the window, to set up input or drag and drop for.
+    pub fn surface(&self) -> WlSurface {
+        self.state.borrow().surface.detach()
+    }
+
+    /// The current size of the window, in surface units.
+    pub fn size(&self) -> (usize, usize) {
+        let state = self.state.borrow();
+        (state.width, state.height)
+    }
+
+    /// Renders the window with `scale` buffer pixels per surface unit from
+    /// the next frame on, for example the scale of the output it is on.
+    ///
+    /// Needs `wl_compositor` 3, the scale stays 1 before.
+    pub fn set_scale(&self, scale: i32) {
+        let mut state = self.state.borrow_mut();
+        if scale >= 1
+            && scale != state.scale
+            && state.surface.as_ref().version() >= wl_surface::REQ_SET_BUFFER_SCALE_SINCE
+        {
+            state.scale = scale;
+            state.redraw();
+        }
+    }
+
+    /// How many buffer pixels make one surface unit.
+    pub fn scale(&self) -> i32 {
+        self.state.borrow().scale
+    }
+
+    /// Renders into ARGB8888 buffers from the next frame on, whose alpha
+    /// lets what is behind the window show through. The canvas starts out
+    /// fully transparent then.
+    ///
+    /// Windows are opaque by default, which is cheaper for the compositor.
+    pub fn set_transparent(&self, transparent: bool) {
+        let mut state = self.state.borrow_mut();
+        state.format = if transparent {
+            PixelFormat::Argb8888
+        } else {
+            PixelFormat::Xrgb8888
+        };
+        state.redraw();
+    }
+
+    /// Renders into buffers laid out as `format` from the next frame on,
+    /// for example `Rgb565` to halve the memory the compositor reads.
+    /// Renderers draw the same either way.
+    ///
+    /// Returns false and keeps the current format if the compositor
+    /// doesn't accept `format`.
+    pub fn set_pixel_format(&self, format: PixelFormat) -> bool {
+        let mut state = self.state.borrow_mut();
+        let supported = state.shm_formats.borrow().contains(&format.to_shm());
+        if supported {
+            state.format = format;
+            state.redraw();
+        }
+        supported
+    }
+
+    /// The layout of the buffers the window renders into.
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.state.borrow().format
+    }
+
+    /// Tells the compositor that the parts of a transparent window within
+    /// `rects`, in surface units, are opaque, so it needn't draw what is
+    /// behind them. Applies from the next frame on. Without rectangles,
+    /// nothing is known to be opaque.
+    pub fn set_opaque_region(&self, rects: &[Rect]) {
+        let mut state = self.state.borrow_mut();
+        state.opaque_region = Some(rects.to_vec());
+        state.redraw();
+    }
+
+    /// Renders a new frame once the compositor is ready for one. The
+    /// renderer still tells what changed in it.
+    ///
+    /// Resizing, rescaling or changing the format redraws on its own.
+    pub fn request_redraw(&self) {
+        self.state.borrow_mut().redraw();
+    }
+
+    /// Renders a frame for every frame the compositor asks for, for
+    /// animations, rather than only when a redraw is requested.
+    ///
+    /// Windows aren't continuous by default, to not keep rendering while
+    /// nothing changes.
+    pub fn set_continuous(&self, continuous: bool) {
+        let mut state = self.state.borrow_mut();
+        state.continuous = continuous;
+        if continuous {
+            state.redraw();
+        }
+    }
+
+    /// Saves the frame last sent to the compositor as a PNG file at
+    /// `path`, read back from its buffer. Returns false if no frame was
+    /// sent yet. Needs the `png` feature.
+    #[cfg(feature = "png")]
+    pub fn screenshot<P: AsRef<std::path::Path>>(&self, path: P) -> Result<bool, Error> {
+        match self.state.borrow().submitted() {
+            Some(buffer) => read_back(buffer).save_png(path).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Records the frames the window sends to the compositor from now on,
+    /// ending the recording in progress if there is one. A recording that
+    /// fails to write a frame stops, and `take_error` returns why.
+    pub fn record(&self, recorder: Recorder) {
+        let previous = self.state.borrow_mut().recorder.replace(recorder);
+        if let Some(Err(error)) = previous.map(Recorder::finish) {
+            self.state.borrow_mut().error.get_or_insert(error);
+        }
+    }
+
+    /// Ends the recording in progress, if there is one, and writes out
+    /// what is still buffered.
+    pub fn stop_recording(&self) -> Result<(), Error> {
+        let recorder = self.state.borrow_mut().recorder.take();
+        recorder.map_or(Ok(()), Recorder::finish)
+    }
+
+    /// Whether the compositor asked for the window to be closed.
+    pub fn is_closed(&self) -> bool {
+        self.state.borrow().asked_to_close
+    }
+
+    /// The first error drawing the window hit since the last call, if any.
+    pub fn take_error(&self) -> Option<Error> {
+        self.state.borrow_mut().error.take()
+    }
+}
+
+fn setup(
+    compositor: &Main<WlCompositor>,
+    xdg_wm_base: &Main<xdg_wm_base::XdgWmBase>,
+    shm: &Main<WlShm>,
+    shm_formats: Rc<RefCell<Vec<wl_shm::Format>>>,
+    title: &str,
+    renderer: Box<dyn Renderer>,
+) -> Rc<RefCell<State>> {
+    let surface = compositor.create_surface();
+    let xdg_surface = xdg_wm_base.get_xdg_surface(&surface);
+    let toplevel = xdg_surface.get_toplevel();
+    toplevel.set_title(title.to_owned());
+    surface.commit();
+
+    let state = Rc::new(RefCell::new(State::new(&surface, compositor, shm, shm_formats, renderer)));
+    
+    toplevel.quick_assign({
+        let state = state.clone();
+        move |toplevel, event, _| {
+            trace::event(&toplevel, &event);
+            state.borrow_mut().handle_toplevel(event);
+        }
+    });
+
+    xdg_surface.quick_assign({
+        let state = state.clone();
+        move |xdg_surface, event, _| {
+            trace::event(&xdg_surface, &event);
+            state.borrow_mut()
+                .handle_xdg_surface(xdg_surface.detach(), event);
+        }
+    });
+
+    state.borrow_mut().this = Rc::downgrade(&state);
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Canvas, Path};
+    use crate::renderer::Checkerboard;
+    use crate::test_compositor::{TestClient, TestCompositor};
+
+    fn window() -> (TestCompositor, TestClient, Window) {
+        let (compositor, mut client) = TestCompositor::start();
+        let window = Window::new(&client.connection, "Example client", Checkerboard::new());
+        client.roundtrip();
+        (compositor, client, window)
+    }
+
+    #[test]
+    fn draws_at_the_configured_size_once_configured() {
+        let (compositor, mut client, _state) = window();
+        assert_eq!(compositor.log().title.as_deref(), Some("Example client"));
+        assert!(compositor.last_buffer().is_none());
+
+        let serial = compositor.configure(320, 200);
+        client.roundtrip();
+        let log = compositor.log();
+        assert_eq!(log.acked_configures, vec![serial]);
+        let buffer = compositor.last_buffer().expect("nothing was drawn");
+        assert_eq!((buffer.width, buffer.height, buffer.stride), (320, 200, 320 * 4));
+        assert_eq!(log.commits.last().unwrap().buffer_damage.len(), 1);
+    }
+
+    #[test]
+    fn falls_back_to_the_default_size() {
+        let (compositor, mut client, window) = window();
+        compositor.configure(0, 0);
+        client.roundtrip();
+        let buffer = compositor.last_buffer().expect("nothing was drawn");
+        assert_eq!((buffer.width, buffer.height), (WIDTH as i32, HEIGHT as i32));
+        assert_eq!(window.size(), (WIDTH, HEIGHT));
+    }
+
+    #[test]
+    fn continuous_windows_redraw_on_frame_callbacks() {
+        let (compositor, mut client, window) = window();
+        window.set_continuous(true);
+        compositor.configure(64, 64);
+        client.roundtrip();
+        // The first frame only starts the clock.
+        compositor.frame_done(1000);
+        client.roundtrip();
+        let first = compositor.last_buffer().unwrap();
+        compositor.frame_done(1100);
+        client.roundtrip();
+        let second = compositor.last_buffer().unwrap();
+        assert_eq!(compositor.log().count("wl_surface.commit"), 4);
+        assert_ne!(first.data, second.data, "the checkerboard should have moved");
+    }
+
+    #[test]
+    fn renderers_get_the_frame_and_its_size() {
+        let (compositor, mut client) = TestCompositor::start();
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let window = Window::new(&client.connection, "Example client", {
+            let frames = frames.clone();
+            move |canvas: &mut Canvas, frame: &Frame| {
+                frames
+                    .borrow_mut()
+                    .push((canvas.width(), canvas.height(), frame.clone()));
+            }
+        });
+        client.roundtrip();
+        compositor.configure(30, 20);
+        client.roundtrip();
+        window.set_scale(2);
+        client.roundtrip();
+        compositor.frame_done(500);
+        client.roundtrip();
+
+        let frames = frames.borrow();
+        assert_eq!(frames.len(), 2);
+        let (width, height, first) = &frames[0];
+        assert_eq!((*width, *height, first.time, first.scale), (30, 20, None, 1));
+        assert_eq!(first.damage, vec![Rect::new(0, 0, 30, 20)]);
+        let (width, height, second) = &frames[1];
+        assert_eq!((*width, *height, second.time, second.scale), (60, 40, Some(500), 2));
+        assert_eq!(second.damage, vec![Rect::new(0, 0, 60, 40)]);
+        assert_eq!(compositor.log().count("wl_surface.set_buffer_scale"), 1);
+        assert_eq!(window.size(), (30, 20));
+    }
+
+    #[test]
+    fn transparent_windows_use_argb_and_an_opaque_region() {
+        let (compositor, mut client, window) = window();
+        compositor.configure(40, 30);
+        client.roundtrip();
+        assert_eq!(compositor.last_buffer().unwrap().format, wl_shm::Format::Xrgb8888.to_raw());
+
+        window.set_transparent(true);
+        window.set_opaque_region(&[Rect::new(0, 10, 40, 20)]);
+        client.roundtrip();
+        compositor.frame_done(16);
+        client.roundtrip();
+        let log = compositor.log();
+        let commit = log.commits.last().unwrap();
+        let buffer = commit.buffer.as_ref().unwrap();
+        assert_eq!(buffer.format, wl_shm::Format::Argb8888.to_raw());
+        let opaque = commit.opaque_region.as_ref().expect("no opaque region was set");
+        assert_eq!(opaque.len(), 1);
+        assert_eq!((opaque[0].y, opaque[0].width, opaque[0].height), (10, 40, 20));
+        assert_eq!(log.count("wl_region.destroy"), 1);
+
+        window.set_opaque_region(&[]);
+        client.roundtrip();
+        compositor.frame_done(32);
+        client.roundtrip();
+        let log = compositor.log();
+        assert_eq!(log.commits.last().unwrap().opaque_region, Some(Vec::new()));
+        assert_eq!(log.count("wl_compositor.create_region"), 1);
+    }
+
+    #[test]
+    fn draws_in_other_formats_the_compositor_supports() {
+        let (compositor, mut client) = TestCompositor::start();
+        // Red, with the left half of the top row blue.
+        let renderer = |canvas: &mut Canvas, _: &Frame| {
+            canvas.clear(0xFFFF_0000);
+            canvas.fill(&Path::rect(0.0, 0.0, 2.0, 1.0), 0xFF00_00FF);
+        };
+        let window = Window::new(&client.connection, "Example client", renderer);
+        assert!(!window.set_pixel_format(PixelFormat::Bgr888));
+        assert!(window.set_pixel_format(PixelFormat::Rgb565));
+        assert_eq!(window.pixel_format(), PixelFormat::Rgb565);
+        client.roundtrip();
+        compositor.configure(4, 3);
+        client.roundtrip();
+        let buffer = compositor.last_buffer().unwrap();
+        assert_eq!(buffer.format, wl_shm::Format::Rgb565.to_raw());
+        assert_eq!(buffer.stride, 4 * 2);
+        assert_eq!(buffer.data.len(), 4 * 2 * 3);
+        assert_eq!((buffer.argb(1, 0), buffer.argb(2, 0)), (0xFF00_00FF, 0xFFFF_0000));
+
+        window.set_pixel_format(PixelFormat::Xrgb2101010);
+        client.roundtrip();
+        compositor.frame_done(16);
+        client.roundtrip();
+        let buffer = compositor.last_buffer().unwrap();
+        assert_eq!(buffer.format, wl_shm::Format::Xrgb2101010.to_raw());
+        assert_eq!((buffer.argb(0, 0), buffer.argb(3, 2)), (0xFF00_00FF, 0xFFFF_0000));
+    }
+
+    /// A path in the temporary directory for this test process.
+    #[cfg(feature = "png")]
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("wayland-book-rust-{}-{}", std::process::id(), name))
+    }
+
+    /// Whether `image` holds the same pixels as what the compositor got.
+    #[cfg(feature = "png")]
+    fn shows(image: &Image, buffer: &crate::test_compositor::ShmBuffer) -> bool {
+        (0..image.height()).all(|y| {
+            (0..image.width()).all(|x| image.pixel(x, y) == buffer.argb(x as i32, y as i32))
+        })
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn screenshots_are_read_back_in_the_window_format() {
+        let (compositor, mut client, window) = window();
+        let path = temp_path("screenshot.png");
+        assert!(!window.screenshot(&path).unwrap(), "nothing was drawn yet");
+        assert!(window.set_pixel_format(PixelFormat::Rgb565));
+        compositor.configure(16, 8);
+        client.roundtrip();
+        assert!(window.screenshot(&path).unwrap());
+        let image = Image::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((image.width(), image.height()), (16, 8));
+        assert!(shows(&image, &compositor.last_buffer().unwrap()));
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn recordings_keep_every_few_frames() {
+        let (compositor, mut client, window) = window();
+        let dir = temp_path("recording");
+        window.set_continuous(true);
+        window.record(Recorder::png_sequence(&dir).every(2));
+        compositor.configure(16, 16);
+        client.roundtrip();
+        let first = compositor.last_buffer().unwrap();
+        let mut shown = Vec::new();
+        for time in [1000, 1100, 1200, 1300] {
+            compositor.frame_done(time);
+            client.roundtrip();
+            shown.push(compositor.last_buffer().unwrap());
+        }
+        window.stop_recording().unwrap();
+        assert!(window.take_error().is_none());
+        // The first frame, then those of 1100 and 1300.
+        let mut files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        files.sort();
+        let images: Vec<_> = files.iter().map(|file| Image::open(file).unwrap()).collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(images.len(), 3);
+        assert!(shows(&images[0], &first));
+        assert!(shows(&images[1], &shown[1]));
+        assert!(shows(&images[2], &shown[3]));
+        assert_ne!(images[1], images[2], "the checkerboard should have moved");
+    }
+
+    /// Rectangles added to `shapes` over black. Only what is damaged is
+    /// repainted, and `repainted` records that.
+    #[derive(Default)]
+    struct Shapes {
+        shapes: Rc<RefCell<Vec<(Rect, u32)>>>,
+        seen: usize,
+        repainted: Rc<RefCell<Vec<Vec<Rect>>>>,
+    }
+
+    impl Shapes {
+        fn color(shapes: &[(Rect, u32)], x: usize, y: usize) -> u32 {
+            let pixel = Rect::new(x, y, 1, 1);
+            let on_top = shapes.iter().rev().find(|(rect, _)| rect.contains(&pixel));
+            on_top.map_or(0xFF00_0000, |&(_, color)| color)
+        }
+    }
+
+    impl Renderer for Shapes {
+        fn damage(&mut self, _: Option<u32>, damage: &mut Damage) {
+            let shapes = self.shapes.borrow();
+            for (rect, _) in &shapes[self.seen..] {
+                damage.add(*rect);
+            }
+            self.seen = shapes.len();
+        }
+
+        fn render(&mut self, canvas: &mut Canvas, frame: &Frame) {
+            self.repainted.borrow_mut().push(frame.damage.clone());
+            let shapes = self.shapes.borrow();
+            let width = canvas.width();
+            for rect in &frame.damage {
+                for y in rect.y..rect.y + rect.height {
+                    for x in rect.x..rect.x + rect.width {
+                        canvas.pixels_mut()[y * width + x] = Self::color(&shapes, x, y);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_damage_is_repainted_and_older_buffers_catch_up() {
+        let (compositor, mut client) = TestCompositor::start();
+        let renderer = Shapes::default();
+        let (shapes, repainted) = (renderer.shapes.clone(), renderer.repainted.clone());
+        let window = Window::new(&client.connection, "Example client", renderer);
+        window.set_continuous(true);
+        client.roundtrip();
+        compositor.configure(8, 4);
+        client.roundtrip();
+        let mut time = 0;
+        let mut frame = |rect: Option<Rect>| {
+            if let Some(rect) = rect {
+                let color = 0xFF00_0000 | ((shapes.borrow().len() as u32 + 1) * 0x30);
+                shapes.borrow_mut().push((rect, color));
+            }
+            time += 16;
+            compositor.frame_done(time);
+            client.roundtrip();
+            let log = compositor.log();
+            let commit = log.commits.last().unwrap().clone();
+            if let Some(buffer) = &commit.buffer {
+                for (x, y) in (0..8).flat_map(|x| (0..4).map(move |y| (x, y))) {
+                    let expected = Shapes::color(&shapes.borrow(), x, y);
+                    assert_eq!(buffer.argb(x as i32, y as i32), expected, "at {}, {}", x, y);
+                }
+            }
+            let damage: Vec<_> =
+                commit.buffer_damage.iter().map(|r| (r.x, r.y, r.width, r.height)).collect();
+            (repainted.borrow_mut().pop(), damage, log.count("wl_shm_pool.create_buffer"))
+        };
+        let everything = Rect::new(0, 0, 8, 4);
+        assert_eq!(repainted.borrow_mut().pop(), Some(vec![everything]));
+
+        // Released right away, so the one buffer is reused.
+        let change = Rect::new(0, 0, 2, 2);
+        assert_eq!(frame(Some(change)), (Some(vec![change]), vec![(0, 0, 2, 2)], 1));
+        // Nothing changed, nothing attached.
+        assert_eq!(frame(None), (None, vec![], 1));
+
+        // The first buffer is busy, a new one is drawn entirely but only
+        // what changed is damaged.
+        compositor.hold_buffers(true);
+        let change = Rect::new(4, 0, 2, 2);
+        assert_eq!(frame(Some(change)), (Some(vec![change]), vec![(4, 0, 2, 2)], 1));
+        let change = Rect::new(6, 2, 2, 2);
+        assert_eq!(frame(Some(change)), (Some(vec![everything]), vec![(6, 2, 2, 2)], 2));
+
+        // The first buffer missed the frame before.
+        compositor.hold_buffers(false);
+        let change = Rect::new(0, 3, 1, 1);
+        let missed = Rect::new(6, 2, 2, 2);
+        assert_eq!(frame(Some(change)), (Some(vec![change, missed]), vec![(0, 3, 1, 1)], 2));
+    }
+
+    #[test]
+    fn draws_only_when_asked_to() {
+        let (compositor, mut client, window) = window();
+        compositor.configure(16, 16);
+        client.roundtrip();
+        compositor.frame_done(16);
+        client.roundtrip();
+        let log = compositor.log();
+        assert_eq!((log.commits.len(), log.count("wl_surface.frame")), (2, 0));
+
+        // Asking twice before the frame still draws once.
+        window.request_redraw();
+        window.request_redraw();
+        client.roundtrip();
+        let log = compositor.log();
+        assert_eq!((log.commits.len(), log.count("wl_surface.frame")), (3, 1));
+        assert!(log.commits[2].buffer.is_none(), "drew before the frame callback");
+        compositor.frame_done(32);
+        client.roundtrip();
+        compositor.frame_done(48);
+        client.roundtrip();
+        let log = compositor.log();
+        assert_eq!((log.commits.len(), log.count("wl_surface.frame")), (4, 1));
+        assert!(log.commits[3].buffer.is_some());
+
+        // Resizing redraws on its own, in the commit after the ack.
+        compositor.configure(20, 10);
+        client.roundtrip();
+        let log = compositor.log();
+        assert_eq!(log.commits.len(), 5);
+        let buffer = log.commits[4].buffer.as_ref().expect("the new size waited for a frame");
+        assert_eq!((buffer.width, buffer.height), (20, 10));
+
+        // Configures keeping the size wait for the frame callback.
+        compositor.configure(20, 10);
+        client.roundtrip();
+        let log = compositor.log();
+        assert_eq!(log.commits.len(), 6);
+        assert!(log.commits[5].buffer.is_none());
+    }
+
+    #[test]
+    fn close_is_remembered() {
+        let (compositor, mut client, window) = window();
+        assert!(!window.is_closed());
+        compositor.close();
+        client.roundtrip();
+        assert!(window.is_closed());
+    }
+}